@@ -1,3 +1,5 @@
+use crate::region::Region;
+
 const LENGTH_TABLE: [u8; 32] = [
     10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
     192, 24, 72, 26, 16, 28, 32, 30,
@@ -12,6 +14,23 @@ const DMC_PERIOD_TABLE: [u16; 16] = [
     428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
 ];
 
+const DMC_PERIOD_TABLE_PAL: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+const NOISE_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2032, 4064,
+];
+
+const NOISE_PERIOD_TABLE_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+/// Frame counter steps: quarter, half, quarter, 4-step IRQ, 4-step end,
+/// 5-step end (in CPU cycles since the last reset).
+const FRAME_STEPS: [u32; 6] = [7457, 14913, 22371, 29828, 29829, 37281];
+const FRAME_STEPS_PAL: [u32; 6] = [8313, 16627, 24939, 33252, 33253, 41565];
+
 pub struct Apu {
     // Pulse 1
     pulse1_enabled: bool,
@@ -98,11 +117,27 @@ pub struct Apu {
     // Frame Counter
     frame_counter_mode: u8, // 0: 4-step, 1: 5-step
     frame_counter_cycle: u32,
+    frame_steps: &'static [u32; 6],
+    noise_periods: &'static [u16; 16],
+    dmc_periods: &'static [u16; 16],
     irq_inhibit: bool,
     irq_pending: bool,
 }
 
 impl Apu {
+    /// Select the NTSC or PAL frame counter and noise/DMC period tables.
+    pub fn set_region(&mut self, region: Region) {
+        if region.has_pal_apu() {
+            self.frame_steps = &FRAME_STEPS_PAL;
+            self.noise_periods = &NOISE_PERIOD_TABLE_PAL;
+            self.dmc_periods = &DMC_PERIOD_TABLE_PAL;
+        } else {
+            self.frame_steps = &FRAME_STEPS;
+            self.noise_periods = &NOISE_PERIOD_TABLE;
+            self.dmc_periods = &DMC_PERIOD_TABLE;
+        }
+    }
+
     pub fn new() -> Self {
         Self {
             pulse1_enabled: false,
@@ -184,6 +219,9 @@ impl Apu {
 
             frame_counter_mode: 0,
             frame_counter_cycle: 0,
+            frame_steps: &FRAME_STEPS,
+            noise_periods: &NOISE_PERIOD_TABLE,
+            dmc_periods: &DMC_PERIOD_TABLE,
             irq_inhibit: true,
             irq_pending: false,
         }
@@ -276,10 +314,7 @@ impl Apu {
             0x400E => {
                 // Noise: Period, Mode
                 self.noise_mode = (data & 0x80) != 0;
-                let noise_table = [
-                    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2032, 4064,
-                ];
-                self.noise_timer_period = noise_table[(data & 0x0F) as usize];
+                self.noise_timer_period = self.noise_periods[(data & 0x0F) as usize];
             }
             0x400F => {
                 // Noise: Length counter load
@@ -291,7 +326,7 @@ impl Apu {
             0x4010 => {
                 self.dmc_irq_enable = (data & 0x80) != 0;
                 self.dmc_loop_flag = (data & 0x40) != 0;
-                self.dmc_timer_period = DMC_PERIOD_TABLE[(data & 0x0F) as usize];
+                self.dmc_timer_period = self.dmc_periods[(data & 0x0F) as usize];
                 if !self.dmc_irq_enable {
                     self.dmc_irq_pending = false;
                 }
@@ -523,48 +558,24 @@ impl Apu {
             }
 
             self.frame_counter_cycle += 1;
-            // ... (rest of frame counter logic)
-            if self.frame_counter_mode == 0 {
-                // 4-step mode
-                match self.frame_counter_cycle {
-                    7457 => self.clock_envelopes(),
-                    14913 => {
-                        self.clock_envelopes();
-                        self.clock_length_counters();
-                        self.clock_sweeps();
-                    }
-                    22371 => self.clock_envelopes(),
-                    29828 => {
-                        if !self.irq_inhibit {
-                            self.irq_pending = true;
-                        }
-                    }
-                    29829 => {
-                        self.clock_envelopes();
-                        self.clock_length_counters();
-                        self.clock_sweeps();
-                        self.frame_counter_cycle = 0;
-                    }
-                    _ => {}
-                }
+            let steps = self.frame_steps;
+            let cycle = self.frame_counter_cycle;
+            let end = if self.frame_counter_mode == 0 {
+                steps[4] // 4-step mode
             } else {
-                // 5-step mode
-                match self.frame_counter_cycle {
-                    7457 => self.clock_envelopes(),
-                    14913 => {
-                        self.clock_envelopes();
-                        self.clock_length_counters();
-                        self.clock_sweeps();
-                    }
-                    22371 => self.clock_envelopes(),
-                    37281 => {
-                        self.clock_envelopes();
-                        self.clock_length_counters();
-                        self.clock_sweeps();
-                        self.frame_counter_cycle = 0;
-                    }
-                    _ => {}
+                steps[5] // 5-step mode
+            };
+            if cycle == steps[0] || cycle == steps[2] {
+                self.clock_envelopes();
+            } else if cycle == steps[1] || cycle == end {
+                self.clock_envelopes();
+                self.clock_length_counters();
+                self.clock_sweeps();
+                if cycle == end {
+                    self.frame_counter_cycle = 0;
                 }
+            } else if self.frame_counter_mode == 0 && cycle == steps[3] && !self.irq_inhibit {
+                self.irq_pending = true;
             }
         }
     }
@@ -761,3 +772,54 @@ impl Apu {
         self.irq_pending || self.dmc_irq_pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CPU cycles until the 4-step frame counter raises its IRQ.
+    fn cycles_to_frame_irq(apu: &mut Apu) -> u32 {
+        apu.write_register(0x4017, 0x00);
+        let mut cycles = 0;
+        while !apu.is_irq_pending() {
+            apu.tick(1);
+            cycles += 1;
+            assert!(cycles < 50_000, "frame IRQ never fired");
+        }
+        cycles
+    }
+
+    #[test]
+    fn test_frame_irq_timing_per_region() {
+        let mut apu = Apu::new();
+        assert_eq!(cycles_to_frame_irq(&mut apu), 29828);
+
+        let mut apu = Apu::new();
+        apu.set_region(Region::Pal);
+        assert_eq!(cycles_to_frame_irq(&mut apu), 33252);
+
+        // Dendy keeps the NTSC APU.
+        let mut apu = Apu::new();
+        apu.set_region(Region::Dendy);
+        assert_eq!(cycles_to_frame_irq(&mut apu), 29828);
+    }
+
+    #[test]
+    fn test_pal_noise_and_dmc_periods() {
+        let mut apu = Apu::new();
+        apu.write_register(0x400E, 0x0F);
+        apu.write_register(0x4010, 0x00);
+        assert_eq!(apu.noise_timer_period, 4064);
+        assert_eq!(apu.dmc_timer_period, 428);
+
+        apu.set_region(Region::Pal);
+        apu.write_register(0x400E, 0x0F);
+        apu.write_register(0x4010, 0x00);
+        assert_eq!(apu.noise_timer_period, 3778);
+        assert_eq!(apu.dmc_timer_period, 398);
+        apu.write_register(0x400E, 0x82);
+        apu.write_register(0x4010, 0x0F);
+        assert_eq!(apu.noise_timer_period, 14);
+        assert_eq!(apu.dmc_timer_period, 50);
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{Error, Result};
+use rust_emu::joypad::JoypadButton;
+use rust_emu::region::Region;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 240;
+const DEFAULT_FRAMES: u32 = 600;
+
+const USAGE: &str = "Usage: headless <rom.nes> [--frames N] [--input script.txt] \
+[--png out.png] [--wav out.wav] [--ram out.bin] [--prg-ram out.bin] [--region ntsc|pal|dendy] [--patch fix.ips] [--game-db nes20db.xml]";
+
+struct Options {
+    rom_path: PathBuf,
+    frames: u32,
+    input_path: Option<PathBuf>,
+    png_path: Option<PathBuf>,
+    wav_path: Option<PathBuf>,
+    ram_path: Option<PathBuf>,
+    prg_ram_path: Option<PathBuf>,
+    region: Option<Region>,
+    patch_path: Option<PathBuf>,
+    game_db_path: Option<PathBuf>,
+}
+
+/// One line of an input script: from `frame` onwards, `buttons` are held.
+#[derive(Debug)]
+struct InputEvent {
+    frame: u32,
+    buttons: u8,
+}
+
+fn parse_args() -> Result<Options> {
+    let mut rom_path: Option<PathBuf> = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut input_path = None;
+    let mut png_path = None;
+    let mut wav_path = None;
+    let mut ram_path = None;
+    let mut prg_ram_path = None;
+    let mut region = None;
+    let mut patch_path = None;
+    let mut game_db_path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| Error::msg(format!("{} requires a value", name)))
+        };
+        match arg.as_str() {
+            "--frames" => {
+                frames = value("--frames")?
+                    .parse()
+                    .map_err(|_| Error::msg("--frames expects a number"))?
+            }
+            "--input" => input_path = Some(PathBuf::from(value("--input")?)),
+            "--png" => png_path = Some(PathBuf::from(value("--png")?)),
+            "--wav" => wav_path = Some(PathBuf::from(value("--wav")?)),
+            "--ram" => ram_path = Some(PathBuf::from(value("--ram")?)),
+            "--prg-ram" => prg_ram_path = Some(PathBuf::from(value("--prg-ram")?)),
+            "--patch" => patch_path = Some(PathBuf::from(value("--patch")?)),
+            "--game-db" => game_db_path = Some(PathBuf::from(value("--game-db")?)),
+            "--region" => region = Some(value("--region")?.parse().map_err(Error::msg)?),
+            _ if !arg.starts_with("--") && rom_path.is_none() => {
+                rom_path = Some(PathBuf::from(arg))
+            }
+            _ => return Err(Error::msg(format!("Unknown argument: {}", arg))),
+        }
+    }
+
+    Ok(Options {
+        rom_path: rom_path.ok_or_else(|| Error::msg(USAGE))?,
+        frames,
+        input_path,
+        png_path,
+        wav_path,
+        ram_path,
+        prg_ram_path,
+        region,
+        patch_path,
+        game_db_path,
+    })
+}
+
+fn parse_button(name: &str) -> Option<JoypadButton> {
+    match name.to_ascii_uppercase().as_str() {
+        "A" => Some(JoypadButton::BUTTON_A),
+        "B" => Some(JoypadButton::BUTTON_B),
+        "SELECT" => Some(JoypadButton::SELECT),
+        "START" => Some(JoypadButton::START),
+        "UP" => Some(JoypadButton::UP),
+        "DOWN" => Some(JoypadButton::DOWN),
+        "LEFT" => Some(JoypadButton::LEFT),
+        "RIGHT" => Some(JoypadButton::RIGHT),
+        _ => None,
+    }
+}
+
+/// Parse an input script. Each non-empty line is `<frame> [BUTTON ...]`; the
+/// listed buttons stay held from that frame until the next line. `#` starts a comment.
+///
+/// ```text
+/// # press Start for two frames, then hold Right+A
+/// 60 START
+/// 62
+/// 120 RIGHT A
+/// ```
+fn parse_input_script(text: &str) -> Result<Vec<InputEvent>> {
+    let mut events = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let frame = fields
+            .next()
+            .and_then(|f| f.parse::<u32>().ok())
+            .ok_or_else(|| Error::msg(format!("line {}: expected frame number", line_no + 1)))?;
+        let mut buttons = JoypadButton::empty();
+        for name in fields {
+            buttons |= parse_button(name).ok_or_else(|| {
+                Error::msg(format!("line {}: unknown button '{}'", line_no + 1, name))
+            })?;
+        }
+        events.push(InputEvent {
+            frame,
+            buttons: buttons.bits(),
+        });
+    }
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+fn apply_buttons(nes: &mut rust_emu::Nes, buttons: u8) {
+    nes.set_joypad_button(JoypadButton::all(), false);
+    nes.set_joypad_button(JoypadButton::from_bits_truncate(buttons), true);
+}
+
+fn write_png(path: &Path, rgba: &[u8]) -> Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(Error::msg)?;
+    writer.write_image_data(rgba).map_err(Error::msg)?;
+    Ok(())
+}
+
+/// Write mono 16-bit PCM WAV.
+fn write_wav(path: &Path, samples: &[f32], sample_rate: u32) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let data_len = (samples.len() * 2) as u32;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?; // Chunk size
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&1u16.to_le_bytes())?; // Mono
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?; // Byte rate
+    out.write_all(&2u16.to_le_bytes())?; // Block align
+    out.write_all(&16u16.to_le_bytes())?; // Bits per sample
+
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.write_all(&value.to_le_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<()> {
+    if let Some(path) = options.game_db_path.as_ref() {
+        rust_emu::game_db::load_nes20db(&std::fs::read_to_string(path)?)?;
+    }
+    let mut rom_data = std::fs::read(&options.rom_path).map_err(Error::msg)?;
+    if let Some(path) = options.patch_path.as_ref() {
+        rom_data = rust_emu::cartridge::apply_ips(&rom_data, &std::fs::read(path)?)?;
+    }
+    let script = match options.input_path.as_ref() {
+        Some(path) => parse_input_script(&std::fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+
+    let mut nes = rust_emu::Nes::new_with_rom(&rom_data)?;
+    if let Some(region) = options.region {
+        nes.set_region(region);
+    }
+    nes.reset();
+
+    let mut audio = Vec::new();
+    let mut next_event = 0;
+
+    for frame in 0..options.frames {
+        while next_event < script.len() && script[next_event].frame <= frame {
+            apply_buttons(&mut nes, script[next_event].buttons);
+            next_event += 1;
+        }
+
+        nes.run_frame();
+        audio.extend(nes.get_audio_samples());
+    }
+
+    if let Some(path) = options.png_path.as_ref() {
+        let mut frame = vec![0; (WIDTH * HEIGHT * 4) as usize];
+        nes.draw(&mut frame);
+        write_png(path, &frame)?;
+    }
+    if let Some(path) = options.wav_path.as_ref() {
+        write_wav(path, &audio, nes.audio_sample_rate as u32)?;
+    }
+    if let Some(path) = options.ram_path.as_ref() {
+        std::fs::write(path, nes.bus.cpu_vram)?;
+    }
+    if let Some(path) = options.prg_ram_path.as_ref() {
+        std::fs::write(path, &nes.bus.prg_ram)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}: {}", options.rom_path.display(), err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames_and_buttons(events: &[InputEvent]) -> Vec<(u32, u8)> {
+        events
+            .iter()
+            .map(|event| (event.frame, event.buttons))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_input_script() {
+        let script = "# press Start for two frames, then hold Right+A\n\
+                      60 START\n\
+                      \n\
+                      62 # release\n\
+                      120 right A\n";
+        let events = parse_input_script(script).unwrap();
+        let right_a = (JoypadButton::RIGHT | JoypadButton::BUTTON_A).bits();
+        assert_eq!(
+            frames_and_buttons(&events),
+            [(60, JoypadButton::START.bits()), (62, 0), (120, right_a)]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_script_sorts_by_frame() {
+        let events = parse_input_script("30 B\n10 A\n20\n").unwrap();
+        assert_eq!(
+            frames_and_buttons(&events),
+            [
+                (10, JoypadButton::BUTTON_A.bits()),
+                (20, 0),
+                (30, JoypadButton::BUTTON_B.bits())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_script_errors() {
+        let err = parse_input_script("10 A\n20 TURBO\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: unknown button 'TURBO'");
+        let err = parse_input_script("# comment\nSTART\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected frame number");
+    }
+}
@@ -1,13 +1,28 @@
 use crate::apu::Apu;
 use crate::cartridge::Mirroring;
+use crate::clock::MasterClock;
 use crate::joypad::Joypad;
 use crate::ppu::Ppu;
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A single memory access, recorded for debugger watchpoints.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MemoryAccess {
+    pub addr: u16,
+    pub value: u8,
+    pub kind: AccessKind,
+}
+
 pub struct Bus {
     pub cpu_vram: [u8; 2048],
     pub prg_rom: Vec<u8>,
     pub ppu: Ppu,
-    pub cycles: usize, // Accumulated cycles (e.g. from DMA)
+    pub cycles: usize, // CPU cycles stolen by DMA since the last `poll_dma_cycles`
     pub joypad1: Joypad,
     pub apu: Apu,
     pub mapper: u8,
@@ -27,10 +42,19 @@ pub struct Bus {
     pub mmc3_bank_select: u8,
     pub mmc3_bank_data: [u8; 8],
     pub mmc3_prg_ram_protect: u8,
-    /// Tracks how many PPU cycles have been "caught up" during the current CPU instruction.
-    /// This is used to simulate parallel CPU/PPU execution: the PPU advances 3 cycles
-    /// for every CPU memory access, so register reads see the correct PPU state.
-    pub ppu_cycles_advanced: u16,
+    /// Divides the master clock between the CPU and PPU.
+    pub master_clock: MasterClock,
+    /// CPU cycles clocked so far, including DMA cycles. DMA reads only
+    /// happen on even ("get") cycles and writes on odd ("put") cycles.
+    pub cycle_count: u64,
+    /// Page written to $4014, waiting for the CPU's next read cycle to halt it.
+    pub oam_dma_page: Option<u8>,
+    /// When set, every CPU read/write is appended here (used by the debugger).
+    pub access_log: Option<Vec<MemoryAccess>>,
+    /// Last value on the CPU data bus, returned by reads nothing answers.
+    /// Unlike the PPU latch it is not decayed: the bus capacitance only
+    /// holds a value for a cycle or two, but every cycle drives it again.
+    pub open_bus: u8,
 }
 
 impl Bus {
@@ -62,7 +86,11 @@ impl Bus {
             mmc3_bank_select: 0,
             mmc3_bank_data: [0; 8],
             mmc3_prg_ram_protect: 0x80, // PRG RAM enabled by default
-            ppu_cycles_advanced: 0,
+            master_clock: MasterClock::ntsc(),
+            cycle_count: 0,
+            oam_dma_page: None,
+            access_log: None,
+            open_bus: 0,
         };
         bus.sync_mmc1_state_to_ppu();
         if bus.mapper == 1 && bus.mmc1_debug {
@@ -71,29 +99,91 @@ impl Bus {
         bus
     }
 
+    /// One CPU read cycle. A pending OAM or DMC DMA halts the CPU here first,
+    /// since the 2A03 can only be halted on a read.
     pub fn read(&mut self, addr: u16) -> u8 {
-        // PPU catch-up: advance PPU 3 cycles per CPU memory access cycle
-        // This simulates parallel CPU/PPU execution so PPU register reads
-        // see the correct PPU state (e.g., VBlank flag at scanline 241).
-        self.ppu_cycles_advanced += 3;
-        self.ppu.tick(3);
+        if self.oam_dma_page.is_some() || self.apu.dmc_needs_fetch() {
+            self.run_dma(addr);
+        }
 
-        match addr {
+        self.clock();
+        let value = self.read_raw(addr);
+        self.log_access(addr, value, AccessKind::Read);
+        value
+    }
+
+    /// Advance everything but the CPU by one CPU cycle: the PPU by however
+    /// many dots the master clock puts in it, the APU and any cartridge
+    /// hardware by one cycle. Every CPU and DMA cycle goes through here, so
+    /// PPU register reads see the PPU state of that cycle (e.g. the VBlank
+    /// flag at scanline 241).
+    fn clock(&mut self) {
+        let dots = self.master_clock.cpu_cycle();
+        self.ppu.tick(dots);
+        self.apu.tick(1);
+        self.clock_cartridge();
+        self.cycle_count += 1;
+    }
+
+    /// Per-CPU-cycle hook for mapper hardware: CPU-cycle IRQ counters and
+    /// expansion audio. None of the supported mappers need it yet; MMC3's
+    /// counter is clocked by PPU A12 inside the PPU.
+    fn clock_cartridge(&mut self) {}
+
+    /// Whether the cartridge is asserting /IRQ.
+    fn cartridge_irq(&self) -> bool {
+        match self.mapper {
+            4 => self.ppu.mmc3_irq_pending,
+            _ => false,
+        }
+    }
+
+    /// Expansion audio from the cartridge, mixed after the APU.
+    fn cartridge_audio(&self) -> f32 {
+        0.0
+    }
+
+    /// Mixed APU and cartridge audio output.
+    pub fn audio_output(&self) -> f32 {
+        self.apu.output() + self.cartridge_audio()
+    }
+
+    /// The memory or register access itself, with side effects but without
+    /// advancing time.
+    fn read_raw(&mut self, addr: u16) -> u8 {
+        // $4015 is read inside the CPU and never reaches the external data
+        // bus, so it leaves open bus as it was. Bit 5 is not driven.
+        if addr == 0x4015 {
+            return self.apu.read_status() | (self.open_bus & 0x20);
+        }
+        let value = match addr {
             0x0000..=0x1FFF => self.cpu_vram[(addr as usize) & 0x7FF],
             0x2000..=0x3FFF => {
                 let reg = addr & 0x2007;
                 self.ppu.read_register(reg)
             }
-            0x4014 => 0, // DMA register
-            0x4015 => self.apu.read_status(),
-            0x4016 => self.joypad1.read(),
-            0x4017 => 0, // Joypad 2 (not implemented)
-            0x6000..=0x7FFF => self.read_prg_ram(addr),
+            // Controller ports only drive the low bits
+            0x4016 => (self.open_bus & 0xE0) | self.joypad1.read(),
+            0x4017 => self.open_bus & 0xE0, // Joypad 2 (not implemented)
+            0x6000..=0x7FFF => self.read_prg_ram(addr).unwrap_or(self.open_bus),
             0x8000..=0xFFFF => self.read_prg_rom(addr),
-            _ => 0,
+            _ => self.open_bus, // $4014 and unmapped addresses
+        };
+        self.open_bus = value;
+        value
+    }
+
+    fn log_access(&mut self, addr: u16, value: u8, kind: AccessKind) {
+        if let Some(log) = self.access_log.as_mut() {
+            log.push(MemoryAccess { addr, value, kind });
         }
     }
 
+    /// Level of the shared /IRQ line: APU frame counter or DMC, or the cartridge.
+    pub fn irq_line(&self) -> bool {
+        self.apu.is_irq_pending() || self.cartridge_irq()
+    }
+
     pub fn begin_cpu_step(&mut self) {
         self.cpu_step_counter = self.cpu_step_counter.wrapping_add(1);
     }
@@ -104,20 +194,31 @@ impl Bus {
         match addr {
             0x0000..=0x1FFF => self.cpu_vram[(addr as usize) & 0x7FF],
             0x2000..=0x3FFF => self.ppu.peek_register(addr & 0x2007),
-            0x4014 => 0,
-            0x4015 => 0, // Don't clear APU status flags
-            0x4016 => 0, // Don't advance joypad shift register
-            0x4017 => 0,
-            0x6000..=0x7FFF => self.read_prg_ram(addr),
+            0x4015 => self.open_bus & 0x20, // Don't clear APU status flags
+            0x4016 | 0x4017 => self.open_bus & 0xE0, // Don't advance joypad shift register
+            0x6000..=0x7FFF => self.read_prg_ram(addr).unwrap_or(self.open_bus),
             0x8000..=0xFFFF => self.read_prg_rom(addr),
-            _ => 0,
+            _ => self.open_bus,
+        }
+    }
+
+    /// Debugger write: stores into CPU RAM or PRG RAM without clocking the
+    /// system or touching registers. Returns `false`, storing nothing, for
+    /// registers, ROM and unmapped addresses.
+    pub fn poke(&mut self, addr: u16, data: u8) -> bool {
+        match addr {
+            0x0000..=0x1FFF => self.cpu_vram[(addr as usize) & 0x7FF] = data,
+            0x6000..=0x7FFF if self.read_prg_ram(addr).is_some() => self.write_prg_ram(addr, data),
+            _ => return false,
         }
+        true
     }
 
+    /// One CPU write cycle.
     pub fn write(&mut self, addr: u16, data: u8) {
-        // PPU catch-up: advance PPU 3 cycles per CPU memory access cycle
-        self.ppu_cycles_advanced += 3;
-        self.ppu.tick(3);
+        self.clock();
+        self.log_access(addr, data, AccessKind::Write);
+        self.open_bus = data;
 
         match addr {
             0x0000..=0x1FFF => self.cpu_vram[(addr as usize) & 0x7FF] = data,
@@ -126,7 +227,7 @@ impl Bus {
                 self.ppu.write_register(reg, data)
             }
             0x4014 => {
-                self.dma_transfer(data);
+                self.oam_dma_page = Some(data);
             }
             0x4016 => {
                 self.joypad1.write(data);
@@ -196,15 +297,16 @@ impl Bus {
         }
     }
 
-    fn read_prg_ram(&self, addr: u16) -> u8 {
+    /// `None` when no PRG RAM answers, leaving the bus open.
+    fn read_prg_ram(&self, addr: u16) -> Option<u8> {
         if self.prg_ram.is_empty() {
-            return 0;
+            return None;
         }
         if self.mapper == 1 && !self.mmc1_is_prg_ram_enabled() {
-            return 0;
+            return None;
         }
         let offset = (addr as usize - 0x6000) % self.prg_ram.len();
-        self.prg_ram[offset]
+        Some(self.prg_ram[offset])
     }
 
     fn write_prg_ram(&mut self, addr: u16, data: u8) {
@@ -218,19 +320,80 @@ impl Bus {
         self.prg_ram[offset] = data;
     }
 
-    fn dma_transfer(&mut self, data: u8) {
-        let hi = (data as u16) << 8;
-        for i in 0..256 {
-            let addr = hi + i;
-            let byte = self.read(addr);
-            self.ppu.oam_data = byte;
-            // Writes to OAMDATA ($2004) automatically increment OAMADDR?
-            // Actually DMA writes directly to OAM memory, bypassing OAMADDR increment usually?
-            // "The CPU is suspended... 513 or 514 cycles... writes to $2004".
-            // So it effectively writes to $2004 256 times.
-            self.ppu.write_register(0x2004, byte);
+    /// Run pending OAM and DMC DMA while the CPU is halted on a read of
+    /// `cpu_addr`. The first (halt) cycle performs the CPU's read and throws
+    /// it away; later idle cycles repeat it. OAM DMA alternates a get cycle
+    /// reading the page with a put cycle writing $2004, after one alignment
+    /// cycle if it would start on a put: 513 or 514 cycles. DMC DMA needs a
+    /// halt and a dummy cycle before its read, which must land on a get: 3 or
+    /// 4 cycles alone, fewer when it overlaps OAM DMA, whose cycles count as
+    /// the DMC's halt and dummy cycles.
+    fn run_dma(&mut self, cpu_addr: u16) {
+        let mut oam_page = self.oam_dma_page.take();
+        let mut oam_index: u16 = 0;
+        let mut oam_latch: Option<u8> = None;
+        let mut dmc_active = false;
+        let mut dmc_dummy = false;
+        let mut dmc_halt = false;
+
+        // Halt cycle. This read has its side effects, while the repeated
+        // idle reads of $4016/$4017 below don't: the joypad's /OE line just
+        // stays asserted. When a DMC read interrupts that, the CPU's own read
+        // clocks the controller a second time and a bit is lost.
+        self.dma_clock();
+        self.read_raw(cpu_addr);
+        if self.apu.dmc_needs_fetch() {
+            dmc_active = true;
+            dmc_dummy = true;
         }
-        self.cycles += 514; // Approx for read/write alignment (odd/even cycles matters but 514 is good avg)
+
+        while dmc_active || oam_page.is_some() {
+            let get = self.cycle_count.is_multiple_of(2);
+            let dmc_ready = dmc_active && !dmc_halt && !dmc_dummy;
+            if dmc_halt {
+                dmc_halt = false;
+            } else if dmc_dummy {
+                dmc_dummy = false;
+            }
+
+            self.dma_clock();
+            match (get, oam_page) {
+                (true, _) if dmc_ready => {
+                    let data = self.read_raw(self.apu.dmc_fetch_address());
+                    self.apu.dmc_provide_sample(data);
+                    dmc_active = false;
+                }
+                (true, Some(page)) if oam_latch.is_none() => {
+                    oam_latch = Some(self.read_raw(((page as u16) << 8) | oam_index));
+                }
+                (false, Some(_)) if oam_latch.is_some() => {
+                    let byte = oam_latch.take().unwrap_or(0);
+                    self.ppu.write_register(0x2004, byte);
+                    oam_index += 1;
+                    if oam_index == 256 {
+                        oam_page = None;
+                    }
+                }
+                _ => {
+                    // Halted idle or alignment cycle.
+                    if !matches!(cpu_addr, 0x4016 | 0x4017) {
+                        self.read_raw(cpu_addr);
+                    }
+                }
+            }
+
+            // The DMC can ask for its next byte while OAM DMA is running.
+            if !dmc_active && self.apu.dmc_needs_fetch() {
+                dmc_active = true;
+                dmc_halt = true;
+                dmc_dummy = true;
+            }
+        }
+    }
+
+    fn dma_clock(&mut self) {
+        self.clock();
+        self.cycles += 1;
     }
 
     pub fn poll_dma_cycles(&mut self) -> usize {
@@ -239,18 +402,6 @@ impl Bus {
         cycles
     }
 
-    pub fn tick_apu(&mut self, cycles: u16) {
-        for _ in 0..cycles {
-            if self.apu.dmc_needs_fetch() {
-                let addr = self.apu.dmc_fetch_address();
-                let data = self.read(addr);
-                self.apu.dmc_provide_sample(data);
-                self.cycles += 4;
-            }
-            self.apu.tick(1);
-        }
-    }
-
     fn read_prg_rom(&self, addr: u16) -> u8 {
         let mut addr = addr - 0x8000;
 
@@ -663,4 +814,95 @@ mod tests {
         let val2 = bus.ppu.read_register(0x2007);
         assert_eq!(val2, 2); // Bank 2 data
     }
+
+    fn create_test_bus() -> Bus {
+        let ppu = Ppu::new(Mirroring::Horizontal, vec![0; 2048]);
+        Bus::new(ppu, vec![0; 0x8000], 0, 8192, false)
+    }
+
+    fn start_dmc_sample(bus: &mut Bus) {
+        bus.write(0x4012, 0x00); // $C000
+        bus.write(0x4013, 0x00); // 1 byte
+        bus.write(0x4015, 0x10);
+    }
+
+    #[test]
+    fn test_oam_dma_takes_513_or_514_cycles() {
+        for (start_cycle, expected) in [(1, 513), (2, 514)] {
+            let mut bus = create_test_bus();
+            for i in 0..256u16 {
+                bus.write(0x0300 + i, i as u8);
+            }
+            bus.write(0x4014, 0x03);
+            assert_eq!(bus.poll_dma_cycles(), 0);
+
+            bus.cycle_count = start_cycle;
+            bus.read(0x0000);
+            assert_eq!(bus.poll_dma_cycles(), expected);
+            assert_eq!(bus.ppu.oam[0x00], 0x00);
+            assert_eq!(bus.ppu.oam[0x7F], 0x7F);
+            assert_eq!(bus.ppu.oam[0xFF], 0xFF);
+        }
+    }
+
+    #[test]
+    fn test_dmc_dma_stalls_three_or_four_cycles() {
+        for (start_cycle, expected) in [(1, 4), (2, 3)] {
+            let mut bus = create_test_bus();
+            start_dmc_sample(&mut bus);
+            assert!(bus.apu.dmc_needs_fetch());
+
+            bus.cycle_count = start_cycle;
+            bus.read(0x0000);
+            assert_eq!(bus.poll_dma_cycles(), expected);
+            assert!(!bus.apu.dmc_needs_fetch());
+        }
+    }
+
+    #[test]
+    fn test_dmc_dma_during_oam_dma_costs_two_cycles() {
+        let mut bus = create_test_bus();
+        start_dmc_sample(&mut bus);
+        bus.write(0x4014, 0x03);
+        bus.cycle_count = 1;
+        bus.read(0x0000);
+        // The DMC's halt and dummy cycles overlap OAM DMA; its read steals
+        // one get cycle and OAM needs one more cycle to realign.
+        assert_eq!(bus.poll_dma_cycles(), 515);
+    }
+
+    #[test]
+    fn test_dmc_dma_deletes_joypad_bit() {
+        use crate::joypad::JoypadButton;
+
+        let mut bus = create_test_bus();
+        bus.joypad1.set_button_status(JoypadButton::BUTTON_A, true);
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+        start_dmc_sample(&mut bus);
+
+        // The halt cycle's read of $4016 shifts out A, so the CPU sees B.
+        assert_eq!(bus.read(0x4016) & 0x01, 0);
+
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+        assert_eq!(bus.read(0x4016) & 0x01, 1);
+    }
+
+    #[test]
+    fn test_open_bus_reads() {
+        let mut bus = create_test_bus();
+        bus.prg_rom[0x1234] = 0x47;
+        bus.read(0x9234);
+        assert_eq!(bus.read(0x5000), 0x47);
+        assert_eq!(bus.peek(0x4014), 0x47);
+        // Controller reads keep the top bits of the last value.
+        assert_eq!(bus.read(0x4017), 0x40);
+        bus.write(0x0000, 0xFF);
+        assert_eq!(bus.read(0x4016), 0xE0);
+        // $4015 doesn't change open bus.
+        bus.read(0x9234);
+        assert_eq!(bus.read(0x4015), 0x00);
+        assert_eq!(bus.read(0x5000), 0x47);
+    }
 }
@@ -1,4 +1,46 @@
-#[derive(Debug, PartialEq, Clone, Copy)]
+use crate::game_db;
+use crate::region::Region;
+use std::fmt;
+
+/// Mapper numbers `Bus` implements. Anything else is rejected on load rather
+/// than run as NROM.
+pub const SUPPORTED_MAPPERS: [u8; 5] = [0, 1, 2, 3, 4];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RomError {
+    /// The file does not start with "NES\x1A".
+    BadMagic,
+    /// The file is shorter than its header says.
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    UnsupportedMapper(u16),
+    /// A header feature this emulator cannot handle.
+    UnsupportedFormat(&'static str),
+    /// An IPS patch that is malformed or writes past its own records.
+    BadPatch(&'static str),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::BadMagic => write!(f, "File is not in iNES format"),
+            RomError::Truncated { expected, actual } => write!(
+                f,
+                "File is truncated: header needs {} bytes but only {} are present",
+                expected, actual
+            ),
+            RomError::UnsupportedMapper(mapper) => write!(f, "Mapper {} is not supported", mapper),
+            RomError::UnsupportedFormat(reason) => write!(f, "Unsupported ROM format: {}", reason),
+            RomError::BadPatch(reason) => write!(f, "Invalid IPS patch: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Mirroring {
     Vertical,
     Horizontal,
@@ -14,25 +56,138 @@ pub struct Rom {
     pub screen_mirroring: Mirroring,
     pub has_battery: bool,
     pub prg_ram_size: usize,
+    pub region: Region,
+}
+
+/// NES 2.0 ROM size from the header's LSB byte and MSB nibble. An MSB nibble
+/// of 0xF selects the exponent-multiplier form, 2^E * (MM * 2 + 1) bytes.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, RomError> {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0b11) as usize * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .filter(|&size| size <= isize::MAX as usize)
+            .ok_or(RomError::UnsupportedFormat("ROM size is too large"))
+    } else {
+        Ok((((msb as usize) << 8) | lsb as usize) * unit)
+    }
+}
+
+/// Apply an IPS patch: "PATCH", then records of a 3-byte offset and 2-byte
+/// length followed by that many bytes, or a zero length followed by a 2-byte
+/// run length and fill byte, ending with "EOF" and an optional 3-byte
+/// truncation size.
+pub fn apply_ips(raw: &[u8], patch: &[u8]) -> Result<Vec<u8>, RomError> {
+    if !patch.starts_with(b"PATCH") {
+        return Err(RomError::BadPatch("missing PATCH header"));
+    }
+    let mut out = raw.to_vec();
+    let mut pos = 5;
+    let mut take = |len: usize| -> Result<&[u8], RomError> {
+        let bytes = patch
+            .get(pos..pos + len)
+            .ok_or(RomError::BadPatch("unexpected end of patch"))?;
+        pos += len;
+        Ok(bytes)
+    };
+    let be = |bytes: &[u8]| bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+
+    loop {
+        let offset = take(3)?;
+        if offset == b"EOF" {
+            if let Ok(size) = take(3) {
+                out.truncate(be(size));
+            }
+            return Ok(out);
+        }
+        let offset = be(offset);
+        let (data, len) = match be(take(2)?) {
+            0 => (None, be(take(2)?)),
+            len => (Some(take(len)?), len),
+        };
+        if out.len() < offset + len {
+            out.resize(offset + len, 0);
+        }
+        match data {
+            Some(data) => out[offset..offset + len].copy_from_slice(data),
+            None => {
+                let fill = take(1)?[0];
+                out[offset..offset + len].fill(fill);
+            }
+        }
+    }
+}
+
+/// Old tools stamped signatures such as "DiskDude!" over bytes 7-15 of iNES
+/// headers. Such a header either has version bits 01 or, with version 00,
+/// non-zero bytes 12-15; bytes 7-15 of it cannot be trusted.
+pub fn has_dirty_header(raw: &[u8]) -> bool {
+    if raw.len() < 16 {
+        return false;
+    }
+    match (raw[7] >> 2) & 0b11 {
+        0 => raw[12..16].iter().any(|&b| b != 0),
+        1 => true,
+        _ => false,
+    }
 }
 
 impl Rom {
-    pub fn new(raw: &Vec<u8>) -> Result<Rom, String> {
-        if &raw[0..4] != b"NES\x1a" {
-            return Err("File is not in iNES format".to_string());
+    /// Parse a cartridge image, correct its header from the game database and
+    /// reject mappers `Bus` does not implement.
+    pub fn new(raw: &[u8]) -> Result<Rom, RomError> {
+        let mut rom = Rom::parse(raw)?;
+        if let Some(entry) = game_db::lookup(&rom.prg_rom, &rom.chr_rom) {
+            entry.apply(&mut rom);
+        }
+        if !SUPPORTED_MAPPERS.contains(&rom.mapper) {
+            return Err(RomError::UnsupportedMapper(rom.mapper as u16));
         }
+        Ok(rom)
+    }
 
-        let prg_rom_size = raw[4] as usize * 16384;
-        let chr_rom_size = raw[5] as usize * 8192;
+    /// Parse a cartridge image exactly as its header describes it.
+    pub fn parse(raw: &[u8]) -> Result<Rom, RomError> {
+        if !raw.starts_with(b"NES\x1a") {
+            return Err(RomError::BadMagic);
+        }
+        if raw.len() < 16 {
+            return Err(RomError::Truncated {
+                expected: 16,
+                actual: raw.len(),
+            });
+        }
 
         let flags_6 = raw[6];
         let flags_7 = raw[7];
 
-        let mapper = (flags_7 & 0xF0) | (flags_6 >> 4);
+        let dirty = has_dirty_header(raw);
+        let mapper_high = if dirty { 0 } else { flags_7 & 0xF0 };
+        let mapper = mapper_high | (flags_6 >> 4);
 
         let version = (flags_7 >> 2) & 0b11;
-        if version != 0 {
-            return Err("iNES 2.0 format is not supported".to_string());
+        let nes2 = match version {
+            0 | 1 => false,
+            2 => true,
+            _ => return Err(RomError::UnsupportedFormat("unknown iNES header version")),
+        };
+        if nes2 && raw[8] & 0x0F != 0 {
+            let plane = (raw[8] & 0x0F) as u16;
+            return Err(RomError::UnsupportedMapper(plane << 8 | mapper as u16));
+        }
+
+        let (prg_rom_size, chr_rom_size) = if nes2 {
+            (
+                nes2_rom_size(raw[4], raw[9] & 0x0F, 16384)?,
+                nes2_rom_size(raw[5], raw[9] >> 4, 8192)?,
+            )
+        } else {
+            (raw[4] as usize * 16384, raw[5] as usize * 8192)
+        };
+        if prg_rom_size == 0 {
+            return Err(RomError::UnsupportedFormat("no PRG ROM"));
         }
 
         let four_screen = (flags_6 & 0b1000) != 0;
@@ -45,11 +200,25 @@ impl Rom {
 
         let has_trainer = (flags_6 & 0b0100) != 0;
         let has_battery = (flags_6 & 0b0010) != 0;
-        let prg_ram_units = raw[8] as usize;
-        let prg_ram_size = if prg_ram_units == 0 {
-            8192
+        let prg_ram_size = if nes2 {
+            // Volatile and battery-backed PRG RAM shift counts, 64 << n bytes each.
+            let shift_size = |n: u8| if n == 0 { 0 } else { 64usize << n };
+            match shift_size(raw[10] & 0x0F) + shift_size(raw[10] >> 4) {
+                0 => 8192,
+                size => size,
+            }
+        } else {
+            match if dirty { 0 } else { raw[8] as usize } {
+                0 => 8192,
+                units => units * 8192,
+            }
+        };
+        let region = if nes2 {
+            Region::from_nes2_timing(raw[12])
+        } else if !dirty && raw[9] & 0x01 != 0 {
+            Region::Pal
         } else {
-            prg_ram_units * 8192
+            Region::Ntsc
         };
         let prg_rom_start = 16 + if has_trainer { 512 } else { 0 };
         let prg_rom_end = prg_rom_start + prg_rom_size;
@@ -57,7 +226,10 @@ impl Rom {
         let chr_rom_end = chr_rom_start + chr_rom_size;
 
         if raw.len() < chr_rom_end {
-            return Err("File is smaller than specified in header".to_string());
+            return Err(RomError::Truncated {
+                expected: chr_rom_end,
+                actual: raw.len(),
+            });
         }
 
         Ok(Rom {
@@ -67,10 +239,28 @@ impl Rom {
             screen_mirroring,
             has_battery,
             prg_ram_size,
+            region,
         })
     }
 }
 
+/// NROM image for tests: `prg_banks` 16KB banks of NOPs with `code` at
+/// $C000, where the reset vector points, and a blank 8KB CHR bank.
+#[cfg(test)]
+pub(crate) fn test_nrom(prg_banks: u8, code: &[u8]) -> Vec<u8> {
+    let mut rom = b"NES\x1a".to_vec();
+    rom.extend_from_slice(&[prg_banks, 0x01, 0x00, 0x00]);
+    rom.extend_from_slice(&[0; 8]);
+    let mut prg = vec![0xEA; prg_banks as usize * 0x4000];
+    let start = prg.len() - 0x4000;
+    prg[start..start + code.len()].copy_from_slice(code);
+    let len = prg.len();
+    prg[len - 4..len - 2].copy_from_slice(&[0x00, 0xC0]);
+    rom.extend(prg);
+    rom.extend(vec![0; 0x2000]);
+    rom
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,5 +309,88 @@ mod tests {
         assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
         assert!(!rom.has_battery);
         assert_eq!(rom.prg_ram_size, 8192);
+        assert_eq!(rom.region, Region::Ntsc);
+    }
+
+    #[test]
+    fn test_nes2_header_parsing() {
+        let mut raw = create_test_rom(2, 1, 4, Mirroring::Horizontal);
+        raw[7] |= 0x08; // NES 2.0 identifier
+        raw[10] = 0x07; // 64 << 7 = 8 KiB PRG RAM
+        raw[12] = 0x01; // PAL timing
+        let rom = Rom::new(&raw).unwrap();
+
+        assert_eq!(rom.prg_rom.len(), 2 * 16384);
+        assert_eq!(rom.chr_rom.len(), 8192);
+        assert_eq!(rom.mapper, 4);
+        assert_eq!(rom.prg_ram_size, 8192);
+        assert_eq!(rom.region, Region::Pal);
+
+        raw[12] = 0x03;
+        assert_eq!(Rom::new(&raw).unwrap().region, Region::Dendy);
+
+        raw[8] = 0x01; // Mapper 256+
+        assert_eq!(
+            Rom::new(&raw).err(),
+            Some(RomError::UnsupportedMapper(0x104))
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        assert_eq!(Rom::new(b"NE").err(), Some(RomError::BadMagic));
+        assert_eq!(
+            Rom::new(b"PK\x03\x04 zip file").err(),
+            Some(RomError::BadMagic)
+        );
+        assert_eq!(
+            Rom::new(b"NES\x1a\x01").err(),
+            Some(RomError::Truncated {
+                expected: 16,
+                actual: 5
+            })
+        );
+
+        let raw = create_test_rom(2, 1, 0, Mirroring::Vertical);
+        assert_eq!(
+            Rom::new(&raw[..raw.len() - 1]).err(),
+            Some(RomError::Truncated {
+                expected: raw.len(),
+                actual: raw.len() - 1
+            })
+        );
+
+        let raw = create_test_rom(2, 1, 7, Mirroring::Vertical);
+        assert_eq!(Rom::new(&raw).err(), Some(RomError::UnsupportedMapper(7)));
+    }
+
+    #[test]
+    fn test_apply_ips() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]); // 2 bytes at 1
+        patch.extend_from_slice(&[0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xCC]); // RLE at 5
+        patch.extend_from_slice(b"EOF");
+        let patched = apply_ips(&[0; 6], &patch).unwrap();
+        assert_eq!(
+            patched,
+            vec![0x00, 0xAA, 0xBB, 0x00, 0x00, 0xCC, 0xCC, 0xCC]
+        );
+
+        patch.extend_from_slice(&[0x00, 0x00, 0x04]); // Truncate to 4 bytes
+        assert_eq!(apply_ips(&[0; 6], &patch).unwrap().len(), 4);
+
+        assert!(apply_ips(&[0; 6], b"PATC").is_err());
+        assert_eq!(
+            apply_ips(&[0; 6], b"PATCH\x00\x00\x01\x00\x05\xAA"),
+            Err(RomError::BadPatch("unexpected end of patch"))
+        );
+    }
+
+    #[test]
+    fn test_nes2_exponent_rom_size() {
+        assert_eq!(nes2_rom_size(0x00, 0x0F, 16384), Ok(1));
+        assert_eq!(nes2_rom_size(0x0A, 0x0F, 16384), Ok(4 * 5));
+        assert_eq!(nes2_rom_size(0x3C, 0x0F, 16384), Ok(1 << 15));
+        assert_eq!(nes2_rom_size(0x01, 0x01, 16384), Ok(0x101 * 16384));
     }
 }
@@ -0,0 +1,83 @@
+/// Master clock that the CPU, PPU, APU and cartridge are derived from.
+///
+/// Each CPU cycle advances the master clock by `cpu_divider` ticks and the
+/// PPU runs every dot whose `ppu_divider`-tick period has elapsed by then.
+/// NTSC divides by 12 and 4, so every CPU cycle holds exactly 3 dots; PAL
+/// divides by 16 and 5, giving 3.2 dots per cycle (a 3, 3, 3, 3, 4 pattern).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasterClock {
+    /// Crystal frequency in Hz.
+    pub master_hz: f64,
+    pub cpu_divider: u64,
+    pub ppu_divider: u64,
+    /// Master clock ticks elapsed since power-on.
+    pub master_cycles: u64,
+    /// Master clock time the PPU has been run up to.
+    ppu_master_cycles: u64,
+}
+
+impl MasterClock {
+    pub fn new(master_hz: f64, cpu_divider: u64, ppu_divider: u64) -> Self {
+        Self {
+            master_hz,
+            cpu_divider,
+            ppu_divider,
+            master_cycles: 0,
+            ppu_master_cycles: 0,
+        }
+    }
+
+    /// 21.477272 MHz crystal, CPU /12, PPU /4.
+    pub fn ntsc() -> Self {
+        Self::new(236_250_000.0 / 11.0, 12, 4)
+    }
+
+    /// 26.601712 MHz crystal, CPU /16, PPU /5.
+    pub fn pal() -> Self {
+        Self::new(26_601_712.0, 16, 5)
+    }
+
+    pub fn cpu_hz(&self) -> f64 {
+        self.master_hz / self.cpu_divider as f64
+    }
+
+    /// Advance by one CPU cycle and return the number of PPU dots that fall
+    /// within it.
+    pub fn cpu_cycle(&mut self) -> u16 {
+        self.master_cycles += self.cpu_divider;
+        let mut dots = 0;
+        while self.ppu_master_cycles + self.ppu_divider <= self.master_cycles {
+            self.ppu_master_cycles += self.ppu_divider;
+            dots += 1;
+        }
+        dots
+    }
+}
+
+impl Default for MasterClock {
+    fn default() -> Self {
+        Self::ntsc()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntsc_runs_three_dots_per_cycle() {
+        let mut clock = MasterClock::ntsc();
+        for _ in 0..1000 {
+            assert_eq!(clock.cpu_cycle(), 3);
+        }
+        assert!((clock.cpu_hz() - 1_789_772.7).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_pal_runs_sixteen_dots_per_five_cycles() {
+        let mut clock = MasterClock::pal();
+        let pattern: Vec<u16> = (0..10).map(|_| clock.cpu_cycle()).collect();
+        assert_eq!(pattern, vec![3, 3, 3, 3, 4, 3, 3, 3, 3, 4]);
+        assert_eq!(clock.master_cycles, 160);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::bus::Bus;
+use crate::opcodes::OPCODES;
 
 pub struct Cpu {
     pub a: u8,
@@ -7,6 +8,22 @@ pub struct Cpu {
     pub st: u8,
     pub pc: u16,
     pub sp: u8,
+    /// Total CPU cycles executed, as shown in the `CYC:` column of nestest logs.
+    pub cycles: u64,
+    /// Set by a KIL/JAM opcode; the CPU keeps re-executing it until reset.
+    pub jammed: bool,
+    /// Bus accesses (one per cycle) performed by the current `step`.
+    step_cycles: u16,
+    /// NMI/IRQ line state sampled at the end of the previous cycle and the
+    /// current one. At the end of an instruction `*_sampled` holds the state
+    /// as of its penultimate cycle, which is when the 6502 polls.
+    nmi_sampled: bool,
+    nmi_latest: bool,
+    irq_sampled: bool,
+    irq_latest: bool,
+    /// Interrupts to enter before the next instruction.
+    nmi_pending: bool,
+    irq_pending: bool,
 }
 
 impl Cpu {
@@ -24,6 +41,15 @@ impl Cpu {
             st: 0,
             pc: 0,
             sp: 0xFD,
+            cycles: 0,
+            jammed: false,
+            step_cycles: 0,
+            nmi_sampled: false,
+            nmi_latest: false,
+            irq_sampled: false,
+            irq_latest: false,
+            nmi_pending: false,
+            irq_pending: false,
         }
     }
 
@@ -33,1000 +59,224 @@ impl Cpu {
         self.y = 0;
         self.st = 0; // nestest expects 0x24 but strictly 0 on startup? Usually 0x34 or 0x24.
         self.sp = 0xFD;
+        self.jammed = false;
+        self.nmi_pending = false;
+        self.irq_pending = false;
 
         // Reset vector
         self.pc = (bus.read(0xFFFC) as u16) | ((bus.read(0xFFFD) as u16) << 8);
     }
 
-    pub fn trace(&mut self, bus: &mut Bus) -> String {
-        let opcode = bus.peek(self.pc);
-        let ops = match crate::opcodes::OPCODES_MAP.get(&opcode) {
-            Some(op) => op,
-            None => panic!("OpCode {:02X} is not implemented in opcodes.rs", opcode),
-        };
-
-        let mut hex_dump = vec![];
-        hex_dump.push(opcode);
-
-        let (mem_addr, stored_value) = match ops.mode {
-            AddressingMode::Immediate | AddressingMode::NoneAddressing => (0, 0),
-            _ => {
-                let addr = self.get_absolute_address(bus, &ops.mode, self.pc + 1);
-                (addr, bus.peek(addr))
-            }
-        };
-
-        let tmp = match ops.len {
-            1 => match ops.mode {
-                AddressingMode::Accumulator => "A ".to_string(),
-                _ => "".to_string(),
-            },
-            2 => {
-                let address = bus.peek(self.pc + 1);
-                hex_dump.push(address);
-                match ops.mode {
-                    AddressingMode::Immediate => format!("#${:02X}", address),
-                    AddressingMode::ZeroPage => format!("${:02X} = {:02X}", address, stored_value),
-                    AddressingMode::ZeroPageX => format!(
-                        "${:02X},X @ {:02X} = {:02X}",
-                        address, mem_addr, stored_value
-                    ),
-                    AddressingMode::ZeroPageY => format!(
-                        "${:02X},Y @ {:02X} = {:02X}",
-                        address, mem_addr, stored_value
-                    ),
-                    AddressingMode::IndirectX => format!(
-                        "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
-                        address,
-                        (address.wrapping_add(self.x)),
-                        mem_addr,
-                        stored_value
-                    ),
-                    AddressingMode::IndirectY => format!(
-                        "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
-                        address,
-                        (mem_addr.wrapping_sub(self.y as u16)),
-                        mem_addr,
-                        stored_value
-                    ),
-                    _ => format!("${:02X}", address), // fallback
-                }
-            }
-            3 => {
-                let address_lo = bus.peek(self.pc + 1);
-                let address_hi = bus.peek(self.pc + 2);
-                hex_dump.push(address_lo);
-                hex_dump.push(address_hi);
-
-                let address = (address_hi as u16) << 8 | address_lo as u16;
-
-                match ops.mode {
-                    AddressingMode::Absolute => {
-                        if ops.name == "JMP" || ops.name == "JSR" {
-                            format!("${:04X}", address)
-                        } else {
-                            format!("${:04X} = {:02X}", address, stored_value)
-                        }
-                    }
-                    AddressingMode::AbsoluteX => format!(
-                        "${:04X},X @ {:04X} = {:02X}",
-                        address, mem_addr, stored_value
-                    ),
-                    AddressingMode::AbsoluteY => format!(
-                        "${:04X},Y @ {:04X} = {:02X}",
-                        address, mem_addr, stored_value
-                    ),
-                    _ => format!("${:04X}", address),
-                }
-            }
-            _ => "".to_string(),
-        };
-
-        let hex_str = hex_dump
-            .iter()
-            .map(|z| format!("{:02X}", z))
-            .collect::<Vec<String>>()
-            .join(" ");
-        let asm_str = format!("{:04X}  {:8} {: >4} {}", self.pc, hex_str, ops.name, tmp);
-
-        format!(
-            "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
-            asm_str, self.a, self.x, self.y, self.st, self.sp
-        )
-        .to_uppercase()
+    /// nestest-style trace line for the instruction at PC (see `disasm::trace`).
+    pub fn trace(&self, bus: &Bus) -> String {
+        crate::disasm::trace(self, bus)
     }
 
+    /// Execute one instruction, or enter a pending NMI/IRQ handler, one bus
+    /// access per CPU cycle. Returns the cycles taken, including DMA stalls.
     pub fn step(&mut self, bus: &mut Bus) -> u16 {
         bus.begin_cpu_step();
+        self.step_cycles = 0;
 
-        // Fetch
-        let opcode = bus.read(self.pc);
-        self.pc = self.pc.wrapping_add(1);
-
-        // Execute
-        match opcode {
-            // LDA
-            0xA9 => {
-                self.lda(bus, AddressingMode::Immediate);
-            }
-            0xA5 => {
-                self.lda(bus, AddressingMode::ZeroPage);
-            }
-            0xB5 => {
-                self.lda(bus, AddressingMode::ZeroPageX);
-            }
-            0xAD => {
-                self.lda(bus, AddressingMode::Absolute);
-            }
-            0xBD => {
-                self.lda(bus, AddressingMode::AbsoluteX);
-            }
-            0xB9 => {
-                self.lda(bus, AddressingMode::AbsoluteY);
-            }
-            0xA1 => {
-                self.lda(bus, AddressingMode::IndirectX);
-            }
-            0xB1 => {
-                self.lda(bus, AddressingMode::IndirectY);
-            }
-
-            // LDX
-            0xA2 => {
-                self.ldx(bus, AddressingMode::Immediate);
-            }
-            0xA6 => {
-                self.ldx(bus, AddressingMode::ZeroPage);
-            }
-            0xB6 => {
-                self.ldx(bus, AddressingMode::ZeroPageY);
-            }
-            0xAE => {
-                self.ldx(bus, AddressingMode::Absolute);
-            }
-            0xBE => {
-                self.ldx(bus, AddressingMode::AbsoluteY);
-            }
-
-            // LDY
-            0xA0 => {
-                self.ldy(bus, AddressingMode::Immediate);
-            }
-            0xA4 => {
-                self.ldy(bus, AddressingMode::ZeroPage);
-            }
-            0xB4 => {
-                self.ldy(bus, AddressingMode::ZeroPageX);
-            }
-            0xAC => {
-                self.ldy(bus, AddressingMode::Absolute);
-            }
-            0xBC => {
-                self.ldy(bus, AddressingMode::AbsoluteX);
-            }
-
-            // STA
-            0x85 => {
-                self.sta(bus, AddressingMode::ZeroPage);
-            }
-            0x95 => {
-                self.sta(bus, AddressingMode::ZeroPageX);
-            }
-            0x8D => {
-                self.sta(bus, AddressingMode::Absolute);
-            }
-            0x9D => {
-                self.sta(bus, AddressingMode::AbsoluteX);
-            }
-            0x99 => {
-                self.sta(bus, AddressingMode::AbsoluteY);
-            }
-            0x81 => {
-                self.sta(bus, AddressingMode::IndirectX);
-            }
-            0x91 => {
-                self.sta(bus, AddressingMode::IndirectY);
-            }
-
-            // STX
-            0x86 => {
-                self.stx(bus, AddressingMode::ZeroPage);
-            }
-            0x96 => {
-                self.stx(bus, AddressingMode::ZeroPageY);
-            }
-            0x8E => {
-                self.stx(bus, AddressingMode::Absolute);
-            }
-
-            // STY
-            0x84 => {
-                self.sty(bus, AddressingMode::ZeroPage);
-            }
-            0x94 => {
-                self.sty(bus, AddressingMode::ZeroPageX);
-            }
-            0x8C => {
-                self.sty(bus, AddressingMode::Absolute);
-            }
-
-            // Increment/Decrement Registers
-            0xE8 => {
-                self.inx();
-            }
-            0xC8 => {
-                self.iny();
-            }
-            0xCA => {
-                self.dex();
-            }
-            0x88 => {
-                self.dey();
-            }
-
-            // Increment/Decrement Memory
-            0xE6 => {
-                self.inc(bus, AddressingMode::ZeroPage);
-            }
-            0xF6 => {
-                self.inc(bus, AddressingMode::ZeroPageX);
-            }
-            0xEE => {
-                self.inc(bus, AddressingMode::Absolute);
-            }
-            0xFE => {
-                self.inc(bus, AddressingMode::AbsoluteX);
-            }
-
-            0xC6 => {
-                self.dec(bus, AddressingMode::ZeroPage);
-            }
-            0xD6 => {
-                self.dec(bus, AddressingMode::ZeroPageX);
-            }
-            0xCE => {
-                self.dec(bus, AddressingMode::Absolute);
-            }
-            0xDE => {
-                self.dec(bus, AddressingMode::AbsoluteX);
-            }
-
-            // Stack Operations
-            0x48 => {
-                self.pha(bus);
-            }
-            0x08 => {
-                self.php(bus);
-            }
-            0x68 => {
-                self.pla(bus);
-            }
-            0x28 => {
-                self.plp(bus);
-            }
-
-            // Transfers
-            0xAA => {
-                self.tax();
-            }
-            0xA8 => {
-                self.tay();
-            }
-            0x8A => {
-                self.txa();
-            }
-            0x98 => {
-                self.tya();
-            }
-            0x9A => {
-                self.txs();
-            }
-            0xBA => {
-                self.tsx();
-            }
-
-            // Status Flags
-            0x18 => {
-                self.clc();
-            }
-            0x38 => {
-                self.sec();
-            }
-            0x58 => {
-                self.cli();
-            }
-            0x78 => {
-                self.sei();
-            }
-            0xB8 => {
-                self.clv();
-            }
-            0xD8 => {
-                self.cld();
-            }
-            0xF8 => {
-                self.sed();
-            }
-
-            // Logical Operations
-            0x29 => {
-                self.and(bus, AddressingMode::Immediate);
-            }
-            0x25 => {
-                self.and(bus, AddressingMode::ZeroPage);
-            }
-            0x35 => {
-                self.and(bus, AddressingMode::ZeroPageX);
-            }
-            0x2D => {
-                self.and(bus, AddressingMode::Absolute);
-            }
-            0x3D => {
-                self.and(bus, AddressingMode::AbsoluteX);
-            }
-            0x39 => {
-                self.and(bus, AddressingMode::AbsoluteY);
-            }
-            0x21 => {
-                self.and(bus, AddressingMode::IndirectX);
-            }
-            0x31 => {
-                self.and(bus, AddressingMode::IndirectY);
-            }
-
-            0x09 => {
-                self.ora(bus, AddressingMode::Immediate);
-            }
-            0x05 => {
-                self.ora(bus, AddressingMode::ZeroPage);
-            }
-            0x15 => {
-                self.ora(bus, AddressingMode::ZeroPageX);
-            }
-            0x0D => {
-                self.ora(bus, AddressingMode::Absolute);
-            }
-            0x1D => {
-                self.ora(bus, AddressingMode::AbsoluteX);
-            }
-            0x19 => {
-                self.ora(bus, AddressingMode::AbsoluteY);
-            }
-            0x01 => {
-                self.ora(bus, AddressingMode::IndirectX);
-            }
-            0x11 => {
-                self.ora(bus, AddressingMode::IndirectY);
-            }
-
-            0x49 => {
-                self.eor(bus, AddressingMode::Immediate);
-            }
-            0x45 => {
-                self.eor(bus, AddressingMode::ZeroPage);
-            }
-            0x55 => {
-                self.eor(bus, AddressingMode::ZeroPageX);
-            }
-            0x4D => {
-                self.eor(bus, AddressingMode::Absolute);
-            }
-            0x5D => {
-                self.eor(bus, AddressingMode::AbsoluteX);
-            }
-            0x59 => {
-                self.eor(bus, AddressingMode::AbsoluteY);
-            }
-            0x41 => {
-                self.eor(bus, AddressingMode::IndirectX);
-            }
-            0x51 => {
-                self.eor(bus, AddressingMode::IndirectY);
-            }
-
-            0x24 => {
-                self.bit(bus, AddressingMode::ZeroPage);
-            }
-            0x2C => {
-                self.bit(bus, AddressingMode::Absolute);
-            }
-
-            // Compare Operations
-            0xC9 => {
-                self.cmp(bus, AddressingMode::Immediate);
-            }
-            0xC5 => {
-                self.cmp(bus, AddressingMode::ZeroPage);
-            }
-            0xD5 => {
-                self.cmp(bus, AddressingMode::ZeroPageX);
-            }
-            0xCD => {
-                self.cmp(bus, AddressingMode::Absolute);
-            }
-            0xDD => {
-                self.cmp(bus, AddressingMode::AbsoluteX);
-            }
-            0xD9 => {
-                self.cmp(bus, AddressingMode::AbsoluteY);
-            }
-            0xC1 => {
-                self.cmp(bus, AddressingMode::IndirectX);
-            }
-            0xD1 => {
-                self.cmp(bus, AddressingMode::IndirectY);
-            }
-
-            0xE0 => {
-                self.cpx(bus, AddressingMode::Immediate);
-            }
-            0xE4 => {
-                self.cpx(bus, AddressingMode::ZeroPage);
-            }
-            0xEC => {
-                self.cpx(bus, AddressingMode::Absolute);
-            }
-
-            0xC0 => {
-                self.cpy(bus, AddressingMode::Immediate);
-            }
-            0xC4 => {
-                self.cpy(bus, AddressingMode::ZeroPage);
-            }
-            0xCC => {
-                self.cpy(bus, AddressingMode::Absolute);
-            }
-
-            // Shifts and Rotates
-            0x0A => {
-                self.asl_acc();
-            }
-            0x06 => {
-                self.asl(bus, AddressingMode::ZeroPage);
-            }
-            0x16 => {
-                self.asl(bus, AddressingMode::ZeroPageX);
-            }
-            0x0E => {
-                self.asl(bus, AddressingMode::Absolute);
-            }
-            0x1E => {
-                self.asl(bus, AddressingMode::AbsoluteX);
-            }
-
-            0x4A => {
-                self.lsr_acc();
-            }
-            0x46 => {
-                self.lsr(bus, AddressingMode::ZeroPage);
-            }
-            0x56 => {
-                self.lsr(bus, AddressingMode::ZeroPageX);
-            }
-            0x4E => {
-                self.lsr(bus, AddressingMode::Absolute);
-            }
-            0x5E => {
-                self.lsr(bus, AddressingMode::AbsoluteX);
-            }
-
-            0x2A => {
-                self.rol_acc();
-            }
-            0x26 => {
-                self.rol(bus, AddressingMode::ZeroPage);
-            }
-            0x36 => {
-                self.rol(bus, AddressingMode::ZeroPageX);
-            }
-            0x2E => {
-                self.rol(bus, AddressingMode::Absolute);
-            }
-            0x3E => {
-                self.rol(bus, AddressingMode::AbsoluteX);
-            }
-
-            0x6A => {
-                self.ror_acc();
-            }
-            0x66 => {
-                self.ror(bus, AddressingMode::ZeroPage);
-            }
-            0x76 => {
-                self.ror(bus, AddressingMode::ZeroPageX);
-            }
-            0x6E => {
-                self.ror(bus, AddressingMode::Absolute);
-            }
-            0x7E => {
-                self.ror(bus, AddressingMode::AbsoluteX);
-            }
-
-            // Arithmetic with Carry
-            0x69 => {
-                self.adc(bus, AddressingMode::Immediate);
-            }
-            0x65 => {
-                self.adc(bus, AddressingMode::ZeroPage);
-            }
-            0x75 => {
-                self.adc(bus, AddressingMode::ZeroPageX);
-            }
-            0x6D => {
-                self.adc(bus, AddressingMode::Absolute);
-            }
-            0x7D => {
-                self.adc(bus, AddressingMode::AbsoluteX);
-            }
-            0x79 => {
-                self.adc(bus, AddressingMode::AbsoluteY);
-            }
-            0x61 => {
-                self.adc(bus, AddressingMode::IndirectX);
-            }
-            0x71 => {
-                self.adc(bus, AddressingMode::IndirectY);
-            }
-
-            0xE9 => {
-                self.sbc(bus, AddressingMode::Immediate);
-            }
-            0xE5 => {
-                self.sbc(bus, AddressingMode::ZeroPage);
-            }
-            0xF5 => {
-                self.sbc(bus, AddressingMode::ZeroPageX);
-            }
-            0xED => {
-                self.sbc(bus, AddressingMode::Absolute);
-            }
-            0xFD => {
-                self.sbc(bus, AddressingMode::AbsoluteX);
-            }
-            0xF9 => {
-                self.sbc(bus, AddressingMode::AbsoluteY);
-            }
-            0xE1 => {
-                self.sbc(bus, AddressingMode::IndirectX);
-            }
-            0xF1 => {
-                self.sbc(bus, AddressingMode::IndirectY);
-            }
-
-            // Jumps and Calls
-            0x4C => {
-                self.jmp(bus, AddressingMode::Absolute);
-            }
-            0x6C => {
-                self.jmp(bus, AddressingMode::Indirect);
-            } // Need Indirect mode for JMP
-            0x20 => {
-                self.jsr(bus);
-            }
-            0x60 => {
-                self.rts(bus);
-            }
-
-            // System
-            0x00 => {
-                self.brk(bus);
-            }
-            0x40 => {
-                self.rti(bus);
-            }
-            0xEA => { /* NOP */ }
-
-            // Branching
-            0x10 => {
-                self.bpl(bus);
-            }
-            0x30 => {
-                self.bmi(bus);
-            }
-            0x50 => {
-                self.bvc(bus);
-            }
-            0x70 => {
-                self.bvs(bus);
-            }
-            0x90 => {
-                self.bcc(bus);
-            }
-            0xB0 => {
-                self.bcs(bus);
-            }
-            0xD0 => {
-                self.bne(bus);
-            }
-            0xF0 => {
-                self.beq(bus);
-            }
-
-            // Unofficial NOPs
-            0x04 | 0x14 | 0x34 | 0x44 | 0x54 | 0x64 | 0x74 | 0x80 | 0x82 | 0x89 | 0xC2 | 0xD4
-            | 0xE2 | 0xF4 | 0x0C | 0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC | 0x02 | 0x12 | 0x22
-            | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => {
-                // All these NOPs read the operand but do nothing.
-                let mode = &crate::opcodes::OPCODES_MAP.get(&opcode).unwrap().mode;
-                match mode {
-                    AddressingMode::Immediate => {
-                        self.pc = self.pc.wrapping_add(1);
-                    }
-                    _ => {
-                        let _ = self.get_operand_address(bus, mode);
-                    }
-                }
-            }
-            0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => {
-                // NOP Implied - do nothing, no operand fetch (already fetched opcode)
-            }
-
-            // SLO
-            0x07 => {
-                self.slo(bus, AddressingMode::ZeroPage);
-            }
-            0x17 => {
-                self.slo(bus, AddressingMode::ZeroPageX);
-            }
-            0x0F => {
-                self.slo(bus, AddressingMode::Absolute);
-            }
-            0x1F => {
-                self.slo(bus, AddressingMode::AbsoluteX);
-            }
-            0x1B => {
-                self.slo(bus, AddressingMode::AbsoluteY);
-            }
-            0x03 => {
-                self.slo(bus, AddressingMode::IndirectX);
-            }
-            0x13 => {
-                self.slo(bus, AddressingMode::IndirectY);
-            }
-
-            // RLA
-            0x27 => {
-                self.rla(bus, AddressingMode::ZeroPage);
-            }
-            0x37 => {
-                self.rla(bus, AddressingMode::ZeroPageX);
-            }
-            0x2F => {
-                self.rla(bus, AddressingMode::Absolute);
-            }
-            0x3F => {
-                self.rla(bus, AddressingMode::AbsoluteX);
-            }
-            0x3B => {
-                self.rla(bus, AddressingMode::AbsoluteY);
-            }
-            0x23 => {
-                self.rla(bus, AddressingMode::IndirectX);
-            }
-            0x33 => {
-                self.rla(bus, AddressingMode::IndirectY);
-            }
-
-            // SRE
-            0x47 => {
-                self.sre(bus, AddressingMode::ZeroPage);
-            }
-            0x57 => {
-                self.sre(bus, AddressingMode::ZeroPageX);
-            }
-            0x4F => {
-                self.sre(bus, AddressingMode::Absolute);
-            }
-            0x5F => {
-                self.sre(bus, AddressingMode::AbsoluteX);
-            }
-            0x5B => {
-                self.sre(bus, AddressingMode::AbsoluteY);
-            }
-            0x43 => {
-                self.sre(bus, AddressingMode::IndirectX);
-            }
-            0x53 => {
-                self.sre(bus, AddressingMode::IndirectY);
+        if self.nmi_pending || self.irq_pending {
+            let kind = if self.nmi_pending { "NMI" } else { "IRQ" };
+            if Self::irq_log_enabled() {
+                #[cfg(not(target_arch = "wasm32"))]
+                println!("[IRQ] {} at PC=${:04X}", kind, self.pc);
             }
+            // Two dummy reads of the interrupted instruction, then the same
+            // push/vector sequence as BRK with the B flag clear.
+            self.read(bus, self.pc);
+            self.read(bus, self.pc);
+            self.enter_interrupt(bus, self.pc, self.st & !0x10 | 0x20);
+        } else {
+            // Fetch
+            let opcode = self.fetch_byte(bus);
+            let op = &OPCODES[opcode as usize];
 
-            // RRA
-            0x67 => {
-                self.rra(bus, AddressingMode::ZeroPage);
-            }
-            0x77 => {
-                self.rra(bus, AddressingMode::ZeroPageX);
-            }
-            0x6F => {
-                self.rra(bus, AddressingMode::Absolute);
-            }
-            0x7F => {
-                self.rra(bus, AddressingMode::AbsoluteX);
-            }
-            0x7B => {
-                self.rra(bus, AddressingMode::AbsoluteY);
-            }
-            0x63 => {
-                self.rra(bus, AddressingMode::IndirectX);
-            }
-            0x73 => {
-                self.rra(bus, AddressingMode::IndirectY);
+            // Every single-byte instruction reads the following byte on its
+            // second cycle and throws it away.
+            if op.len == 1 {
+                self.read(bus, self.pc);
             }
 
-            // SAX
-            0x87 => {
-                self.sax(bus, AddressingMode::ZeroPage);
-            }
-            0x97 => {
-                self.sax(bus, AddressingMode::ZeroPageY);
-            }
-            0x8F => {
-                self.sax(bus, AddressingMode::Absolute);
-            }
-            0x83 => {
-                self.sax(bus, AddressingMode::IndirectX);
-            }
+            // Execute
+            (op.handler)(self, bus, op.mode);
 
-            // LAX
-            0xA7 => {
-                self.lax(bus, AddressingMode::ZeroPage);
-            }
-            0xB7 => {
-                self.lax(bus, AddressingMode::ZeroPageY);
-            }
-            0xAF => {
-                self.lax(bus, AddressingMode::Absolute);
-            }
-            0xBF => {
-                self.lax(bus, AddressingMode::AbsoluteY);
-            }
-            0xA3 => {
-                self.lax(bus, AddressingMode::IndirectX);
-            }
-            0xB3 => {
-                self.lax(bus, AddressingMode::IndirectY);
-            }
-
-            // DCP
-            0xC7 => {
-                self.dcp(bus, AddressingMode::ZeroPage);
-            }
-            0xD7 => {
-                self.dcp(bus, AddressingMode::ZeroPageX);
-            }
-            0xCF => {
-                self.dcp(bus, AddressingMode::Absolute);
-            }
-            0xDF => {
-                self.dcp(bus, AddressingMode::AbsoluteX);
-            }
-            0xDB => {
-                self.dcp(bus, AddressingMode::AbsoluteY);
-            }
-            0xC3 => {
-                self.dcp(bus, AddressingMode::IndirectX);
-            }
-            0xD3 => {
-                self.dcp(bus, AddressingMode::IndirectY);
-            }
+            self.nmi_pending = self.nmi_sampled;
+            self.irq_pending = self.irq_sampled;
+        }
 
-            // ISC
-            0xE7 => {
-                self.isc(bus, AddressingMode::ZeroPage);
-            }
-            0xF7 => {
-                self.isc(bus, AddressingMode::ZeroPageX);
-            }
-            0xEF => {
-                self.isc(bus, AddressingMode::Absolute);
-            }
-            0xFF => {
-                self.isc(bus, AddressingMode::AbsoluteX);
-            }
-            0xFB => {
-                self.isc(bus, AddressingMode::AbsoluteY);
-            }
-            0xE3 => {
-                self.isc(bus, AddressingMode::IndirectX);
-            }
-            0xF3 => {
-                self.isc(bus, AddressingMode::IndirectY);
-            }
+        let dma_cycles = bus.poll_dma_cycles() as u16;
+        let total = self.step_cycles + dma_cycles;
+        self.cycles += total as u64;
+        total
+    }
 
-            // ALR
-            0x4B => {
-                self.alr(bus);
-            }
-            // ANC
-            0x0B | 0x2B => {
-                self.anc(bus);
-            }
-            // ARR
-            0x6B => {
-                self.arr(bus);
-            }
-            // AXS
-            0xCB => {
-                self.axs(bus);
-            }
-            // LAS
-            0xBB => {
-                self.las(bus);
-            }
-            // XAA
-            0x8B => {
-                self.xaa(bus);
-            }
-            // AHX
-            0x93 | 0x9F => {
-                let mode = &crate::opcodes::OPCODES_MAP.get(&opcode).unwrap().mode;
-                self.ahx(bus, mode);
-            }
-            // SHY
-            0x9C => {
-                self.shy(bus);
-            }
-            // SHX
-            0x9E => {
-                self.shx(bus);
-            }
-            // TAS
-            0x9B => {
-                self.tas(bus);
-            }
+    /// One read cycle.
+    fn read(&mut self, bus: &mut Bus, addr: u16) -> u8 {
+        let value = bus.read(addr);
+        self.end_cycle(bus);
+        value
+    }
 
-            _ => {
-                // Unknown opcode
-                #[cfg(not(target_arch = "wasm32"))]
-                println!("Unknown opcode: {:02X}", opcode);
-            }
-        }
+    /// One write cycle.
+    fn write(&mut self, bus: &mut Bus, addr: u16, value: u8) {
+        bus.write(addr, value);
+        self.end_cycle(bus);
+    }
 
-        let cycles = crate::opcodes::OPCODES_MAP
-            .get(&opcode)
-            .map(|op| op.cycles)
-            .unwrap_or(0);
-        let dma_cycles = bus.poll_dma_cycles() as u16;
-        (cycles as u16) + dma_cycles
+    fn end_cycle(&mut self, bus: &Bus) {
+        self.step_cycles += 1;
+        self.nmi_sampled = self.nmi_latest;
+        self.irq_sampled = self.irq_latest;
+        self.nmi_latest = bus.ppu.nmi_interrupt;
+        self.irq_latest = bus.irq_line() && (self.st & 0x04) == 0;
     }
 
     fn fetch_byte(&mut self, bus: &mut Bus) -> u8 {
-        let value = bus.read(self.pc);
+        let value = self.read(bus, self.pc);
         self.pc = self.pc.wrapping_add(1);
         value
     }
 
-    fn lda(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn lda(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.a = value;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn ldx(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn ldx(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.x = value;
         self.update_zero_negative_flags(self.x);
     }
 
-    fn ldy(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn ldy(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.y = value;
         self.update_zero_negative_flags(self.y);
     }
 
-    fn sta(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        bus.write(addr, self.a);
+    pub(crate) fn sta(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        self.write(bus, addr, self.a);
     }
 
-    fn stx(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        bus.write(addr, self.x);
+    pub(crate) fn stx(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        self.write(bus, addr, self.x);
     }
 
-    fn sty(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        bus.write(addr, self.y);
+    pub(crate) fn sty(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        self.write(bus, addr, self.y);
     }
 
-    fn tax(&mut self) {
+    pub(crate) fn tax(&mut self) {
         self.x = self.a;
         self.update_zero_negative_flags(self.x);
     }
 
-    fn tay(&mut self) {
+    pub(crate) fn tay(&mut self) {
         self.y = self.a;
         self.update_zero_negative_flags(self.y);
     }
 
-    fn txa(&mut self) {
+    pub(crate) fn txa(&mut self) {
         self.a = self.x;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn tya(&mut self) {
+    pub(crate) fn tya(&mut self) {
         self.a = self.y;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn txs(&mut self) {
+    pub(crate) fn txs(&mut self) {
         self.sp = self.x;
     }
 
-    fn tsx(&mut self) {
+    pub(crate) fn tsx(&mut self) {
         self.x = self.sp;
         self.update_zero_negative_flags(self.x);
     }
 
     // Increment/Decrement Registers
-    fn inx(&mut self) {
+    pub(crate) fn inx(&mut self) {
         self.x = self.x.wrapping_add(1);
         self.update_zero_negative_flags(self.x);
     }
 
-    fn iny(&mut self) {
+    pub(crate) fn iny(&mut self) {
         self.y = self.y.wrapping_add(1);
         self.update_zero_negative_flags(self.y);
     }
 
-    fn dex(&mut self) {
+    pub(crate) fn dex(&mut self) {
         self.x = self.x.wrapping_sub(1);
         self.update_zero_negative_flags(self.x);
     }
 
-    fn dey(&mut self) {
+    pub(crate) fn dey(&mut self) {
         self.y = self.y.wrapping_sub(1);
         self.update_zero_negative_flags(self.y);
     }
 
     // Increment/Decrement Memory
-    fn inc(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_value = bus.read(addr);
+    pub(crate) fn inc(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_value = self.read(bus, addr);
         let new_value = old_value.wrapping_add(1);
-        bus.write(addr, old_value);
-        bus.write(addr, new_value);
+        self.write(bus, addr, old_value);
+        self.write(bus, addr, new_value);
         self.update_zero_negative_flags(new_value);
     }
 
-    fn dec(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_value = bus.read(addr);
+    pub(crate) fn dec(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_value = self.read(bus, addr);
         let new_value = old_value.wrapping_sub(1);
-        bus.write(addr, old_value);
-        bus.write(addr, new_value);
+        self.write(bus, addr, old_value);
+        self.write(bus, addr, new_value);
         self.update_zero_negative_flags(new_value);
     }
 
     // Stack Operations
     fn push(&mut self, bus: &mut Bus, value: u8) {
-        bus.write(0x0100 + self.sp as u16, value);
+        self.write(bus, 0x0100 + self.sp as u16, value);
         self.sp = self.sp.wrapping_sub(1);
     }
 
     fn pop(&mut self, bus: &mut Bus) -> u8 {
         self.sp = self.sp.wrapping_add(1);
-        bus.read(0x0100 + self.sp as u16)
+        self.read(bus, 0x0100 + self.sp as u16)
     }
 
-    fn pha(&mut self, bus: &mut Bus) {
+    pub(crate) fn pha(&mut self, bus: &mut Bus) {
         self.push(bus, self.a);
     }
 
-    fn php(&mut self, bus: &mut Bus) {
+    pub(crate) fn php(&mut self, bus: &mut Bus) {
         // Break flag and bit 5 are set when pushing status
         self.push(bus, self.st | 0x30);
     }
 
-    fn pla(&mut self, bus: &mut Bus) {
+    pub(crate) fn pla(&mut self, bus: &mut Bus) {
+        self.read(bus, 0x0100 + self.sp as u16);
         self.a = self.pop(bus);
         self.update_zero_negative_flags(self.a);
     }
 
-    fn plp(&mut self, bus: &mut Bus) {
+    pub(crate) fn plp(&mut self, bus: &mut Bus) {
+        self.read(bus, 0x0100 + self.sp as u16);
+        self.pull_status(bus);
+    }
+
+    fn pull_status(&mut self, bus: &mut Bus) {
         self.st = self.pop(bus);
         // Break flag and bit 5 are ignored when pulling
         self.st &= !0x10;
@@ -1034,25 +284,25 @@ impl Cpu {
     }
 
     // Status Flags
-    fn clc(&mut self) {
+    pub(crate) fn clc(&mut self) {
         self.st &= !0x01;
     }
-    fn sec(&mut self) {
+    pub(crate) fn sec(&mut self) {
         self.st |= 0x01;
     }
-    fn cli(&mut self) {
+    pub(crate) fn cli(&mut self) {
         self.st &= !0x04;
     }
-    fn sei(&mut self) {
+    pub(crate) fn sei(&mut self) {
         self.st |= 0x04;
     }
-    fn clv(&mut self) {
+    pub(crate) fn clv(&mut self) {
         self.st &= !0x40;
     }
-    fn cld(&mut self) {
+    pub(crate) fn cld(&mut self) {
         self.st &= !0x08;
     }
-    fn sed(&mut self) {
+    pub(crate) fn sed(&mut self) {
         self.st |= 0x08;
     }
 
@@ -1060,76 +310,87 @@ impl Cpu {
     fn branch(&mut self, bus: &mut Bus, condition: bool) {
         let offset = self.fetch_byte(bus) as i8;
         if condition {
+            let (nmi, irq) = (self.nmi_sampled, self.irq_sampled);
+            self.read(bus, self.pc);
             let jump_addr = self.pc.wrapping_add(offset as u16);
+            if (jump_addr & 0xFF00) != (self.pc & 0xFF00) {
+                // PCH hasn't been fixed up yet on this cycle.
+                self.read(bus, (self.pc & 0xFF00) | (jump_addr & 0x00FF));
+            } else {
+                // A taken branch that stays on its page doesn't poll
+                // interrupts on its last cycle.
+                self.nmi_sampled = nmi;
+                self.irq_sampled = irq;
+            }
             self.pc = jump_addr;
         }
     }
 
-    fn bpl(&mut self, bus: &mut Bus) {
+    pub(crate) fn bpl(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x80) == 0;
         self.branch(bus, condition);
     }
 
-    fn bmi(&mut self, bus: &mut Bus) {
+    pub(crate) fn bmi(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x80) != 0;
         self.branch(bus, condition);
     }
 
-    fn bvc(&mut self, bus: &mut Bus) {
+    pub(crate) fn bvc(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x40) == 0;
         self.branch(bus, condition);
     }
 
-    fn bvs(&mut self, bus: &mut Bus) {
+    pub(crate) fn bvs(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x40) != 0;
         self.branch(bus, condition);
     }
 
-    fn bcc(&mut self, bus: &mut Bus) {
+    pub(crate) fn bcc(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x01) == 0;
         self.branch(bus, condition);
     }
 
-    fn bcs(&mut self, bus: &mut Bus) {
+    pub(crate) fn bcs(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x01) != 0;
         self.branch(bus, condition);
     }
 
-    fn bne(&mut self, bus: &mut Bus) {
+    pub(crate) fn bne(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x02) == 0;
         self.branch(bus, condition);
     }
 
-    fn beq(&mut self, bus: &mut Bus) {
+    pub(crate) fn beq(&mut self, bus: &mut Bus) {
         let condition = (self.st & 0x02) != 0;
         self.branch(bus, condition);
     }
 
     // Logical Operations
-    fn and(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn and(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.a &= value;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn ora(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn ora(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.a |= value;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn eor(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn eor(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.a ^= value;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn bit(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn bit(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         let result = self.a & value;
         if result == 0 {
             self.st |= 0x02; // Zero flag
@@ -1140,21 +401,21 @@ impl Cpu {
     }
 
     // Compare Operations
-    fn cmp(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn cmp(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.compare(self.a, value);
     }
 
-    fn cpx(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn cpx(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.compare(self.x, value);
     }
 
-    fn cpy(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn cpy(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
         self.compare(self.y, value);
     }
 
@@ -1168,7 +429,7 @@ impl Cpu {
     }
 
     // Shifts and Rotates
-    fn asl_acc(&mut self) {
+    pub(crate) fn asl_acc(&mut self) {
         let mut value = self.a;
         if (value >> 7) != 0 {
             self.st |= 0x01; // Carry
@@ -1180,21 +441,21 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn asl(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_value = bus.read(addr);
+    pub(crate) fn asl(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_value = self.read(bus, addr);
         if (old_value >> 7) != 0 {
             self.st |= 0x01;
         } else {
             self.st &= !0x01;
         }
         let new_value = old_value << 1;
-        bus.write(addr, old_value);
-        bus.write(addr, new_value);
+        self.write(bus, addr, old_value);
+        self.write(bus, addr, new_value);
         self.update_zero_negative_flags(new_value);
     }
 
-    fn lsr_acc(&mut self) {
+    pub(crate) fn lsr_acc(&mut self) {
         let mut value = self.a;
         if (value & 0x01) != 0 {
             self.st |= 0x01;
@@ -1206,21 +467,21 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn lsr(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_value = bus.read(addr);
+    pub(crate) fn lsr(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_value = self.read(bus, addr);
         if (old_value & 0x01) != 0 {
             self.st |= 0x01;
         } else {
             self.st &= !0x01;
         }
         let new_value = old_value >> 1;
-        bus.write(addr, old_value);
-        bus.write(addr, new_value);
+        self.write(bus, addr, old_value);
+        self.write(bus, addr, new_value);
         self.update_zero_negative_flags(new_value);
     }
 
-    fn rol_acc(&mut self) {
+    pub(crate) fn rol_acc(&mut self) {
         let mut value = self.a;
         let old_carry = self.st & 0x01;
         if (value >> 7) != 0 {
@@ -1233,9 +494,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn rol(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_value = bus.read(addr);
+    pub(crate) fn rol(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_value = self.read(bus, addr);
         let old_carry = self.st & 0x01;
         if (old_value >> 7) != 0 {
             self.st |= 0x01;
@@ -1243,12 +504,12 @@ impl Cpu {
             self.st &= !0x01;
         }
         let new_value = (old_value << 1) | old_carry;
-        bus.write(addr, old_value);
-        bus.write(addr, new_value);
+        self.write(bus, addr, old_value);
+        self.write(bus, addr, new_value);
         self.update_zero_negative_flags(new_value);
     }
 
-    fn ror_acc(&mut self) {
+    pub(crate) fn ror_acc(&mut self) {
         let mut value = self.a;
         let old_carry = (self.st & 0x01) << 7;
         if (value & 0x01) != 0 {
@@ -1261,9 +522,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn ror(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_value = bus.read(addr);
+    pub(crate) fn ror(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_value = self.read(bus, addr);
         let old_carry = (self.st & 0x01) << 7;
         if (old_value & 0x01) != 0 {
             self.st |= 0x01;
@@ -1271,8 +532,8 @@ impl Cpu {
             self.st &= !0x01;
         }
         let new_value = (old_value >> 1) | old_carry;
-        bus.write(addr, old_value);
-        bus.write(addr, new_value);
+        self.write(bus, addr, old_value);
+        self.write(bus, addr, new_value);
         self.update_zero_negative_flags(new_value);
     }
 
@@ -1291,9 +552,9 @@ impl Cpu {
     }
 
     // Arithmetic
-    fn adc(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn adc(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
 
         let carry_in = if (self.st & 0x01) != 0 { 1 } else { 0 };
         let sum = (self.a as u16) + (value as u16) + carry_in;
@@ -1319,9 +580,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn sbc(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn sbc(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let value = bus.read(addr);
+        let value = self.read(bus, addr);
 
         // SBC is ADC with inverted value
         let value = value ^ 0xFF; // Invert bits
@@ -1348,40 +609,34 @@ impl Cpu {
     }
 
     // Control Flow
-    fn jmp(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn jmp(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
         self.pc = addr;
     }
 
-    fn jsr(&mut self, bus: &mut Bus) {
-        let sub_addr = self.fetch_word(bus);
-
-        // Push PC + 2 - 1 (which is PC - 1 since fetch_word advanced pc by 2, wait)
-        // fetch_word advances PC by 2.
-        // Current PC points to next instruction.
-        // We want to push the address of the 3rd byte of JSR instruction.
-        // Original PC -> JSR opcode
-        // PC+1 -> sub_addr LO
-        // PC+2 -> sub_addr HI
-        // After fetch_word, PC points to next instruction (PC+3).
-        // So we push PC - 1.
-
-        let ret_addr = self.pc.wrapping_sub(1);
+    pub(crate) fn jsr(&mut self, bus: &mut Bus) {
+        // The low byte is fetched before the return address is pushed and the
+        // high byte after, so the pushed address is that of the high byte.
+        let lo = self.fetch_byte(bus) as u16;
+        self.read(bus, 0x0100 + self.sp as u16);
+        let ret_addr = self.pc;
         self.push(bus, (ret_addr >> 8) as u8);
         self.push(bus, (ret_addr & 0xFF) as u8);
-
-        self.pc = sub_addr;
+        let hi = self.read(bus, self.pc) as u16;
+        self.pc = lo | (hi << 8);
     }
 
-    fn rts(&mut self, bus: &mut Bus) {
+    pub(crate) fn rts(&mut self, bus: &mut Bus) {
+        self.read(bus, 0x0100 + self.sp as u16);
         let lo = self.pop(bus) as u16;
         let hi = self.pop(bus) as u16;
         let ret_addr = lo | (hi << 8);
+        self.read(bus, ret_addr);
         self.pc = ret_addr.wrapping_add(1);
     }
 
     // System
-    fn brk(&mut self, bus: &mut Bus) {
+    pub(crate) fn brk(&mut self, bus: &mut Bus) {
         if Self::irq_log_enabled() {
             #[cfg(not(target_arch = "wasm32"))]
             println!(
@@ -1390,68 +645,49 @@ impl Cpu {
             );
         }
 
-        // Push PC + 2 (BRK is 2 bytes, but usually padding byte is skipped by interrupt handler?)
-        // 6502 BRK pushes PC+2.
+        // BRK skips the padding byte read on its second cycle, so it pushes PC + 2.
         let pc = self.pc.wrapping_add(1);
-        self.push(bus, (pc >> 8) as u8);
-        self.push(bus, (pc & 0xFF) as u8);
-        self.push(bus, self.st | 0x10 | 0x20); // Break flag set, bit 5 set
-        self.st |= 0x04; // Set Interrupt Disable
-
-        // Load vector from 0xFFFE
-        let lo = bus.read(0xFFFE) as u16;
-        let hi = bus.read(0xFFFF) as u16;
-        self.pc = lo | (hi << 8);
+        self.enter_interrupt(bus, pc, self.st | 0x10 | 0x20); // Break flag set, bit 5 set
     }
 
-    pub fn nmi(&mut self, bus: &mut Bus) {
-        if Self::irq_log_enabled() {
-            #[cfg(not(target_arch = "wasm32"))]
-            println!("[IRQ] NMI at PC=${:04X}", self.pc);
-        }
-
-        self.push(bus, (self.pc >> 8) as u8);
-        self.push(bus, (self.pc & 0xFF) as u8);
-        self.push(bus, self.st & !0x10 | 0x20); // Break flag clear, bit 5 set
+    /// Push the return address and status, set I and jump through the vector.
+    /// An NMI raised before the vector fetch hijacks BRK and IRQ, which then
+    /// land in the NMI handler (with the B flag as pushed).
+    fn enter_interrupt(&mut self, bus: &mut Bus, pc: u16, status: u8) {
+        self.push(bus, (pc >> 8) as u8);
+        self.push(bus, (pc & 0xFF) as u8);
+        self.push(bus, status);
         self.st |= 0x04; // Set Interrupt Disable
 
-        // Load vector from 0xFFFA
-        let lo = bus.read(0xFFFA) as u16;
-        let hi = bus.read(0xFFFB) as u16;
+        let vector = if bus.ppu.nmi_interrupt {
+            bus.ppu.nmi_interrupt = false;
+            0xFFFA
+        } else {
+            0xFFFE
+        };
+        let lo = self.read(bus, vector) as u16;
+        let hi = self.read(bus, vector + 1) as u16;
         self.pc = lo | (hi << 8);
-    }
-
-    pub fn irq(&mut self, bus: &mut Bus) {
-        // IRQ is maskable: only fires when I flag is clear
-        if (self.st & 0x04) != 0 {
-            return;
-        }
-        if Self::irq_log_enabled() {
-            #[cfg(not(target_arch = "wasm32"))]
-            println!("[IRQ] IRQ at PC=${:04X}", self.pc);
-        }
-        self.push(bus, (self.pc >> 8) as u8);
-        self.push(bus, (self.pc & 0xFF) as u8);
-        self.push(bus, self.st & !0x10 | 0x20); // Break flag clear, bit 5 set
-        self.st |= 0x04; // Set Interrupt Disable
 
-        // Load vector from 0xFFFE (same as BRK)
-        let lo = bus.read(0xFFFE) as u16;
-        let hi = bus.read(0xFFFF) as u16;
-        self.pc = lo | (hi << 8);
+        // The first instruction of the handler always runs before the next
+        // interrupt is taken.
+        self.nmi_pending = false;
+        self.irq_pending = false;
     }
 
-    fn rti(&mut self, bus: &mut Bus) {
-        self.plp(bus);
+    pub(crate) fn rti(&mut self, bus: &mut Bus) {
+        self.read(bus, 0x0100 + self.sp as u16);
+        self.pull_status(bus);
         let lo = self.pop(bus) as u16;
         let hi = self.pop(bus) as u16;
         self.pc = lo | (hi << 8);
     }
+
     // Unofficial Opcode Implementations
 
-    fn slo(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_data = bus.read(addr);
+    pub(crate) fn slo(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_data = self.read(bus, addr);
 
         // ASL part
         if (old_data & 0x80) != 0 {
@@ -1460,17 +696,17 @@ impl Cpu {
             self.st &= !0x01;
         }
         let new_data = old_data << 1;
-        bus.write(addr, old_data);
-        bus.write(addr, new_data);
+        self.write(bus, addr, old_data);
+        self.write(bus, addr, new_data);
 
         // ORA part
         self.a |= new_data;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn rla(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_data = bus.read(addr);
+    pub(crate) fn rla(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_data = self.read(bus, addr);
 
         // ROL part
         let carry_in = if (self.st & 0x01) != 0 { 1 } else { 0 };
@@ -1480,17 +716,17 @@ impl Cpu {
             self.st &= !0x01;
         }
         let new_data = (old_data << 1) | carry_in;
-        bus.write(addr, old_data);
-        bus.write(addr, new_data);
+        self.write(bus, addr, old_data);
+        self.write(bus, addr, new_data);
 
         // AND part
         self.a &= new_data;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn sre(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_data = bus.read(addr);
+    pub(crate) fn sre(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_data = self.read(bus, addr);
 
         // LSR part
         if (old_data & 0x01) != 0 {
@@ -1499,17 +735,17 @@ impl Cpu {
             self.st &= !0x01;
         }
         let new_data = old_data >> 1;
-        bus.write(addr, old_data);
-        bus.write(addr, new_data);
+        self.write(bus, addr, old_data);
+        self.write(bus, addr, new_data);
 
         // EOR part
         self.a ^= new_data;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn rra(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_data = bus.read(addr);
+    pub(crate) fn rra(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_data = self.read(bus, addr);
 
         // ROR part
         let carry_in = if (self.st & 0x01) != 0 { 0x80 } else { 0 };
@@ -1519,48 +755,48 @@ impl Cpu {
             self.st &= !0x01;
         }
         let new_data = (old_data >> 1) | carry_in;
-        bus.write(addr, old_data);
-        bus.write(addr, new_data);
+        self.write(bus, addr, old_data);
+        self.write(bus, addr, new_data);
 
         // ADC part
         self.add_to_accumulator(new_data);
     }
 
-    fn sax(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
+    pub(crate) fn sax(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
         let data = self.a & self.x;
-        bus.write(addr, data);
+        self.write(bus, addr, data);
     }
 
-    fn lax(&mut self, bus: &mut Bus, mode: AddressingMode) {
+    pub(crate) fn lax(&mut self, bus: &mut Bus, mode: AddressingMode) {
         let addr = self.get_operand_address(bus, &mode);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         self.a = data;
         self.x = data;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn dcp(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_data = bus.read(addr);
+    pub(crate) fn dcp(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_data = self.read(bus, addr);
 
         // DEC part
         let new_data = old_data.wrapping_sub(1);
-        bus.write(addr, old_data);
-        bus.write(addr, new_data);
+        self.write(bus, addr, old_data);
+        self.write(bus, addr, new_data);
 
         // CMP part
         self.compare(self.a, new_data);
     }
 
-    fn isc(&mut self, bus: &mut Bus, mode: AddressingMode) {
-        let addr = self.get_operand_address(bus, &mode);
-        let old_data = bus.read(addr);
+    pub(crate) fn isc(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        let addr = self.get_store_address(bus, &mode);
+        let old_data = self.read(bus, addr);
 
         // INC part
         let new_data = old_data.wrapping_add(1);
-        bus.write(addr, old_data);
-        bus.write(addr, new_data);
+        self.write(bus, addr, old_data);
+        self.write(bus, addr, new_data);
 
         // SBC part
         let value = new_data ^ 0xFF;
@@ -1585,9 +821,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn alr(&mut self, bus: &mut Bus) {
+    pub(crate) fn alr(&mut self, bus: &mut Bus) {
         let addr = self.get_operand_address(bus, &AddressingMode::Immediate);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         self.a &= data;
 
         if (self.a & 0x01) != 0 {
@@ -1599,9 +835,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn anc(&mut self, bus: &mut Bus) {
+    pub(crate) fn anc(&mut self, bus: &mut Bus) {
         let addr = self.get_operand_address(bus, &AddressingMode::Immediate);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         self.a &= data;
         self.update_zero_negative_flags(self.a);
         if (self.st & 0x80) != 0 {
@@ -1611,9 +847,9 @@ impl Cpu {
         }
     }
 
-    fn arr(&mut self, bus: &mut Bus) {
+    pub(crate) fn arr(&mut self, bus: &mut Bus) {
         let addr = self.get_operand_address(bus, &AddressingMode::Immediate);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         self.a &= data;
 
         let carry_in = if (self.st & 0x01) != 0 { 0x80 } else { 0 };
@@ -1640,9 +876,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
-    fn axs(&mut self, bus: &mut Bus) {
+    pub(crate) fn axs(&mut self, bus: &mut Bus) {
         let addr = self.get_operand_address(bus, &AddressingMode::Immediate);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         let val = self.a & self.x;
         let result = val.wrapping_sub(data);
 
@@ -1655,9 +891,9 @@ impl Cpu {
         self.update_zero_negative_flags(self.x);
     }
 
-    fn las(&mut self, bus: &mut Bus) {
+    pub(crate) fn las(&mut self, bus: &mut Bus) {
         let addr = self.get_operand_address(bus, &AddressingMode::AbsoluteY);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         let result = data & self.sp;
         self.a = result;
         self.x = result;
@@ -1665,73 +901,70 @@ impl Cpu {
         self.update_zero_negative_flags(result);
     }
 
-    fn xaa(&mut self, bus: &mut Bus) {
+    pub(crate) fn xaa(&mut self, bus: &mut Bus) {
         let addr = self.get_operand_address(bus, &AddressingMode::Immediate);
-        let data = bus.read(addr);
+        let data = self.read(bus, addr);
         self.a = self.x & data;
         self.update_zero_negative_flags(self.a);
     }
 
-    fn ahx(&mut self, bus: &mut Bus, mode: &AddressingMode) {
-        // AHX AbsoluteY (0x9F) and IndirectY (0x93)
-        // Store (A & X & (H + 1))
-        // This is a complex opcode with some variation in behavior.
-        // We'll use a common implementation for nestest compatibility.
-        // First we need the address, but AHX needs the HIGH byte of the address.
-        // Since get_operand_address already advanced PC, we have to follow its logic carefully.
+    pub(crate) fn lxa(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        // LXA Immediate (0xAB)
+        // A = X = (A | CONST) & imm. The constant varies between chips; 0xEE
+        // matches the value most test ROMs and other emulators assume.
+        let addr = self.get_operand_address(bus, &mode);
+        let data = self.read(bus, addr);
+        self.a = (self.a | 0xEE) & data;
+        self.x = self.a;
+        self.update_zero_negative_flags(self.a);
+    }
 
-        match mode {
-            AddressingMode::AbsoluteY => {
-                let base = self.fetch_word(bus);
-                let hi = (base >> 8) as u8;
-                let final_addr = base.wrapping_add(self.y as u16);
-                let val = self.a & self.x & hi.wrapping_add(1);
-                bus.write(final_addr, val);
-            }
-            AddressingMode::IndirectY => {
-                let ptr = self.fetch_byte(bus);
-                let lo = bus.read(ptr as u16) as u16;
-                let hi = bus.read(ptr.wrapping_add(1) as u16) as u16;
-                let base = lo | (hi << 8);
-                let final_addr = base.wrapping_add(self.y as u16);
-                let val = self.a & self.x & (hi as u8).wrapping_add(1);
-                bus.write(final_addr, val);
-            }
-            _ => {}
+    pub(crate) fn nop(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        // Official and unofficial NOPs read their operand but do nothing.
+        if mode != AddressingMode::NoneAddressing {
+            let addr = self.get_operand_address(bus, &mode);
+            self.read(bus, addr);
         }
     }
 
-    fn shy(&mut self, bus: &mut Bus) {
-        // SHY AbsoluteX (0x9C)
-        // Store (Y & (H + 1))
-        let base = self.fetch_word(bus);
-        let hi = (base >> 8) as u8;
-        let final_addr = base.wrapping_add(self.x as u16);
-        let val = self.y & hi.wrapping_add(1);
-        // Note: SHY can be unstable if page boundary is crossed.
-        // For simplicity, we implement the basic logic.
-        bus.write(final_addr, val);
+    pub(crate) fn jam(&mut self, _bus: &mut Bus, _mode: AddressingMode) {
+        // KIL/JAM: the CPU locks up until reset. Re-fetching the same opcode
+        // each step keeps PC pinned at the jammed instruction. (The real chip
+        // stops driving the bus; here each step costs the opcode fetch plus
+        // the dummy read.)
+        self.jammed = true;
+        self.pc = self.pc.wrapping_sub(1);
     }
 
-    fn shx(&mut self, bus: &mut Bus) {
-        // SHX AbsoluteY (0x9E)
-        // Store (X & (H + 1))
-        let base = self.fetch_word(bus);
-        let hi = (base >> 8) as u8;
-        let final_addr = base.wrapping_add(self.y as u16);
-        let val = self.x & hi.wrapping_add(1);
-        bus.write(final_addr, val);
+    // AHX, SHY, SHX and TAS store a register ANDed with the high byte of the
+    // base address plus one. The unstable address corruption on a page
+    // crossing isn't modelled.
+    pub(crate) fn ahx(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        self.store_and_high(bus, mode, self.a & self.x);
     }
 
-    fn tas(&mut self, bus: &mut Bus) {
-        // TAS AbsoluteY (0x9B)
-        // S = A & X, Store (S & (H + 1))
+    pub(crate) fn shy(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        self.store_and_high(bus, mode, self.y);
+    }
+
+    pub(crate) fn shx(&mut self, bus: &mut Bus, mode: AddressingMode) {
+        self.store_and_high(bus, mode, self.x);
+    }
+
+    pub(crate) fn tas(&mut self, bus: &mut Bus, mode: AddressingMode) {
         self.sp = self.a & self.x;
-        let base = self.fetch_word(bus);
-        let hi = (base >> 8) as u8;
-        let final_addr = base.wrapping_add(self.y as u16);
-        let val = self.sp & hi.wrapping_add(1);
-        bus.write(final_addr, val);
+        self.store_and_high(bus, mode, self.sp);
+    }
+
+    fn store_and_high(&mut self, bus: &mut Bus, mode: AddressingMode, value: u8) {
+        let addr = self.get_store_address(bus, &mode);
+        let index = if mode == AddressingMode::AbsoluteX {
+            self.x
+        } else {
+            self.y
+        };
+        let hi = (addr.wrapping_sub(index as u16) >> 8) as u8;
+        self.write(bus, addr, value & hi.wrapping_add(1));
     }
 
     fn add_to_accumulator(&mut self, value: u8) {
@@ -1756,7 +989,20 @@ impl Cpu {
         self.update_zero_negative_flags(self.a);
     }
 
+    /// Effective address for an instruction that only reads its operand.
+    /// Indexed modes spend an extra cycle, reading from the not-yet-carried
+    /// address, only when the index crosses a page.
     fn get_operand_address(&mut self, bus: &mut Bus, mode: &AddressingMode) -> u16 {
+        self.resolve_address(bus, mode, false)
+    }
+
+    /// Effective address for stores and read-modify-write instructions,
+    /// which always take the dummy read on indexed modes.
+    fn get_store_address(&mut self, bus: &mut Bus, mode: &AddressingMode) -> u16 {
+        self.resolve_address(bus, mode, true)
+    }
+
+    fn resolve_address(&mut self, bus: &mut Bus, mode: &AddressingMode, write: bool) -> u16 {
         match mode {
             AddressingMode::Immediate => {
                 let addr = self.pc;
@@ -1766,44 +1012,42 @@ impl Cpu {
             AddressingMode::ZeroPage => self.fetch_byte(bus) as u16,
             AddressingMode::ZeroPageX => {
                 let pos = self.fetch_byte(bus);
-                let addr = pos.wrapping_add(self.x) as u16;
-                addr
+                self.read(bus, pos as u16);
+                pos.wrapping_add(self.x) as u16
             }
             AddressingMode::ZeroPageY => {
                 let pos = self.fetch_byte(bus);
-                let addr = pos.wrapping_add(self.y) as u16;
-                addr
+                self.read(bus, pos as u16);
+                pos.wrapping_add(self.y) as u16
             }
             AddressingMode::Absolute => self.fetch_word(bus),
             AddressingMode::AbsoluteX => {
-                let addr = self.fetch_word(bus);
-                let addr = addr.wrapping_add(self.x as u16);
-                addr
+                let base = self.fetch_word(bus);
+                self.index_address(bus, base, self.x, write)
             }
             AddressingMode::AbsoluteY => {
-                let addr = self.fetch_word(bus);
-                let addr = addr.wrapping_add(self.y as u16);
-                addr
+                let base = self.fetch_word(bus);
+                self.index_address(bus, base, self.y, write)
             }
             AddressingMode::IndirectX => {
                 let base = self.fetch_byte(bus);
+                self.read(bus, base as u16);
                 let ptr = base.wrapping_add(self.x);
-                let lo = bus.read(ptr as u16) as u16;
-                let hi = bus.read(ptr.wrapping_add(1) as u16) as u16;
+                let lo = self.read(bus, ptr as u16) as u16;
+                let hi = self.read(bus, ptr.wrapping_add(1) as u16) as u16;
                 lo | (hi << 8)
             }
             AddressingMode::IndirectY => {
                 let base = self.fetch_byte(bus);
-                let lo = bus.read(base as u16) as u16;
-                let hi = bus.read(base.wrapping_add(1) as u16) as u16;
+                let lo = self.read(bus, base as u16) as u16;
+                let hi = self.read(bus, base.wrapping_add(1) as u16) as u16;
                 let deref_base = lo | (hi << 8);
-                let addr = deref_base.wrapping_add(self.y as u16);
-                addr
+                self.index_address(bus, deref_base, self.y, write)
             }
             AddressingMode::Indirect => {
                 // JMP Indirect
                 let addr = self.fetch_word(bus);
-                let lo = bus.read(addr) as u16;
+                let lo = self.read(bus, addr) as u16;
 
                 // Hardware bug: if addr low byte is 0xFF, hi byte is read from XX00, not XX00+100
                 let hi_addr = if (addr & 0x00FF) == 0x00FF {
@@ -1812,7 +1056,7 @@ impl Cpu {
                     addr.wrapping_add(1)
                 };
 
-                let hi = bus.read(hi_addr) as u16;
+                let hi = self.read(bus, hi_addr) as u16;
                 lo | (hi << 8)
             }
             AddressingMode::NoneAddressing => 0,
@@ -1820,52 +1064,64 @@ impl Cpu {
         }
     }
 
+    fn index_address(&mut self, bus: &mut Bus, base: u16, index: u8, write: bool) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        let crossed = (base & 0xFF00) != (addr & 0xFF00);
+        if crossed || write {
+            // The low byte has been added but the carry into the high byte
+            // hasn't, so this cycle reads from the wrong page.
+            self.read(bus, (base & 0xFF00) | (addr & 0x00FF));
+        }
+        addr
+    }
+
     fn fetch_word(&mut self, bus: &mut Bus) -> u16 {
         let lo = self.fetch_byte(bus) as u16;
         let hi = self.fetch_byte(bus) as u16;
         lo | (hi << 8)
     }
 
-    pub fn get_absolute_address(&mut self, bus: &mut Bus, mode: &AddressingMode, addr: u16) -> u16 {
+    /// Resolve the effective address of the operand at `addr` without side effects.
+    pub fn get_absolute_address(&self, bus: &Bus, mode: &AddressingMode, addr: u16) -> u16 {
         match mode {
-            AddressingMode::ZeroPage => bus.read(addr) as u16,
+            AddressingMode::ZeroPage => bus.peek(addr) as u16,
             AddressingMode::Accumulator => 0,
             AddressingMode::Absolute => {
-                let lo = bus.read(addr) as u16;
-                let hi = bus.read(addr + 1) as u16;
+                let lo = bus.peek(addr) as u16;
+                let hi = bus.peek(addr + 1) as u16;
                 lo | (hi << 8)
             }
             AddressingMode::ZeroPageX => {
-                let pos = bus.read(addr);
+                let pos = bus.peek(addr);
                 pos.wrapping_add(self.x) as u16
             }
             AddressingMode::ZeroPageY => {
-                let pos = bus.read(addr);
+                let pos = bus.peek(addr);
                 pos.wrapping_add(self.y) as u16
             }
             AddressingMode::AbsoluteX => {
-                let lo = bus.read(addr) as u16;
-                let hi = bus.read(addr + 1) as u16;
+                let lo = bus.peek(addr) as u16;
+                let hi = bus.peek(addr + 1) as u16;
                 let base = lo | (hi << 8);
                 base.wrapping_add(self.x as u16)
             }
             AddressingMode::AbsoluteY => {
-                let lo = bus.read(addr) as u16;
-                let hi = bus.read(addr + 1) as u16;
+                let lo = bus.peek(addr) as u16;
+                let hi = bus.peek(addr + 1) as u16;
                 let base = lo | (hi << 8);
                 base.wrapping_add(self.y as u16)
             }
             AddressingMode::IndirectX => {
-                let base = bus.read(addr);
+                let base = bus.peek(addr);
                 let ptr = base.wrapping_add(self.x);
-                let lo = bus.read(ptr as u16) as u16;
-                let hi = bus.read(ptr.wrapping_add(1) as u16) as u16;
+                let lo = bus.peek(ptr as u16) as u16;
+                let hi = bus.peek(ptr.wrapping_add(1) as u16) as u16;
                 lo | (hi << 8)
             }
             AddressingMode::IndirectY => {
-                let base = bus.read(addr);
-                let lo = bus.read(base as u16) as u16;
-                let hi = bus.read(base.wrapping_add(1) as u16) as u16;
+                let base = bus.peek(addr);
+                let lo = bus.peek(base as u16) as u16;
+                let hi = bus.peek(base.wrapping_add(1) as u16) as u16;
                 let deref_base = lo | (hi << 8);
                 deref_base.wrapping_add(self.y as u16)
             }
@@ -1893,7 +1149,7 @@ pub enum AddressingMode {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bus::Bus;
+    use crate::bus::{AccessKind, Bus};
     use crate::cartridge::Mirroring;
     use crate::ppu::Ppu;
 
@@ -1903,6 +1159,27 @@ mod tests {
         Bus::new(ppu, rom, 0, 8192, false)
     }
 
+    /// Dummy ROM whose NMI vector is $9000 (a NOP) and IRQ/BRK vector is $A000.
+    fn create_bus_with_vectors() -> Bus {
+        let ppu = Ppu::new(Mirroring::Horizontal, vec![0; 2048]);
+        let mut rom = vec![0; 0x8000];
+        rom[0x1000] = 0xEA;
+        rom[0x7FFA..0x7FFC].copy_from_slice(&[0x00, 0x90]);
+        rom[0x7FFE..0x8000].copy_from_slice(&[0x00, 0xA0]);
+        Bus::new(ppu, rom, 0, 8192, false)
+    }
+
+    fn load(bus: &mut Bus, addr: u16, program: &[u8]) {
+        for (i, byte) in program.iter().enumerate() {
+            bus.write(addr + i as u16, *byte);
+        }
+    }
+
+    fn accesses(bus: &mut Bus) -> Vec<(u16, AccessKind)> {
+        let log = bus.access_log.replace(Vec::new()).unwrap_or_default();
+        log.into_iter().map(|a| (a.addr, a.kind)).collect()
+    }
+
     #[test]
     fn test_0xa9_lda_immediate_load_data() {
         let mut bus = create_bus();
@@ -2076,4 +1353,183 @@ mod tests {
         assert_eq!(cpu.a, 0x80);
         assert!(cpu.st & 0x01 != 0); // Carry set from Neg flag
     }
+
+    #[test]
+    fn test_page_cross_penalty() {
+        let mut bus = create_bus();
+        let mut cpu = Cpu::new();
+        // LDA $00F0,X with X = 0x20 crosses into page 1; STA abs,X never pays extra.
+        for (i, byte) in [0xBD, 0xF0, 0x00, 0xBD, 0x10, 0x00, 0x9D, 0xF0, 0x00]
+            .iter()
+            .enumerate()
+        {
+            bus.write(0x0200 + i as u16, *byte);
+        }
+        cpu.x = 0x20;
+        cpu.pc = 0x0200;
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.step(&mut bus), 5);
+    }
+
+    #[test]
+    fn test_branch_cycles() {
+        let mut bus = create_bus();
+        let mut cpu = Cpu::new();
+        // BNE not taken, BEQ taken within the page, BEQ taken across a page.
+        bus.write(0x02F0, 0xD0);
+        bus.write(0x02F1, 0x10);
+        bus.write(0x02F2, 0xF0);
+        bus.write(0x02F3, 0x02);
+        bus.write(0x02F6, 0xF0);
+        bus.write(0x02F7, 0x10);
+        cpu.st = 0x02;
+        cpu.pc = 0x02F0;
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.pc, 0x02F6);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.pc, 0x0308);
+    }
+
+    #[test]
+    fn test_kil_jams_until_reset() {
+        let mut bus = create_bus();
+        let mut cpu = Cpu::new();
+        bus.write(0, 0x02);
+        cpu.pc = 0;
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert!(cpu.jammed);
+        assert_eq!(cpu.pc, 0);
+        cpu.reset(&mut bus);
+        assert!(!cpu.jammed);
+    }
+
+    #[test]
+    fn test_cycle_counts_match_table() {
+        // With X = Y = 0 nothing crosses a page and, with all flags set or
+        // clear as needed, branches aren't taken, so every opcode should take
+        // exactly its base cycle count.
+        for op in OPCODES.iter() {
+            let mut bus = create_bus();
+            let mut cpu = Cpu::new();
+            load(&mut bus, 0x0200, &[op.code, 0x10, 0x00]);
+            cpu.pc = 0x0200;
+            cpu.st = match op.code {
+                0x10 | 0x50 | 0x90 | 0xD0 => 0xC3, // BPL BVC BCC BNE
+                _ => 0x24,
+            };
+            assert_eq!(
+                cpu.step(&mut bus),
+                op.cycles as u16,
+                "{:02X} {}",
+                op.code,
+                op.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_dummy_reads_and_rmw_writes() {
+        use AccessKind::{Read, Write};
+        let mut bus = create_bus();
+        let mut cpu = Cpu::new();
+        // LDA $02F0,X (crosses), STA $0200,X (no cross), INC $10
+        load(
+            &mut bus,
+            0x0400,
+            &[0xBD, 0xF0, 0x02, 0x9D, 0x00, 0x02, 0xE6, 0x10],
+        );
+        cpu.x = 0x20;
+        cpu.pc = 0x0400;
+        bus.access_log = Some(Vec::new());
+
+        cpu.step(&mut bus);
+        assert_eq!(
+            accesses(&mut bus),
+            vec![
+                (0x0400, Read),
+                (0x0401, Read),
+                (0x0402, Read),
+                (0x0210, Read),
+                (0x0310, Read)
+            ]
+        );
+        cpu.step(&mut bus);
+        assert_eq!(
+            accesses(&mut bus),
+            vec![
+                (0x0403, Read),
+                (0x0404, Read),
+                (0x0405, Read),
+                (0x0220, Read),
+                (0x0220, Write)
+            ]
+        );
+        cpu.step(&mut bus);
+        assert_eq!(
+            accesses(&mut bus),
+            vec![
+                (0x0406, Read),
+                (0x0407, Read),
+                (0x0010, Read),
+                (0x0010, Write),
+                (0x0010, Write)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dummy_read_clears_vblank() {
+        let mut bus = create_bus();
+        let mut cpu = Cpu::new();
+        // LDA $20F2,X with X = $10: the dummy read hits $2002 before the real
+        // read of its mirror at $2102.
+        load(&mut bus, 0x0200, &[0xBD, 0xF2, 0x20]);
+        cpu.x = 0x10;
+        cpu.pc = 0x0200;
+        bus.ppu.status = 0x80;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a & 0x80, 0);
+    }
+
+    #[test]
+    fn test_cli_delays_irq_by_one_instruction() {
+        let mut bus = create_bus_with_vectors();
+        let mut cpu = Cpu::new();
+        load(&mut bus, 0x0200, &[0x58, 0xEA, 0xEA]); // CLI; NOP; NOP
+        cpu.pc = 0x0200;
+        cpu.st = 0x24;
+        // MMC3 IRQ; at reset its banks map the same ROM bytes as NROM here.
+        bus.mapper = 4;
+        bus.ppu.mmc3_irq_pending = true;
+
+        cpu.step(&mut bus); // CLI
+        cpu.step(&mut bus); // NOP runs before the IRQ is taken
+        assert_eq!(cpu.pc, 0x0202);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0xA000);
+        assert_eq!(bus.read(0x01FD), 0x02);
+        assert_eq!(bus.read(0x01FC), 0x02);
+        assert_eq!(bus.read(0x01FB) & 0x10, 0); // B clear
+    }
+
+    #[test]
+    fn test_nmi_hijacks_brk() {
+        let mut bus = create_bus_with_vectors();
+        let mut cpu = Cpu::new();
+        load(&mut bus, 0x0200, &[0x00, 0x00]);
+        cpu.pc = 0x0200;
+        bus.ppu.nmi_interrupt = true;
+
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x9000);
+        assert!(!bus.ppu.nmi_interrupt);
+        assert_eq!(bus.read(0x01FB) & 0x10, 0x10); // B set: it was a BRK
+
+        // The NMI was consumed by the hijack and isn't taken a second time.
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x9001);
+    }
 }
@@ -0,0 +1,519 @@
+use crate::ppu::Ppu;
+
+/// Both pattern tables side by side, 16x16 tiles each.
+pub const PATTERN_VIEW_WIDTH: usize = 256;
+pub const PATTERN_VIEW_HEIGHT: usize = 128;
+/// All four nametables in a 2x2 grid, as laid out in PPU address space.
+pub const NAMETABLE_VIEW_WIDTH: usize = 512;
+pub const NAMETABLE_VIEW_HEIGHT: usize = 480;
+/// 64 sprites in an 8x8 grid of 8x16 cells (8x8 sprites use the top half).
+pub const OAM_VIEW_WIDTH: usize = 64;
+pub const OAM_VIEW_HEIGHT: usize = 128;
+/// Palette RAM as two rows of 16 swatches: background, then sprites.
+pub const PALETTE_VIEW_WIDTH: usize = 256;
+pub const PALETTE_VIEW_HEIGHT: usize = 32;
+
+/// Overlays cover the 256x240 picture.
+pub const OVERLAY_WIDTH: usize = 256;
+pub const OVERLAY_HEIGHT: usize = 240;
+
+const SWATCH_SIZE: usize = 16;
+const SCROLL_RECT_COLOR: (u8, u8, u8) = (0xFF, 0x20, 0x20);
+const SPRITE_BOX_COLOR: (u8, u8, u8) = (0x20, 0xFF, 0x20);
+const SPRITE_ZERO_HIT_COLOR: (u8, u8, u8) = (0xFF, 0xFF, 0x20);
+const MMC3_IRQ_COLOR: (u8, u8, u8) = (0xFF, 0x20, 0xFF);
+
+/// 3x5 digits for sprite labels, one row of three bits per entry.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Which marks `overlay` draws.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overlays {
+    /// A box around each on-screen sprite, labeled with its OAM index.
+    pub sprite_boxes: bool,
+    /// A cross at the sprite 0 hit and a line along its scanline.
+    pub sprite_zero_hit: bool,
+    /// A line along each scanline where the MMC3 IRQ fired.
+    pub mmc3_irq: bool,
+}
+
+impl Overlays {
+    pub fn any(self) -> bool {
+        self.sprite_boxes || self.sprite_zero_hit || self.mmc3_irq
+    }
+}
+
+/// One decoded OAM entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OamEntry {
+    pub index: u8,
+    pub x: u8,
+    /// Top scanline minus one, as stored in OAM.
+    pub y: u8,
+    pub tile: u8,
+    /// Sprite palette 0-3 (palette RAM entries $3F10 + 4 * palette).
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+pub fn oam_entries(ppu: &Ppu) -> Vec<OamEntry> {
+    ppu.oam
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(index, bytes)| OamEntry {
+            index: index as u8,
+            y: bytes[0],
+            tile: bytes[1],
+            palette: bytes[2] & 0x03,
+            behind_background: bytes[2] & 0x20 != 0,
+            flip_horizontal: bytes[2] & 0x40 != 0,
+            flip_vertical: bytes[2] & 0x80 != 0,
+            x: bytes[3],
+        })
+        .collect()
+}
+
+/// RGB of a palette RAM entry in the current output palette, without emphasis.
+fn color(ppu: &Ppu, palette_addr: u16) -> (u8, u8, u8) {
+    ppu.output_palette.colors[(ppu.peek_vram(palette_addr) & 0x3F) as usize]
+}
+
+/// RGB of `pixel` (0-3) in `palette` (0-3 background, 4-7 sprites).
+fn palette_color(ppu: &Ppu, palette: u8, pixel: u8) -> (u8, u8, u8) {
+    if pixel == 0 {
+        color(ppu, 0x3F00)
+    } else {
+        color(ppu, 0x3F00 + palette as u16 * 4 + pixel as u16)
+    }
+}
+
+fn put_pixel(buffer: &mut [u8], width: usize, x: usize, y: usize, rgb: (u8, u8, u8)) {
+    let idx = (y * width + x) * 4;
+    buffer[idx..idx + 4].copy_from_slice(&[rgb.0, rgb.1, rgb.2, 0xFF]);
+}
+
+/// Draw the 8x8 tile at `tile_addr` with its top-left corner at (`x`, `y`).
+fn draw_tile(
+    ppu: &Ppu,
+    buffer: &mut [u8],
+    width: usize,
+    (x, y): (usize, usize),
+    tile_addr: u16,
+    palette: u8,
+    (flip_h, flip_v): (bool, bool),
+) {
+    for row in 0..8u16 {
+        let src_row = if flip_v { 7 - row } else { row };
+        let lo = ppu.peek_vram(tile_addr + src_row);
+        let hi = ppu.peek_vram(tile_addr + src_row + 8);
+        for col in 0..8u8 {
+            let bit = if flip_h { col } else { 7 - col };
+            let pixel = ((lo >> bit) & 1) | (((hi >> bit) & 1) << 1);
+            let rgb = palette_color(ppu, palette, pixel);
+            put_pixel(buffer, width, x + col as usize, y + row as usize, rgb);
+        }
+    }
+}
+
+/// RGBA image of pattern tables $0000 and $1000, drawn with `palette`
+/// (0-3 background, 4-7 sprites) through the current CHR banking.
+pub fn pattern_tables(ppu: &Ppu, palette: u8) -> Vec<u8> {
+    let mut buffer = vec![0; PATTERN_VIEW_WIDTH * PATTERN_VIEW_HEIGHT * 4];
+    for table in 0..2u16 {
+        for tile in 0..256u16 {
+            let x = table as usize * 128 + (tile as usize % 16) * 8;
+            let y = (tile as usize / 16) * 8;
+            let addr = table * 0x1000 + tile * 16;
+            draw_tile(
+                ppu,
+                &mut buffer,
+                PATTERN_VIEW_WIDTH,
+                (x, y),
+                addr,
+                palette & 0x07,
+                (false, false),
+            );
+        }
+    }
+    buffer
+}
+
+/// Top-left corner of the visible screen within the 512x480 nametable
+/// view, from the scroll latched in `t` and fine X. Coarse Y 30 and 31
+/// (the attribute rows) wrap around to the top of the view.
+pub fn scroll_origin(ppu: &Ppu) -> (usize, usize) {
+    let t = ppu.t as usize;
+    let x = ((t >> 10) & 1) * 256 + (t & 0x1F) * 8 + ppu.x as usize;
+    let y = ((t >> 11) & 1) * 240 + ((t >> 5) & 0x1F) * 8 + ((t >> 12) & 0x07);
+    (x, y % NAMETABLE_VIEW_HEIGHT)
+}
+
+/// RGBA image of nametables $2000-$2FFF with the cartridge's mirroring
+/// applied and the current 256x240 scroll window outlined.
+pub fn nametables(ppu: &Ppu) -> Vec<u8> {
+    let mut buffer = vec![0; NAMETABLE_VIEW_WIDTH * NAMETABLE_VIEW_HEIGHT * 4];
+    let pattern_base = if ppu.ctrl & 0x10 != 0 { 0x1000 } else { 0 };
+
+    for table in 0..4u16 {
+        let base = 0x2000 + table * 0x400;
+        let origin_x = (table as usize % 2) * 256;
+        let origin_y = (table as usize / 2) * 240;
+        for row in 0..30u16 {
+            for col in 0..32u16 {
+                let tile = ppu.peek_vram(base + row * 32 + col) as u16;
+                let attr = ppu.peek_vram(base + 0x3C0 + (row / 4) * 8 + col / 4);
+                let shift = ((row & 2) << 1) | (col & 2);
+                let palette = (attr >> shift) & 0x03;
+                draw_tile(
+                    ppu,
+                    &mut buffer,
+                    NAMETABLE_VIEW_WIDTH,
+                    (origin_x + col as usize * 8, origin_y + row as usize * 8),
+                    pattern_base + tile * 16,
+                    palette,
+                    (false, false),
+                );
+            }
+        }
+    }
+
+    // The scroll window wraps around both axes of the 2x2 grid.
+    let (sx, sy) = scroll_origin(ppu);
+    for i in 0..256 {
+        let x = (sx + i) % NAMETABLE_VIEW_WIDTH;
+        put_pixel(&mut buffer, NAMETABLE_VIEW_WIDTH, x, sy, SCROLL_RECT_COLOR);
+        let y = (sy + 239) % NAMETABLE_VIEW_HEIGHT;
+        put_pixel(&mut buffer, NAMETABLE_VIEW_WIDTH, x, y, SCROLL_RECT_COLOR);
+    }
+    for i in 0..240 {
+        let y = (sy + i) % NAMETABLE_VIEW_HEIGHT;
+        put_pixel(&mut buffer, NAMETABLE_VIEW_WIDTH, sx, y, SCROLL_RECT_COLOR);
+        let x = (sx + 255) % NAMETABLE_VIEW_WIDTH;
+        put_pixel(&mut buffer, NAMETABLE_VIEW_WIDTH, x, y, SCROLL_RECT_COLOR);
+    }
+    buffer
+}
+
+/// RGBA image of all 64 sprites with their palettes and flips, in OAM order.
+pub fn oam_sprites(ppu: &Ppu) -> Vec<u8> {
+    let mut buffer = vec![0; OAM_VIEW_WIDTH * OAM_VIEW_HEIGHT * 4];
+    let tall = ppu.ctrl & 0x20 != 0;
+    let table_8x8 = if ppu.ctrl & 0x08 != 0 { 0x1000 } else { 0 };
+
+    for entry in oam_entries(ppu) {
+        let x = (entry.index as usize % 8) * 8;
+        let y = (entry.index as usize / 8) * 16;
+        let palette = entry.palette + 4;
+        let flips = (entry.flip_horizontal, entry.flip_vertical);
+        if tall {
+            // 8x16: bit 0 selects the table, and vertical flip swaps halves.
+            let base = (entry.tile as u16 & 1) * 0x1000 + (entry.tile as u16 & 0xFE) * 16;
+            let (top, bottom) = if entry.flip_vertical {
+                (base + 16, base)
+            } else {
+                (base, base + 16)
+            };
+            draw_tile(
+                ppu,
+                &mut buffer,
+                OAM_VIEW_WIDTH,
+                (x, y),
+                top,
+                palette,
+                flips,
+            );
+            draw_tile(
+                ppu,
+                &mut buffer,
+                OAM_VIEW_WIDTH,
+                (x, y + 8),
+                bottom,
+                palette,
+                flips,
+            );
+        } else {
+            let addr = table_8x8 + entry.tile as u16 * 16;
+            draw_tile(
+                ppu,
+                &mut buffer,
+                OAM_VIEW_WIDTH,
+                (x, y),
+                addr,
+                palette,
+                flips,
+            );
+        }
+    }
+    buffer
+}
+
+/// RGBA image of the 32 palette RAM entries as they read back through $3F00.
+pub fn palette_ram(ppu: &Ppu) -> Vec<u8> {
+    let mut buffer = vec![0; PALETTE_VIEW_WIDTH * PALETTE_VIEW_HEIGHT * 4];
+    for entry in 0..32u16 {
+        let rgb = color(ppu, 0x3F00 + entry);
+        let x0 = (entry as usize % 16) * SWATCH_SIZE;
+        let y0 = (entry as usize / 16) * SWATCH_SIZE;
+        for y in y0..y0 + SWATCH_SIZE {
+            for x in x0..x0 + SWATCH_SIZE {
+                put_pixel(&mut buffer, PALETTE_VIEW_WIDTH, x, y, rgb);
+            }
+        }
+    }
+    buffer
+}
+
+/// Like `put_pixel` on a 256x240 overlay, but clipped to the picture.
+fn put_overlay_pixel(buffer: &mut [u8], x: usize, y: usize, rgb: (u8, u8, u8)) {
+    if x < OVERLAY_WIDTH && y < OVERLAY_HEIGHT {
+        put_pixel(buffer, OVERLAY_WIDTH, x, y, rgb);
+    }
+}
+
+/// A dashed line across the picture, so the scanline stays visible.
+fn mark_scanline(buffer: &mut [u8], y: usize, rgb: (u8, u8, u8)) {
+    for x in (0..OVERLAY_WIDTH).filter(|x| x % 4 < 2) {
+        put_overlay_pixel(buffer, x, y, rgb);
+    }
+}
+
+fn draw_number(buffer: &mut [u8], (x, y): (usize, usize), number: u8, rgb: (u8, u8, u8)) {
+    let text = number.to_string();
+    for (i, digit) in text.bytes().enumerate() {
+        for (row, bits) in DIGITS[(digit - b'0') as usize].iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) != 0 {
+                    put_overlay_pixel(buffer, x + i * 4 + col, y + row, rgb);
+                }
+            }
+        }
+    }
+}
+
+/// Transparent 256x240 RGBA image with the `overlays` marks for the frame
+/// the PPU just finished, to draw over the picture with `blend_overlay`.
+pub fn overlay(ppu: &Ppu, overlays: Overlays) -> Vec<u8> {
+    let mut buffer = vec![0; OVERLAY_WIDTH * OVERLAY_HEIGHT * 4];
+
+    if overlays.sprite_boxes {
+        let height = if ppu.ctrl & 0x20 != 0 { 16 } else { 8 };
+        // Sprites are drawn one line below their Y; $EF and up are hidden.
+        for entry in oam_entries(ppu).iter().filter(|entry| entry.y < 0xEF) {
+            let (left, top) = (entry.x as usize, entry.y as usize + 1);
+            for i in 0..8 {
+                put_overlay_pixel(&mut buffer, left + i, top, SPRITE_BOX_COLOR);
+                put_overlay_pixel(&mut buffer, left + i, top + height - 1, SPRITE_BOX_COLOR);
+            }
+            for i in 0..height {
+                put_overlay_pixel(&mut buffer, left, top + i, SPRITE_BOX_COLOR);
+                put_overlay_pixel(&mut buffer, left + 7, top + i, SPRITE_BOX_COLOR);
+            }
+            // The label goes above the box, or below it at the top edge.
+            let label_y = if top >= 6 { top - 6 } else { top + height + 1 };
+            draw_number(&mut buffer, (left, label_y), entry.index, SPRITE_BOX_COLOR);
+        }
+    }
+
+    if overlays.sprite_zero_hit {
+        if let Some((x, y)) = ppu.sprite_zero_hit_dot {
+            let (x, y) = (x as usize, y as usize);
+            mark_scanline(&mut buffer, y, SPRITE_ZERO_HIT_COLOR);
+            for i in 0..7 {
+                put_overlay_pixel(
+                    &mut buffer,
+                    (x + i).wrapping_sub(3),
+                    y,
+                    SPRITE_ZERO_HIT_COLOR,
+                );
+                put_overlay_pixel(
+                    &mut buffer,
+                    x,
+                    (y + i).wrapping_sub(3),
+                    SPRITE_ZERO_HIT_COLOR,
+                );
+            }
+        }
+    }
+
+    if overlays.mmc3_irq {
+        for &scanline in &ppu.mmc3_irq_scanlines {
+            mark_scanline(&mut buffer, scanline as usize, MMC3_IRQ_COLOR);
+        }
+    }
+    buffer
+}
+
+/// Draw a 256x240 `overlay` over a `width` x `height` RGBA `frame`,
+/// stretching it to fit (e.g. over NTSC filter or scaler output).
+pub fn blend_overlay(frame: &mut [u8], width: usize, height: usize, overlay: &[u8]) {
+    for y in 0..height {
+        let src_y = y * OVERLAY_HEIGHT / height;
+        for x in 0..width {
+            let src = (src_y * OVERLAY_WIDTH + x * OVERLAY_WIDTH / width) * 4;
+            let alpha = overlay[src + 3] as u16;
+            if alpha == 0 {
+                continue;
+            }
+            let dst = (y * width + x) * 4;
+            for channel in 0..3 {
+                let (over, under) = (overlay[src + channel] as u16, frame[dst + channel] as u16);
+                frame[dst + channel] = ((over * alpha + under * (255 - alpha)) / 255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Mirroring;
+    use crate::ppu::SYSTEM_PALETTE;
+
+    fn rgba(buffer: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+        let idx = (y * width + x) * 4;
+        buffer[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_pattern_table_uses_selected_palette() {
+        let mut chr = vec![0; 0x2000];
+        chr[0x1000] = 0x80; // Tile 0 of table 1: top-left pixel is color 1
+        let mut ppu = Ppu::new(Mirroring::Horizontal, chr);
+        ppu.palette[0] = 0x0F;
+        ppu.palette[4 * 2 + 1] = 0x16;
+
+        let buffer = pattern_tables(&ppu, 2);
+        let (r, g, b) = SYSTEM_PALETTE[0x16];
+        assert_eq!(rgba(&buffer, PATTERN_VIEW_WIDTH, 128, 0), [r, g, b, 0xFF]);
+        let (r, g, b) = SYSTEM_PALETTE[0x0F];
+        assert_eq!(rgba(&buffer, PATTERN_VIEW_WIDTH, 129, 0), [r, g, b, 0xFF]);
+    }
+
+    #[test]
+    fn test_nametables_apply_mirroring() {
+        let mut chr = vec![0; 0x2000];
+        chr[16..24].fill(0xFF); // Tile 1: solid color 1
+        let mut ppu = Ppu::new(Mirroring::Vertical, chr);
+        ppu.palette[1] = 0x21;
+        ppu.vram[0x400 + 33] = 1; // Nametable $2400, row 1, column 1
+        ppu.t = 0x0400; // Scroll to $2400 so the outline sits at x = 256
+
+        let buffer = nametables(&ppu);
+        let (r, g, b) = SYSTEM_PALETTE[0x21];
+        // Vertical mirroring shows $2400 again at $2C00 (bottom right).
+        assert_eq!(rgba(&buffer, NAMETABLE_VIEW_WIDTH, 264, 8), [r, g, b, 0xFF]);
+        assert_eq!(
+            rgba(&buffer, NAMETABLE_VIEW_WIDTH, 264, 248),
+            [r, g, b, 0xFF]
+        );
+        assert_ne!(rgba(&buffer, NAMETABLE_VIEW_WIDTH, 8, 248), [r, g, b, 0xFF]);
+
+        let (r, g, b) = SCROLL_RECT_COLOR;
+        assert_eq!(
+            rgba(&buffer, NAMETABLE_VIEW_WIDTH, 256, 100),
+            [r, g, b, 0xFF]
+        );
+        assert_eq!(scroll_origin(&ppu), (256, 0));
+    }
+
+    #[test]
+    fn test_scroll_origin_wraps_attribute_rows() {
+        // $2800 selected with a Y scroll of $F8: coarse Y 31, past row 29.
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 0x2000]);
+        ppu.t = 0x0BE0;
+        assert_eq!(scroll_origin(&ppu), (0, 8));
+        let buffer = nametables(&ppu);
+        let (r, g, b) = SCROLL_RECT_COLOR;
+        assert_eq!(rgba(&buffer, NAMETABLE_VIEW_WIDTH, 100, 8), [r, g, b, 0xFF]);
+    }
+
+    #[test]
+    fn test_oam_entries_decode_attributes() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 0x2000]);
+        ppu.oam[4..8].copy_from_slice(&[0x20, 0x41, 0b1110_0010, 0x30]);
+        let entry = oam_entries(&ppu)[1];
+        assert_eq!(
+            entry,
+            OamEntry {
+                index: 1,
+                x: 0x30,
+                y: 0x20,
+                tile: 0x41,
+                palette: 2,
+                behind_background: true,
+                flip_horizontal: true,
+                flip_vertical: true,
+            }
+        );
+        assert_eq!(
+            oam_sprites(&ppu).len(),
+            OAM_VIEW_WIDTH * OAM_VIEW_HEIGHT * 4
+        );
+        assert_eq!(
+            palette_ram(&ppu).len(),
+            PALETTE_VIEW_WIDTH * PALETTE_VIEW_HEIGHT * 4
+        );
+    }
+
+    #[test]
+    fn test_overlay_marks() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 0x2000]);
+        ppu.oam.fill(0xFF);
+        ppu.oam[8..12].copy_from_slice(&[19, 0, 0, 40]); // Sprite 2 drawn from line 20
+        ppu.sprite_zero_hit_dot = Some((100, 50));
+        ppu.mmc3_irq_scanlines.push(120);
+
+        let none = overlay(&ppu, Overlays::default());
+        assert!(none.iter().all(|&byte| byte == 0));
+
+        let all = Overlays {
+            sprite_boxes: true,
+            sprite_zero_hit: true,
+            mmc3_irq: true,
+        };
+        let buffer = overlay(&ppu, all);
+        let color = |(r, g, b): (u8, u8, u8)| [r, g, b, 0xFF];
+        assert_eq!(
+            rgba(&buffer, OVERLAY_WIDTH, 40, 20),
+            color(SPRITE_BOX_COLOR)
+        );
+        assert_eq!(
+            rgba(&buffer, OVERLAY_WIDTH, 47, 27),
+            color(SPRITE_BOX_COLOR)
+        );
+        assert_eq!(rgba(&buffer, OVERLAY_WIDTH, 43, 23), [0; 4]);
+        // Label "2" above the box: its top row is solid.
+        assert_eq!(
+            rgba(&buffer, OVERLAY_WIDTH, 42, 14),
+            color(SPRITE_BOX_COLOR)
+        );
+        assert_eq!(
+            rgba(&buffer, OVERLAY_WIDTH, 100, 48),
+            color(SPRITE_ZERO_HIT_COLOR)
+        );
+        assert_eq!(
+            rgba(&buffer, OVERLAY_WIDTH, 0, 50),
+            color(SPRITE_ZERO_HIT_COLOR)
+        );
+        assert_eq!(rgba(&buffer, OVERLAY_WIDTH, 4, 120), color(MMC3_IRQ_COLOR));
+
+        // Blending stretches the overlay and leaves transparent areas alone.
+        let mut frame = vec![0xFF; 512 * 480 * 4];
+        blend_overlay(&mut frame, 512, 480, &buffer);
+        assert_eq!(rgba(&frame, 512, 9, 241), color(MMC3_IRQ_COLOR));
+        assert_eq!(rgba(&frame, 512, 9, 239), [0xFF; 4]);
+    }
+}
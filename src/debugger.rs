@@ -0,0 +1,1231 @@
+use crate::bus::{AccessKind, MemoryAccess};
+use crate::debug_view::oam_entries;
+use crate::disasm::{self, SymbolTable};
+use crate::Nes;
+
+/// Upper bound for step over / step out / run-to-scanline, so a routine that
+/// never returns doesn't hang the REPL (about ten seconds of emulated time).
+const MAX_RUN_CYCLES: usize = 29781 * 600;
+
+const OP_JSR: u8 = 0x20;
+const OP_RTI: u8 = 0x40;
+const OP_RTS: u8 = 0x60;
+
+const HELP: &str = "\
+break <addr> [if <expr>]          stop when PC reaches addr
+watch [r|w|rw|x] [ppu] <addr>[-<end>] [if <expr>]
+                                  stop on memory access (default rw, CPU space)
+delete <id> | list                remove / show breakpoints and watchpoints
+continue | pause                  resume or halt emulation
+step [n] | next | finish          step into / over / out
+scanline <n>                      run until the PPU reaches scanline n
+regs | mem [ppu] <addr> [len]     show registers / dump memory
+dis [addr] [count]                disassemble (default: 10 instructions at PC)
+oam                               list the 64 sprites with decoded attributes
+hide | show <bg|sprites|n|all>    hide or show the background, all sprites or sprite n
+symbols <file.nl|file.dbg>        load labels; addresses may then be given by name
+print <expr>                      evaluate an expression
+
+Expressions: numbers ($FF, 0xFF, 255), registers (A X Y P SP PC CYC SCANLINE DOT),
+VALUE (byte of the triggering access), [addr] (CPU memory), operators
+! + - & | == != < <= > >= && || and parentheses.";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AddressSpace {
+    Cpu,
+    Ppu,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    P,
+    Sp,
+    Pc,
+    Cycles,
+    Scanline,
+    Dot,
+    /// Value read or written by the access that triggered a watchpoint.
+    Value,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    BitAnd,
+    BitOr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+/// Condition expression attached to a breakpoint or watchpoint.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expr {
+    Number(u32),
+    Register(Register),
+    /// Byte at a CPU address, read with `Bus::peek`.
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected '{}'", token)),
+        }
+    }
+
+    /// Evaluate against the current machine state. `value` is the byte of the
+    /// access being checked (0 outside watchpoints).
+    pub fn evaluate(&self, nes: &Nes, value: u8) -> u32 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Register(reg) => match reg {
+                Register::A => nes.cpu.a as u32,
+                Register::X => nes.cpu.x as u32,
+                Register::Y => nes.cpu.y as u32,
+                Register::P => nes.cpu.st as u32,
+                Register::Sp => nes.cpu.sp as u32,
+                Register::Pc => nes.cpu.pc as u32,
+                Register::Cycles => nes.cpu.cycles as u32,
+                Register::Scanline => nes.bus.ppu.scanline as u32,
+                Register::Dot => nes.bus.ppu.cycle as u32,
+                Register::Value => value as u32,
+            },
+            Expr::Memory(addr) => nes.bus.peek(addr.evaluate(nes, value) as u16) as u32,
+            Expr::Not(inner) => (inner.evaluate(nes, value) == 0) as u32,
+            Expr::Binary(op, lhs, rhs) => {
+                let l = lhs.evaluate(nes, value);
+                let r = rhs.evaluate(nes, value);
+                match op {
+                    BinaryOp::Add => l.wrapping_add(r),
+                    BinaryOp::Sub => l.wrapping_sub(r),
+                    BinaryOp::BitAnd => l & r,
+                    BinaryOp::BitOr => l | r,
+                    BinaryOp::Eq => (l == r) as u32,
+                    BinaryOp::Ne => (l != r) as u32,
+                    BinaryOp::Lt => (l < r) as u32,
+                    BinaryOp::Le => (l <= r) as u32,
+                    BinaryOp::Gt => (l > r) as u32,
+                    BinaryOp::Ge => (l >= r) as u32,
+                    BinaryOp::And => (l != 0 && r != 0) as u32,
+                    BinaryOp::Or => (l != 0 || r != 0) as u32,
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "${:X}", n),
+            Expr::Register(reg) => write!(f, "{:?}", reg),
+            Expr::Memory(addr) => write!(f, "[{}]", addr),
+            Expr::Not(inner) => write!(f, "!{}", inner),
+            Expr::Binary(op, lhs, rhs) => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::BitAnd => "&",
+                    BinaryOp::BitOr => "|",
+                    BinaryOp::Eq => "==",
+                    BinaryOp::Ne => "!=",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                    BinaryOp::And => "&&",
+                    BinaryOp::Or => "||",
+                };
+                write!(f, "({} {} {})", lhs, op, rhs)
+            }
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '$' || c == '_' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if ["==", "!=", "<=", ">=", "&&", "||"].contains(&pair.as_str()) {
+                tokens.push(pair);
+                i += 2;
+            } else if "()[]!+-&|<>".contains(c) {
+                tokens.push(c.to_string());
+                i += 1;
+            } else {
+                return Err(format!("unexpected character '{}'", c));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse `$FF`, `0xFF` or decimal.
+pub fn parse_number(text: &str) -> Option<u32> {
+    if let Some(hex) = text.strip_prefix('$') {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            Some(t) => Err(format!("expected '{}', found '{}'", token, t)),
+            None => Err(format!("expected '{}'", token)),
+        }
+    }
+
+    /// Parse one precedence level of left-associative binary operators.
+    fn parse_level(
+        &mut self,
+        ops: &[(&str, BinaryOp)],
+        next: fn(&mut Parser) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut lhs = next(self)?;
+        while let Some(&(_, op)) = ops.iter().find(|(token, _)| self.peek() == Some(*token)) {
+            self.pos += 1;
+            let rhs = next(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        self.parse_level(&[("||", BinaryOp::Or)], Parser::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        self.parse_level(&[("&&", BinaryOp::And)], Parser::parse_comparison)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        self.parse_level(
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            Parser::parse_bitwise,
+        )
+    }
+
+    fn parse_bitwise(&mut self) -> Result<Expr, String> {
+        self.parse_level(
+            &[("&", BinaryOp::BitAnd), ("|", BinaryOp::BitOr)],
+            Parser::parse_additive,
+        )
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        self.parse_level(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Parser::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let token = self.next().ok_or("unexpected end of expression")?;
+        match token.as_str() {
+            "(" => {
+                let expr = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            "[" => {
+                let expr = self.parse_or()?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            _ => {
+                let register = match token.to_ascii_uppercase().as_str() {
+                    "A" => Some(Register::A),
+                    "X" => Some(Register::X),
+                    "Y" => Some(Register::Y),
+                    "P" => Some(Register::P),
+                    "SP" => Some(Register::Sp),
+                    "PC" => Some(Register::Pc),
+                    "CYC" => Some(Register::Cycles),
+                    "SCANLINE" => Some(Register::Scanline),
+                    "DOT" => Some(Register::Dot),
+                    "VALUE" => Some(Register::Value),
+                    _ => None,
+                };
+                if let Some(register) = register {
+                    Ok(Expr::Register(register))
+                } else {
+                    parse_number(&token)
+                        .map(Expr::Number)
+                        .ok_or_else(|| format!("unknown token '{}'", token))
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: u32,
+    pub addr: u16,
+    pub condition: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub id: u32,
+    pub space: AddressSpace,
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    pub condition: Option<Expr>,
+}
+
+impl Watchpoint {
+    /// CPU addresses match through their mirrors, so `$07F0-$0B00` catches
+    /// $07F0-$07FF and $0000-$0300 alike.
+    fn contains(&self, addr: u16) -> bool {
+        if self.space == AddressSpace::Ppu {
+            return (self.start..=self.end).contains(&addr);
+        }
+        let addr = canonical_cpu_addr(addr);
+        let (first, last, period) = match addr {
+            0x0000..=0x07FF => (0x0000, 0x1FFF, 0x800),
+            0x2000..=0x2007 => (0x2000, 0x3FFF, 8),
+            _ => return (self.start..=self.end).contains(&addr),
+        };
+        let (lo, hi) = (self.start.max(first) as u32, self.end.min(last) as u32);
+        // The first mirror of `addr` at or after `lo`; periods divide 0x10000.
+        let mirror = lo + (addr as u32 + 0x10000 - lo) % period;
+        mirror <= hi
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StopReason {
+    Breakpoint {
+        id: u32,
+        pc: u16,
+    },
+    Watchpoint {
+        id: u32,
+        space: AddressSpace,
+        access: MemoryAccess,
+    },
+    Executed {
+        id: u32,
+        pc: u16,
+    },
+    Step,
+    Scanline(u16),
+    /// A step over / out / run-to-scanline gave up after `MAX_RUN_CYCLES`.
+    Timeout,
+    Paused,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Breakpoint { id, pc } => write!(f, "breakpoint {} at ${:04X}", id, pc),
+            StopReason::Watchpoint { id, space, access } => write!(
+                f,
+                "watchpoint {}: {} {} ${:04X} = ${:02X}",
+                id,
+                match space {
+                    AddressSpace::Cpu => "CPU",
+                    AddressSpace::Ppu => "PPU",
+                },
+                match access.kind {
+                    AccessKind::Read => "read",
+                    AccessKind::Write => "write",
+                },
+                access.addr,
+                access.value
+            ),
+            StopReason::Executed { id, pc } => {
+                write!(f, "watchpoint {}: execute ${:04X}", id, pc)
+            }
+            StopReason::Step => write!(f, "step"),
+            StopReason::Scanline(line) => write!(f, "reached scanline {}", line),
+            StopReason::Timeout => write!(f, "gave up after {} cycles", MAX_RUN_CYCLES),
+            StopReason::Paused => write!(f, "paused"),
+        }
+    }
+}
+
+/// Mirrored CPU addresses map to their canonical form so a watchpoint on
+/// $2002 also catches $3FFA, and one on $0300 catches $0B00.
+fn canonical_cpu_addr(addr: u16) -> u16 {
+    match addr {
+        0x0000..=0x1FFF => addr & 0x07FF,
+        0x2000..=0x3FFF => addr & 0x2007,
+        _ => addr,
+    }
+}
+
+/// Parse a number or, failing that, a label from `symbols`.
+fn parse_address(text: &str, symbols: &SymbolTable) -> Result<u16, String> {
+    parse_number(text)
+        .filter(|n| *n <= 0xFFFF)
+        .map(|n| n as u16)
+        .or_else(|| symbols.address(text))
+        .ok_or_else(|| format!("bad address '{}'", text))
+}
+
+/// Split `args` at an `if` keyword into the leading words and an optional condition.
+fn split_condition(args: &str) -> Result<(Vec<&str>, Option<Expr>), String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    match words.iter().position(|w| *w == "if") {
+        Some(index) => {
+            let condition = Expr::parse(&words[index + 1..].join(" "))?;
+            Ok((words[..index].to_vec(), Some(condition)))
+        }
+        None => Ok((words, None)),
+    }
+}
+
+/// One line per OAM entry: position, tile, palette and flags (B = behind
+/// background, H/V = flipped).
+fn command_oam(nes: &Nes) -> String {
+    let lines: Vec<String> = oam_entries(&nes.bus.ppu)
+        .iter()
+        .map(|sprite| {
+            format!(
+                "{:02}: X={:02X} Y={:02X} tile={:02X} pal={} {}{}{}",
+                sprite.index,
+                sprite.x,
+                sprite.y,
+                sprite.tile,
+                sprite.palette,
+                if sprite.behind_background { 'B' } else { '-' },
+                if sprite.flip_horizontal { 'H' } else { '-' },
+                if sprite.flip_vertical { 'V' } else { '-' },
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// `hide`/`show`: display-only layer switches.
+fn command_layer(nes: &mut Nes, args: &str, hidden: bool) -> Result<String, String> {
+    match args {
+        "bg" => nes.set_hide_background(hidden),
+        "sprites" => nes.set_hide_sprites(hidden),
+        "all" => {
+            nes.set_hide_background(hidden);
+            nes.set_hide_sprites(hidden);
+            for index in 0..64 {
+                nes.set_sprite_hidden(index, hidden);
+            }
+        }
+        _ => match parse_number(args) {
+            Some(index) if index < 64 => nes.set_sprite_hidden(index as u8, hidden),
+            _ => return Err("usage: hide|show <bg|sprites|0-63|all>".to_string()),
+        },
+    }
+    let hidden_sprites: Vec<String> = (0..64)
+        .filter(|&index| nes.sprite_hidden(index))
+        .map(|index| index.to_string())
+        .collect();
+    Ok(format!(
+        "Background {}, sprites {}, hidden sprites: {}",
+        if nes.hide_background() {
+            "hidden"
+        } else {
+            "shown"
+        },
+        if nes.hide_sprites() {
+            "hidden"
+        } else {
+            "shown"
+        },
+        if hidden_sprites.is_empty() {
+            "none".to_string()
+        } else {
+            hidden_sprites.join(" ")
+        }
+    ))
+}
+
+/// Breakpoints, watchpoints and stepping on top of `Nes::tick`.
+///
+/// Frontends drive emulation one instruction at a time through
+/// [`Debugger::before_instruction`], `Nes::tick` and
+/// [`Debugger::after_instruction`], or hand the debugger text commands through
+/// [`Debugger::execute_command`].
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    pub watchpoints: Vec<Watchpoint>,
+    /// While paused, frontends should not advance emulation.
+    pub paused: bool,
+    /// Labels used by `dis` and accepted wherever an address is expected.
+    pub symbols: SymbolTable,
+    next_id: u32,
+    /// Set when we stopped in front of an instruction, so resuming executes it
+    /// instead of hitting the same breakpoint again.
+    resuming: bool,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            paused: false,
+            symbols: SymbolTable::new(),
+            next_id: 1,
+            resuming: false,
+        }
+    }
+
+    fn allocate_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16, condition: Option<Expr>) -> u32 {
+        let id = self.allocate_id();
+        self.breakpoints.push(Breakpoint {
+            id,
+            addr,
+            condition,
+        });
+        id
+    }
+
+    pub fn add_watchpoint(&mut self, mut watchpoint: Watchpoint) -> u32 {
+        watchpoint.id = self.allocate_id();
+        let id = watchpoint.id;
+        self.watchpoints.push(watchpoint);
+        id
+    }
+
+    /// Remove a breakpoint or watchpoint by id.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let before = self.breakpoints.len() + self.watchpoints.len();
+        self.breakpoints.retain(|bp| bp.id != id);
+        self.watchpoints.retain(|wp| wp.id != id);
+        before != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    fn condition_holds(condition: &Option<Expr>, nes: &Nes, value: u8) -> bool {
+        condition
+            .as_ref()
+            .is_none_or(|expr| expr.evaluate(nes, value) != 0)
+    }
+
+    /// Only record bus accesses while a watchpoint needs them.
+    fn sync_access_logs(&self, nes: &mut Nes) {
+        let wants = |space: AddressSpace| {
+            self.watchpoints
+                .iter()
+                .any(|wp| wp.space == space && (wp.read || wp.write))
+        };
+        let cpu = wants(AddressSpace::Cpu);
+        let ppu = wants(AddressSpace::Ppu);
+        match (cpu, nes.bus.access_log.as_mut()) {
+            (true, Some(log)) => log.clear(),
+            (true, None) => nes.bus.access_log = Some(Vec::new()),
+            (false, _) => nes.bus.access_log = None,
+        }
+        match (ppu, nes.bus.ppu.vram_access_log.as_mut()) {
+            (true, Some(log)) => log.clear(),
+            (true, None) => nes.bus.ppu.vram_access_log = Some(Vec::new()),
+            (false, _) => nes.bus.ppu.vram_access_log = None,
+        }
+    }
+
+    /// Check breakpoints and execute watchpoints for the instruction at PC.
+    /// Call before `Nes::tick`; on `Some`, don't execute the instruction.
+    pub fn before_instruction(&mut self, nes: &mut Nes) -> Option<StopReason> {
+        self.sync_access_logs(nes);
+        if std::mem::take(&mut self.resuming) {
+            return None;
+        }
+
+        let pc = nes.cpu.pc;
+        let reason = if let Some(bp) = self
+            .breakpoints
+            .iter()
+            .find(|bp| bp.addr == pc && Self::condition_holds(&bp.condition, nes, 0))
+        {
+            Some(StopReason::Breakpoint { id: bp.id, pc })
+        } else {
+            let opcode = nes.bus.peek(pc);
+            self.watchpoints
+                .iter()
+                .find(|wp| {
+                    wp.execute
+                        && wp.space == AddressSpace::Cpu
+                        && wp.contains(pc)
+                        && Self::condition_holds(&wp.condition, nes, opcode)
+                })
+                .map(|wp| StopReason::Executed { id: wp.id, pc })
+        };
+
+        if reason.is_some() {
+            self.paused = true;
+            self.resuming = true;
+        }
+        reason
+    }
+
+    /// Check read/write watchpoints against the accesses made by the last
+    /// `Nes::tick`.
+    pub fn after_instruction(&mut self, nes: &mut Nes) -> Option<StopReason> {
+        let cpu_log = nes.bus.access_log.take().unwrap_or_default();
+        let ppu_log = nes.bus.ppu.vram_access_log.take().unwrap_or_default();
+        let accesses = cpu_log
+            .iter()
+            .map(|access| {
+                let canonical = MemoryAccess {
+                    addr: canonical_cpu_addr(access.addr),
+                    ..*access
+                };
+                (AddressSpace::Cpu, canonical)
+            })
+            .chain(ppu_log.iter().map(|access| (AddressSpace::Ppu, *access)));
+
+        let mut reason = None;
+        'outer: for (space, access) in accesses {
+            for wp in &self.watchpoints {
+                let kind_matches = match access.kind {
+                    AccessKind::Read => wp.read,
+                    AccessKind::Write => wp.write,
+                };
+                if wp.space == space
+                    && kind_matches
+                    && wp.contains(access.addr)
+                    && Self::condition_holds(&wp.condition, nes, access.value)
+                {
+                    reason = Some(StopReason::Watchpoint {
+                        id: wp.id,
+                        space,
+                        access,
+                    });
+                    break 'outer;
+                }
+            }
+        }
+
+        // Hand the buffers back so their allocations are reused.
+        nes.bus.access_log = Some(cpu_log);
+        nes.bus.ppu.vram_access_log = Some(ppu_log);
+        self.sync_access_logs(nes);
+
+        if reason.is_some() {
+            self.paused = true;
+        }
+        reason
+    }
+
+    /// Execute one instruction with breakpoint and watchpoint checks.
+    /// Returns the cycles taken and the reason emulation stopped, if any.
+    pub fn run_instruction(&mut self, nes: &mut Nes) -> (usize, Option<StopReason>) {
+        if let Some(reason) = self.before_instruction(nes) {
+            return (0, Some(reason));
+        }
+        let cycles = nes.tick();
+        (cycles, self.after_instruction(nes))
+    }
+
+    /// Run until `done` returns true, a breakpoint/watchpoint triggers, or
+    /// `MAX_RUN_CYCLES` elapse. The first instruction always executes.
+    fn run_until(&mut self, nes: &mut Nes, mut done: impl FnMut(&Nes, u8) -> bool) -> StopReason {
+        self.resuming = true;
+        let mut cycles = 0;
+        while cycles < MAX_RUN_CYCLES {
+            let opcode = nes.bus.peek(nes.cpu.pc);
+            let (taken, reason) = self.run_instruction(nes);
+            if let Some(reason) = reason {
+                return reason;
+            }
+            cycles += taken;
+            if done(nes, opcode) {
+                self.paused = true;
+                return StopReason::Step;
+            }
+        }
+        self.paused = true;
+        StopReason::Timeout
+    }
+
+    pub fn step_into(&mut self, nes: &mut Nes) -> StopReason {
+        self.run_until(nes, |_, _| true)
+    }
+
+    /// Step, treating a JSR and the subroutine it calls as one instruction.
+    pub fn step_over(&mut self, nes: &mut Nes) -> StopReason {
+        if nes.bus.peek(nes.cpu.pc) != OP_JSR {
+            return self.step_into(nes);
+        }
+        let return_pc = nes.cpu.pc.wrapping_add(3);
+        let sp = nes.cpu.sp;
+        self.run_until(nes, |nes, _| nes.cpu.pc == return_pc && nes.cpu.sp == sp)
+    }
+
+    /// Run until the current subroutine (or interrupt handler) returns.
+    pub fn step_out(&mut self, nes: &mut Nes) -> StopReason {
+        let sp = nes.cpu.sp;
+        self.run_until(nes, |nes, opcode| {
+            (opcode == OP_RTS || opcode == OP_RTI) && nes.cpu.sp > sp
+        })
+    }
+
+    /// Run until the PPU enters `scanline`.
+    pub fn run_to_scanline(&mut self, nes: &mut Nes, scanline: u16) -> StopReason {
+        let mut previous = nes.bus.ppu.scanline;
+        match self.run_until(nes, |nes, _| {
+            let current = nes.bus.ppu.scanline;
+            let entered = current == scanline && previous != scanline;
+            previous = current;
+            entered
+        }) {
+            StopReason::Step => StopReason::Scanline(scanline),
+            reason => reason,
+        }
+    }
+
+    fn describe_stop(&self, nes: &Nes, reason: &StopReason) -> String {
+        format!("Stopped: {}\n{}", reason, nes.cpu.trace(&nes.bus))
+    }
+
+    /// Run one REPL command and return the text to print.
+    pub fn execute_command(&mut self, nes: &mut Nes, line: &str) -> String {
+        let line = line.trim();
+        let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+
+        let result = match command {
+            "" => Ok(String::new()),
+            "help" | "h" | "?" => Ok(HELP.to_string()),
+            "break" | "b" => self.command_break(args),
+            "watch" | "w" => self.command_watch(args),
+            "delete" | "d" => parse_number(args)
+                .ok_or_else(|| "usage: delete <id>".to_string())
+                .and_then(|id| {
+                    if self.remove(id) {
+                        Ok(format!("Deleted {}", id))
+                    } else {
+                        Err(format!("No breakpoint or watchpoint {}", id))
+                    }
+                }),
+            "list" | "l" => Ok(self.list()),
+            "continue" | "c" => {
+                self.paused = false;
+                Ok("Continuing".to_string())
+            }
+            "pause" | "p" => {
+                self.paused = true;
+                Ok(self.describe_stop(nes, &StopReason::Paused))
+            }
+            "step" | "s" => {
+                let count = if args.is_empty() {
+                    Some(1)
+                } else {
+                    parse_number(args)
+                };
+                match count {
+                    Some(count) => {
+                        let mut reason = StopReason::Step;
+                        for _ in 0..count.max(1) {
+                            reason = self.step_into(nes);
+                            if reason != StopReason::Step {
+                                break;
+                            }
+                        }
+                        Ok(self.describe_stop(nes, &reason))
+                    }
+                    None => Err("usage: step [count]".to_string()),
+                }
+            }
+            "next" | "n" => {
+                let reason = self.step_over(nes);
+                Ok(self.describe_stop(nes, &reason))
+            }
+            "finish" | "out" | "o" => {
+                let reason = self.step_out(nes);
+                Ok(self.describe_stop(nes, &reason))
+            }
+            "scanline" => {
+                let scanlines = nes.region().scanlines();
+                match parse_number(args) {
+                    Some(line) if line < scanlines as u32 => {
+                        let reason = self.run_to_scanline(nes, line as u16);
+                        Ok(self.describe_stop(nes, &reason))
+                    }
+                    _ => Err(format!("usage: scanline <0-{}>", scanlines - 1)),
+                }
+            }
+            "regs" | "r" => Ok(nes.cpu.trace(&nes.bus)),
+            "mem" | "m" => self.command_mem(nes, args),
+            "dis" => self.command_dis(nes, args),
+            "oam" => Ok(command_oam(nes)),
+            "hide" => command_layer(nes, args, true),
+            "show" => command_layer(nes, args, false),
+            "symbols" => self
+                .symbols
+                .load(std::path::Path::new(args))
+                .map(|count| format!("Loaded {} labels", count)),
+            "print" => Expr::parse(args).map(|expr| {
+                let value = expr.evaluate(nes, 0);
+                format!("${:X} ({})", value, value)
+            }),
+            _ => Err(format!("Unknown command '{}' (try 'help')", command)),
+        };
+
+        result.unwrap_or_else(|err| format!("Error: {}", err))
+    }
+
+    fn command_break(&mut self, args: &str) -> Result<String, String> {
+        let (words, condition) = split_condition(args)?;
+        let [addr] = words[..] else {
+            return Err("usage: break <addr> [if <expr>]".to_string());
+        };
+        let addr = parse_address(addr, &self.symbols)?;
+        let id = self.add_breakpoint(addr, condition);
+        Ok(format!("Breakpoint {} at ${:04X}", id, addr))
+    }
+
+    fn command_watch(&mut self, args: &str) -> Result<String, String> {
+        let (words, condition) = split_condition(args)?;
+        let mut words = words.into_iter().peekable();
+
+        let (read, write, execute) = match words.peek().copied() {
+            Some("r") => (true, false, false),
+            Some("w") => (false, true, false),
+            Some("x") => (false, false, true),
+            _ => (true, true, false),
+        };
+        if matches!(words.peek().copied(), Some("r" | "w" | "rw" | "x")) {
+            words.next();
+        }
+
+        let space = if words.peek() == Some(&"ppu") {
+            words.next();
+            AddressSpace::Ppu
+        } else {
+            AddressSpace::Cpu
+        };
+        if space == AddressSpace::Ppu && execute {
+            return Err("execute watchpoints only apply to CPU space".to_string());
+        }
+
+        let range = words
+            .next()
+            .ok_or("usage: watch [r|w|rw|x] [ppu] <addr>[-<end>] [if <expr>]")?;
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (
+                parse_address(start, &self.symbols)?,
+                parse_address(end, &self.symbols)?,
+            ),
+            None => {
+                let addr = parse_address(range, &self.symbols)?;
+                (addr, addr)
+            }
+        };
+        if end < start {
+            return Err("range end is before start".to_string());
+        }
+
+        let id = self.add_watchpoint(Watchpoint {
+            id: 0,
+            space,
+            start,
+            end,
+            read,
+            write,
+            execute,
+            condition,
+        });
+        Ok(format!("Watchpoint {}", id))
+    }
+
+    fn command_dis(&self, nes: &Nes, args: &str) -> Result<String, String> {
+        let mut words = args.split_whitespace();
+        let start = match words.next() {
+            Some(addr) => parse_address(addr, &self.symbols)?,
+            None => nes.cpu.pc,
+        };
+        let count = match words.next() {
+            Some(count) => parse_number(count).ok_or_else(|| format!("bad count '{}'", count))?,
+            None => 10,
+        };
+        let lines = disasm::disassemble(&nes.bus, start, count as usize, Some(&self.symbols));
+        Ok(lines.join("\n"))
+    }
+
+    fn command_mem(&self, nes: &Nes, args: &str) -> Result<String, String> {
+        let mut words = args.split_whitespace().peekable();
+        let ppu = words.peek() == Some(&"ppu");
+        if ppu {
+            words.next();
+        }
+        let start = parse_address(
+            words.next().ok_or("usage: mem [ppu] <addr> [len]")?,
+            &self.symbols,
+        )?;
+        let len = match words.next() {
+            Some(len) => parse_number(len).ok_or_else(|| format!("bad length '{}'", len))?,
+            None => 16,
+        };
+
+        let mut out = String::new();
+        for row in (0..len).step_by(16) {
+            let addr = start.wrapping_add(row as u16);
+            out.push_str(&format!("{:04X}:", addr));
+            for offset in row..(row + 16).min(len) {
+                let addr = start.wrapping_add(offset as u16);
+                let byte = if ppu {
+                    nes.bus.ppu.peek_vram(addr)
+                } else {
+                    nes.bus.peek(addr)
+                };
+                out.push_str(&format!(" {:02X}", byte));
+            }
+            out.push('\n');
+        }
+        Ok(out.trim_end().to_string())
+    }
+
+    fn list(&self) -> String {
+        let mut lines = Vec::new();
+        for bp in &self.breakpoints {
+            let mut line = format!("{:>3}  break  ${:04X}", bp.id, bp.addr);
+            if let Some(condition) = &bp.condition {
+                line.push_str(&format!("  if {}", condition));
+            }
+            lines.push(line);
+        }
+        for wp in &self.watchpoints {
+            let kinds = format!(
+                "{}{}{}",
+                if wp.read { "r" } else { "" },
+                if wp.write { "w" } else { "" },
+                if wp.execute { "x" } else { "" }
+            );
+            let space = match wp.space {
+                AddressSpace::Cpu => "cpu",
+                AddressSpace::Ppu => "ppu",
+            };
+            let mut line = format!(
+                "{:>3}  watch  {:<2} {} ${:04X}-${:04X}",
+                wp.id, kinds, space, wp.start, wp.end
+            );
+            if let Some(condition) = &wp.condition {
+                line.push_str(&format!("  if {}", condition));
+            }
+            lines.push(line);
+        }
+        if lines.is_empty() {
+            "No breakpoints or watchpoints".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_nrom;
+    use crate::region::Region;
+
+    /// NROM image with `code` at $C000 and a subroutine `INX; RTS` at $C100.
+    fn build_rom(code: &[u8]) -> Vec<u8> {
+        let mut rom = test_nrom(1, code);
+        rom[16 + 0x100..16 + 0x102].copy_from_slice(&[0xE8, 0x60]);
+        rom
+    }
+
+    fn nes_with(code: &[u8]) -> Nes {
+        let mut nes = Nes::new_with_rom(&build_rom(code)).unwrap();
+        nes.reset();
+        nes
+    }
+
+    const PROGRAM: &[u8] = &[
+        0xA9, 0x05, // C000 LDA #$05
+        0x8D, 0x00, 0x03, // C002 STA $0300
+        0x20, 0x00, 0xC1, // C005 JSR $C100
+        0xAD, 0x02, 0x20, // C008 LDA $2002
+        0x4C, 0x0B, 0xC0, // C00B JMP $C00B
+    ];
+
+    #[test]
+    fn test_expression_parsing_and_evaluation() {
+        let mut nes = nes_with(PROGRAM);
+        nes.cpu.a = 0x10;
+        nes.cpu.x = 3;
+        nes.bus.write(0x0010, 0x42);
+        let expr = Expr::parse("A == $10 && (X + 1) >= 4 && [A] == 0x42").unwrap();
+        assert_eq!(expr.evaluate(&nes, 0), 1);
+        assert_eq!(Expr::parse("!(P & 2)").unwrap().evaluate(&nes, 0), 1);
+        assert!(Expr::parse("A ==").is_err());
+        assert!(Expr::parse("A @ 1").is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_stops_and_resumes() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0xC005, None);
+
+        let mut reason = None;
+        for _ in 0..10 {
+            if let (_, Some(r)) = debugger.run_instruction(&mut nes) {
+                reason = Some(r);
+                break;
+            }
+        }
+        assert_eq!(reason, Some(StopReason::Breakpoint { id: 1, pc: 0xC005 }));
+        assert!(debugger.paused);
+
+        // Resuming executes the JSR instead of stopping at $C005 again.
+        assert_eq!(debugger.run_instruction(&mut nes).1, None);
+        assert_eq!(nes.cpu.pc, 0xC100);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0xC002, Some(Expr::parse("A == 6").unwrap()));
+        for _ in 0..5 {
+            assert_eq!(debugger.run_instruction(&mut nes).1, None);
+        }
+    }
+
+    #[test]
+    fn test_write_watchpoint_on_mirrored_ram() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.execute_command(&mut nes, "watch w $0B00 if value == 5");
+        let (_, reason) = debugger.run_instruction(&mut nes);
+        assert_eq!(reason, None);
+        let (_, reason) = debugger.run_instruction(&mut nes);
+        match reason {
+            Some(StopReason::Watchpoint { access, .. }) => {
+                assert_eq!(access.addr, 0x0300);
+                assert_eq!(access.value, 5);
+                assert_eq!(access.kind, AccessKind::Write);
+            }
+            other => panic!("expected watchpoint, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watchpoint_range_across_mirrors() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        // $0B00 is a mirror of the $0300 the program writes.
+        debugger.execute_command(&mut nes, "watch w $07F0-$0B00");
+        let (_, reason) = debugger.run_instruction(&mut nes);
+        assert_eq!(reason, None);
+        let (_, reason) = debugger.run_instruction(&mut nes);
+        assert!(
+            matches!(reason, Some(StopReason::Watchpoint { access, .. }) if access.addr == 0x0300),
+            "{:?}",
+            reason
+        );
+
+        let wp = &debugger.watchpoints[0];
+        assert!(wp.contains(0x07F8) && wp.contains(0x0000) && !wp.contains(0x0301));
+        let wp = Watchpoint {
+            start: 0x1FF8,
+            end: 0x2001,
+            ..wp.clone()
+        };
+        assert!(wp.contains(0x07FC) && wp.contains(0x2001) && wp.contains(0x3FF9));
+        assert!(!wp.contains(0x2002) && !wp.contains(0x0000));
+    }
+
+    #[test]
+    fn test_ppu_register_read_watchpoint() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.execute_command(&mut nes, "watch r $2002");
+        debugger.execute_command(&mut nes, "next");
+        debugger.execute_command(&mut nes, "next");
+        debugger.execute_command(&mut nes, "next");
+        let output = debugger.execute_command(&mut nes, "next");
+        assert!(
+            output.contains("watchpoint 1: CPU read $2002"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_ppu_space_watchpoint() {
+        let code = [
+            0xA9, 0x20, 0x8D, 0x06, 0x20, // LDA #$20; STA $2006
+            0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00; STA $2006
+            0xA9, 0x7F, 0x8D, 0x07, 0x20, // LDA #$7F; STA $2007
+        ];
+        let mut nes = nes_with(&code);
+        let mut debugger = Debugger::new();
+        debugger.execute_command(&mut nes, "watch w ppu $2000-$23FF");
+        let output = debugger.execute_command(&mut nes, "step 10");
+        assert!(output.contains("PPU write $2000 = $7F"), "{}", output);
+        assert_eq!(nes.cpu.pc, 0xC00F);
+    }
+
+    #[test]
+    fn test_labels_in_commands() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.symbols.load_nl("$C100#add_one#\n");
+        assert_eq!(
+            debugger.execute_command(&mut nes, "break add_one"),
+            "Breakpoint 1 at $C100"
+        );
+        let listing = debugger.execute_command(&mut nes, "dis $C005 1");
+        assert_eq!(listing, "C005  20 00 C1  JSR add_one");
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.step_into(&mut nes);
+        debugger.step_into(&mut nes);
+        assert_eq!(nes.cpu.pc, 0xC005);
+
+        assert_eq!(debugger.step_over(&mut nes), StopReason::Step);
+        assert_eq!(nes.cpu.pc, 0xC008);
+        assert_eq!(nes.cpu.x, 1);
+
+        let mut nes = nes_with(PROGRAM);
+        for _ in 0..3 {
+            debugger.step_into(&mut nes);
+        }
+        assert_eq!(nes.cpu.pc, 0xC100);
+        assert_eq!(debugger.step_out(&mut nes), StopReason::Step);
+        assert_eq!(nes.cpu.pc, 0xC008);
+    }
+
+    #[test]
+    fn test_execute_watchpoint_and_run_to_scanline() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.run_to_scanline(&mut nes, 100),
+            StopReason::Scanline(100)
+        );
+        assert_eq!(nes.bus.ppu.scanline, 100);
+
+        let mut nes = nes_with(PROGRAM);
+        debugger.execute_command(&mut nes, "watch x $C100-$C1FF");
+        assert_eq!(
+            debugger.run_to_scanline(&mut nes, 10),
+            StopReason::Executed { id: 1, pc: 0xC100 }
+        );
+    }
+
+    #[test]
+    fn test_scanline_bound_follows_region() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.execute_command(&mut nes, "scanline 300"),
+            "Error: usage: scanline <0-261>"
+        );
+
+        nes.set_region(Region::Pal);
+        assert!(debugger
+            .execute_command(&mut nes, "scanline 300")
+            .starts_with("Stopped: "));
+        assert_eq!(nes.bus.ppu.scanline, 300);
+        assert_eq!(
+            debugger.execute_command(&mut nes, "scanline 312"),
+            "Error: usage: scanline <0-311>"
+        );
+    }
+
+    #[test]
+    fn test_hide_and_show_layers() {
+        let mut nes = nes_with(PROGRAM);
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.execute_command(&mut nes, "hide 5"),
+            "Background shown, sprites shown, hidden sprites: 5"
+        );
+        debugger.execute_command(&mut nes, "hide $0A");
+        debugger.execute_command(&mut nes, "hide bg");
+        assert!(nes.hide_background() && nes.sprite_hidden(10));
+        assert!(debugger
+            .execute_command(&mut nes, "hide 64")
+            .starts_with("Error: usage"));
+
+        debugger.execute_command(&mut nes, "show all");
+        assert!(!nes.hide_background() && !nes.sprite_hidden(5));
+    }
+}
@@ -0,0 +1,444 @@
+use crate::bus::Bus;
+use crate::cpu::{AddressingMode, Cpu};
+use crate::opcodes::OPCODES;
+use std::collections::HashMap;
+
+/// Anything the disassembler can read bytes from without side effects.
+pub trait MemorySource {
+    fn peek(&self, addr: u16) -> u8;
+}
+
+impl MemorySource for Bus {
+    fn peek(&self, addr: u16) -> u8 {
+        Bus::peek(self, addr)
+    }
+}
+
+/// Raw PRG bytes (e.g. one bank of a ROM file) mapped at `base`. Reads
+/// outside the slice return 0.
+pub struct PrgBank<'a> {
+    pub base: u16,
+    pub data: &'a [u8],
+}
+
+impl MemorySource for PrgBank<'_> {
+    fn peek(&self, addr: u16) -> u8 {
+        addr.checked_sub(self.base)
+            .and_then(|offset| self.data.get(offset as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// One decoded instruction.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Instruction {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    /// Mnemonic from `OPCODES`; unofficial opcodes carry a `*` prefix.
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    /// Operand as encoded: the byte or little-endian word following the opcode.
+    pub operand: u16,
+}
+
+impl Instruction {
+    pub fn len(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Conditional branches are `xxy10000`; their operand is a signed offset.
+    pub fn is_branch(&self) -> bool {
+        self.bytes.len() == 2 && self.bytes[0] & 0x1F == 0x10
+    }
+
+    /// Destination of a branch, relative to the following instruction.
+    pub fn branch_target(&self) -> u16 {
+        self.addr
+            .wrapping_add(2)
+            .wrapping_add(self.operand as u8 as i8 as u16)
+    }
+
+    fn is_jump(&self) -> bool {
+        matches!(self.bytes[0], 0x20 | 0x4C)
+    }
+
+    /// Operand text without machine state, e.g. `#$05`, `$0300,X`, `($20),Y`,
+    /// `($0200)`, `A`. Addresses are replaced by labels when `symbols` has one.
+    pub fn operand_text(&self, symbols: Option<&SymbolTable>) -> String {
+        let name = |addr: u16, width: usize| match symbols.and_then(|s| s.label(addr)) {
+            Some(label) => label.to_string(),
+            None if width == 2 => format!("${:02X}", addr),
+            None => format!("${:04X}", addr),
+        };
+        if self.is_branch() {
+            return name(self.branch_target(), 4);
+        }
+        let op = self.operand;
+        match self.mode {
+            AddressingMode::Immediate => format!("#${:02X}", op),
+            AddressingMode::ZeroPage => name(op, 2),
+            AddressingMode::ZeroPageX => format!("{},X", name(op, 2)),
+            AddressingMode::ZeroPageY => format!("{},Y", name(op, 2)),
+            AddressingMode::Absolute => name(op, 4),
+            AddressingMode::AbsoluteX => format!("{},X", name(op, 4)),
+            AddressingMode::AbsoluteY => format!("{},Y", name(op, 4)),
+            AddressingMode::IndirectX => format!("({},X)", name(op, 2)),
+            AddressingMode::IndirectY => format!("({}),Y", name(op, 2)),
+            AddressingMode::Indirect => format!("({})", name(op, 4)),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::NoneAddressing => String::new(),
+        }
+    }
+}
+
+/// Decode the instruction at `addr`.
+pub fn decode(memory: &impl MemorySource, addr: u16) -> Instruction {
+    let opcode = memory.peek(addr);
+    let op = &OPCODES[opcode as usize];
+    let bytes: Vec<u8> = (0..op.len as u16)
+        .map(|i| memory.peek(addr.wrapping_add(i)))
+        .collect();
+    let operand = match bytes.len() {
+        2 => bytes[1] as u16,
+        3 => u16::from_le_bytes([bytes[1], bytes[2]]),
+        _ => 0,
+    };
+    Instruction {
+        addr,
+        bytes,
+        mnemonic: op.name,
+        mode: op.mode,
+        operand,
+    }
+}
+
+/// Disassemble `count` instructions starting at `start`, one line each:
+/// `C000  A9 05     LDA #$05`. Lines at labelled addresses are preceded by
+/// a `label:` line.
+pub fn disassemble(
+    memory: &impl MemorySource,
+    start: u16,
+    count: usize,
+    symbols: Option<&SymbolTable>,
+) -> Vec<String> {
+    let mut lines = Vec::with_capacity(count);
+    let mut addr = start;
+    for _ in 0..count {
+        let ins = decode(memory, addr);
+        if let Some(label) = symbols.and_then(|s| s.label(addr)) {
+            lines.push(format!("{}:", label));
+        }
+        lines.push(
+            format!(
+                "{:04X}  {:8}  {} {}",
+                addr,
+                hex_bytes(&ins.bytes),
+                ins.mnemonic,
+                ins.operand_text(symbols)
+            )
+            .trim_end()
+            .to_string(),
+        );
+        addr = addr.wrapping_add(ins.len());
+    }
+    lines
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Operand annotated with the machine state the instruction will see, in the
+/// nestest log format (`$0300,X @ 0305 = 7F`, `($0200) = DB7E`).
+fn traced_operand(cpu: &Cpu, bus: &Bus, ins: &Instruction) -> String {
+    if ins.is_branch() {
+        return format!("${:04X}", ins.branch_target());
+    }
+    let op = ins.operand;
+    let effective = match ins.mode {
+        AddressingMode::Immediate
+        | AddressingMode::Accumulator
+        | AddressingMode::Indirect
+        | AddressingMode::NoneAddressing => 0,
+        _ => cpu.get_absolute_address(bus, &ins.mode, ins.addr.wrapping_add(1)),
+    };
+    let value = bus.peek(effective);
+
+    match ins.mode {
+        AddressingMode::Immediate => format!("#${:02X}", op),
+        AddressingMode::ZeroPage => format!("${:02X} = {:02X}", op, value),
+        AddressingMode::ZeroPageX => format!("${:02X},X @ {:02X} = {:02X}", op, effective, value),
+        AddressingMode::ZeroPageY => format!("${:02X},Y @ {:02X} = {:02X}", op, effective, value),
+        AddressingMode::IndirectX => format!(
+            "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
+            op,
+            (op as u8).wrapping_add(cpu.x),
+            effective,
+            value
+        ),
+        AddressingMode::IndirectY => format!(
+            "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
+            op,
+            effective.wrapping_sub(cpu.y as u16),
+            effective,
+            value
+        ),
+        AddressingMode::Absolute if ins.is_jump() => format!("${:04X}", op),
+        AddressingMode::Absolute => format!("${:04X} = {:02X}", op, value),
+        AddressingMode::AbsoluteX => format!("${:04X},X @ {:04X} = {:02X}", op, effective, value),
+        AddressingMode::AbsoluteY => format!("${:04X},Y @ {:04X} = {:02X}", op, effective, value),
+        AddressingMode::Indirect => {
+            // JMP ($xxFF) fetches the high byte from $xx00, like the CPU does.
+            let hi_addr = (op & 0xFF00) | (op.wrapping_add(1) & 0x00FF);
+            let target = u16::from_le_bytes([bus.peek(op), bus.peek(hi_addr)]);
+            format!("(${:04X}) = {:04X}", op, target)
+        }
+        AddressingMode::Accumulator | AddressingMode::NoneAddressing => ins.operand_text(None),
+    }
+}
+
+/// Format the instruction at PC with registers, PPU position and cycle count
+/// as one nestest-style trace line.
+pub fn trace(cpu: &Cpu, bus: &Bus) -> String {
+    let ins = decode(bus, cpu.pc);
+    let asm_str = format!(
+        "{:04X}  {:8} {: >4} {}",
+        cpu.pc,
+        hex_bytes(&ins.bytes),
+        ins.mnemonic,
+        traced_operand(cpu, bus, &ins)
+    );
+
+    format!(
+        "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:3},{:3} CYC:{}",
+        asm_str, cpu.a, cpu.x, cpu.y, cpu.st, cpu.sp, bus.ppu.scanline, bus.ppu.cycle, cpu.cycles
+    )
+    .to_uppercase()
+}
+
+/// Labels for addresses, loaded from FCEUX `.nl` or ca65/ld65 `.dbg` files.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    labels: HashMap<u16, String>,
+    addresses: HashMap<String, u16>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(|s| s.as_str())
+    }
+
+    pub fn address(&self, label: &str) -> Option<u16> {
+        self.addresses.get(label).copied()
+    }
+
+    /// Add a label. The first global name for an address wins; cheap local
+    /// labels (`@loop`) only fill gaps.
+    pub fn insert(&mut self, addr: u16, name: &str) {
+        self.addresses.entry(name.to_string()).or_insert(addr);
+        match self.labels.get(&addr) {
+            Some(existing) if !existing.starts_with('@') || name.starts_with('@') => {}
+            _ => {
+                self.labels.insert(addr, name.to_string());
+            }
+        }
+    }
+
+    /// Load a file, choosing the parser by extension (`.nl` or `.dbg`).
+    pub fn load(&mut self, path: &std::path::Path) -> Result<usize, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("nl") => Ok(self.load_nl(&text)),
+            Some("dbg") => Ok(self.load_dbg(&text)),
+            _ => Err(format!(
+                "{}: expected a .nl or .dbg symbol file",
+                path.display()
+            )),
+        }
+    }
+
+    /// Parse FCEUX name lists: `$C000#Reset#optional comment`. Returns the
+    /// number of labels read.
+    pub fn load_nl(&mut self, text: &str) -> usize {
+        let mut count = 0;
+        for line in text.lines() {
+            let mut fields = line.trim().split('#');
+            let (Some(addr), Some(name)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Some(addr) = addr
+                .strip_prefix('$')
+                .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+            else {
+                continue;
+            };
+            let name = name.trim();
+            if !name.is_empty() {
+                self.insert(addr, name);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Parse the `sym` records of an ld65 `--dbgfile`, keeping labels
+    /// (`type=lab`). Returns the number of labels read.
+    pub fn load_dbg(&mut self, text: &str) -> usize {
+        let mut count = 0;
+        for line in text.lines() {
+            let Some(record) = line.strip_prefix("sym\t") else {
+                continue;
+            };
+            let mut name = None;
+            let mut value = None;
+            let mut is_label = false;
+            for field in record.split(',') {
+                match field.split_once('=') {
+                    Some(("name", v)) => name = Some(v.trim_matches('"')),
+                    Some(("val", v)) => {
+                        value = v
+                            .strip_prefix("0x")
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .or_else(|| v.parse().ok())
+                    }
+                    Some(("type", v)) => is_label = v == "lab",
+                    _ => {}
+                }
+            }
+            if let (true, Some(name), Some(value)) = (is_label, name, value) {
+                if value <= 0xFFFF {
+                    self.insert(value as u16, name);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_nrom;
+
+    fn bank(code: &[u8]) -> PrgBank<'_> {
+        PrgBank {
+            base: 0xC000,
+            data: code,
+        }
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        // BNE -2 (to itself) and BEQ +4
+        let code = [0xD0, 0xFE, 0xF0, 0x04];
+        let lines = disassemble(&bank(&code), 0xC000, 2, None);
+        assert_eq!(lines[0], "C000  D0 FE     BNE $C000");
+        assert_eq!(lines[1], "C002  F0 04     BEQ $C008");
+    }
+
+    #[test]
+    fn test_indirect_jmp_and_accumulator() {
+        let code = [0x6C, 0x00, 0x02, 0x4A, 0xB1, 0x20, 0xEA];
+        let lines = disassemble(&bank(&code), 0xC000, 4, None);
+        assert_eq!(lines[0], "C000  6C 00 02  JMP ($0200)");
+        assert_eq!(lines[1], "C003  4A        LSR A");
+        assert_eq!(lines[2], "C004  B1 20     LDA ($20),Y");
+        assert_eq!(lines[3], "C006  EA        NOP");
+    }
+
+    #[test]
+    fn test_every_opcode_decodes() {
+        for op in OPCODES.iter() {
+            let code = [op.code, 0x10, 0x20];
+            // Length follows from the addressing mode; branches are the
+            // only two-byte instructions without an operand mode.
+            let len = match op.mode {
+                AddressingMode::Immediate
+                | AddressingMode::ZeroPage
+                | AddressingMode::ZeroPageX
+                | AddressingMode::ZeroPageY
+                | AddressingMode::IndirectX
+                | AddressingMode::IndirectY => 2,
+                AddressingMode::Absolute
+                | AddressingMode::AbsoluteX
+                | AddressingMode::AbsoluteY
+                | AddressingMode::Indirect => 3,
+                AddressingMode::NoneAddressing if op.code & 0x1F == 0x10 => 2,
+                AddressingMode::Accumulator | AddressingMode::NoneAddressing => 1,
+            };
+            let ins = decode(&bank(&code), 0xC000);
+            assert_eq!(ins.mnemonic, op.name, "{:02X}", op.code);
+            assert_eq!(ins.len(), len, "{:02X} {}", op.code, op.name);
+            assert_eq!(ins.bytes, code[..len as usize], "{:02X}", op.code);
+            let line = &disassemble(&bank(&code), 0xC000, 1, None)[0];
+            assert_eq!(&line[16..16 + op.name.len()], op.name, "{}", line);
+        }
+
+        let code = [0x02, 0xEB, 0x10, 0xAB, 0x10];
+        let lines = disassemble(&bank(&code), 0xC000, 3, None);
+        assert_eq!(lines[0], "C000  02        *KIL");
+        assert_eq!(lines[1], "C001  EB 10     *SBC #$10");
+        assert_eq!(lines[2], "C003  AB 10     *LXA #$10");
+    }
+
+    #[test]
+    fn test_labels_from_nl_and_dbg() {
+        let mut symbols = SymbolTable::new();
+        assert_eq!(
+            symbols.load_nl("$C000#reset#entry point\n$0010#player_x#\n"),
+            2
+        );
+        let dbg = "version\tmajor=2,minor=0\n\
+                   sym\tid=0,name=\"main_loop\",addrsize=absolute,scope=0,def=3,val=0xC004,seg=0,type=lab\n\
+                   sym\tid=1,name=\"SPEED\",addrsize=zeropage,scope=0,def=4,val=0x2,type=equ\n";
+        assert_eq!(symbols.load_dbg(dbg), 1);
+        assert_eq!(symbols.address("main_loop"), Some(0xC004));
+
+        let code = [0xA5, 0x10, 0x4C, 0x00, 0xC0, 0xD0, 0xFD];
+        let lines = disassemble(&bank(&code), 0xC000, 3, Some(&symbols));
+        assert_eq!(
+            lines,
+            vec![
+                "reset:",
+                "C000  A5 10     LDA player_x",
+                "C002  4C 00 C0  JMP reset",
+                "C005  D0 FD     BNE main_loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace_renders_branch_and_indirect_jmp() {
+        let rom = test_nrom(1, &[0x90, 0x10, 0x6C, 0xFF, 0x02]);
+        let mut nes = crate::Nes::new_with_rom(&rom).unwrap();
+        nes.cpu.pc = 0xC000;
+        nes.bus.write(0x02FF, 0x34);
+        nes.bus.write(0x0200, 0x12);
+
+        assert!(trace(&nes.cpu, &nes.bus).starts_with("C000  90 10     BCC $C012 "));
+        nes.cpu.pc = 0xC002;
+        assert!(trace(&nes.cpu, &nes.bus).starts_with("C002  6C FF 02  JMP ($02FF) = 1234 "));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::cartridge::{Mirroring, Rom};
+use crate::checksum::Crc32;
+use crate::region::Region;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::RwLock;
+
+/// Known-good cartridge settings for one dump, in the style of the NES 2.0
+/// header database. Headers that disagree with an entry are corrected on load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameDbEntry {
+    /// CRC-32 of PRG ROM followed by CHR ROM, without the header or trainer.
+    pub crc32: u32,
+    pub name: Cow<'static, str>,
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub region: Region,
+}
+
+/// Only dumps whose hashes have been checked against a real cartridge belong
+/// here. The full NES 2.0 DB is loaded at runtime with `load_nes20db`.
+pub static GAME_DB: &[GameDbEntry] = &[GameDbEntry {
+    crc32: 0x3337_EC46,
+    name: Cow::Borrowed("Super Mario Bros. (World)"),
+    mapper: 0,
+    mirroring: Mirroring::Vertical,
+    battery: false,
+    region: Region::Ntsc,
+}];
+
+/// CRC-32 of a cartridge's PRG and CHR ROM, the key `GAME_DB` uses.
+pub fn rom_crc32(prg_rom: &[u8], chr_rom: &[u8]) -> u32 {
+    Crc32::new().update(prg_rom).update(chr_rom).finish()
+}
+
+/// Entries from `load_nes20db`, consulted before `GAME_DB`.
+static LOADED: RwLock<Vec<GameDbEntry>> = RwLock::new(Vec::new());
+
+pub fn lookup_crc32(crc32: u32) -> Option<GameDbEntry> {
+    let loaded = LOADED.read().unwrap_or_else(|err| err.into_inner());
+    loaded
+        .iter()
+        .chain(GAME_DB)
+        .find(|entry| entry.crc32 == crc32)
+        .cloned()
+}
+
+pub fn lookup(prg_rom: &[u8], chr_rom: &[u8]) -> Option<GameDbEntry> {
+    lookup_crc32(rom_crc32(prg_rom, chr_rom))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GameDbError {
+    /// The `<game>` element at this position (from 0) is malformed.
+    BadEntry { index: usize, reason: String },
+    /// The file holds no `<game>` elements at all.
+    NoEntries,
+}
+
+impl fmt::Display for GameDbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameDbError::BadEntry { index, reason } => {
+                write!(f, "Game database entry {}: {}", index, reason)
+            }
+            GameDbError::NoEntries => write!(f, "Game database has no <game> entries"),
+        }
+    }
+}
+
+impl std::error::Error for GameDbError {}
+
+/// Parse `nes20db.xml` from the NesDev wiki and make its entries available
+/// to `lookup`, so every later `Rom::new` corrects headers from it. Returns
+/// the number of entries added. Games on mappers above 255 are skipped,
+/// since `Rom` cannot represent them.
+pub fn load_nes20db(xml: &str) -> Result<usize, GameDbError> {
+    let entries = parse_nes20db(xml)?;
+    let count = entries.len();
+    let mut loaded = LOADED.write().unwrap_or_else(|err| err.into_inner());
+    loaded.retain(|old| !entries.iter().any(|new| new.crc32 == old.crc32));
+    loaded.extend(entries);
+    Ok(count)
+}
+
+/// The entries of an NES 2.0 DB XML file. Each `<game>` is read from its
+/// `<rom crc32>`, `<pcb mapper mirroring battery>` and `<console region>`
+/// elements, and named after the comment that precedes them.
+pub fn parse_nes20db(xml: &str) -> Result<Vec<GameDbEntry>, GameDbError> {
+    let mut entries = Vec::new();
+    let mut games = 0;
+    let mut rest = xml;
+    while let Some(start) = rest.find("<game>") {
+        let body = &rest[start + "<game>".len()..];
+        let end = body.find("</game>").unwrap_or(body.len());
+        let game = &body[..end];
+        rest = &body[end..];
+
+        let index = games;
+        games += 1;
+        let bad = |reason: String| GameDbError::BadEntry { index, reason };
+        let field = |tag: &str, name: &str| {
+            element(game, tag)
+                .and_then(|attributes| attribute(attributes, name))
+                .ok_or_else(|| bad(format!("missing <{} {}>", tag, name)))
+        };
+
+        let crc32 = field("rom", "crc32")?;
+        let crc32 =
+            u32::from_str_radix(crc32, 16).map_err(|_| bad(format!("bad crc32 '{}'", crc32)))?;
+        let mapper: u16 = field("pcb", "mapper")?
+            .parse()
+            .map_err(|_| bad("bad mapper".to_string()))?;
+        let Ok(mapper) = u8::try_from(mapper) else {
+            continue;
+        };
+        let mirroring = match field("pcb", "mirroring")? {
+            "H" => Mirroring::Horizontal,
+            "V" => Mirroring::Vertical,
+            "4" => Mirroring::FourScreen,
+            "1" => Mirroring::OneScreenLower,
+            other => return Err(bad(format!("unknown mirroring '{}'", other))),
+        };
+        let battery = field("pcb", "battery")? == "1";
+        let region: u8 = field("console", "region")?
+            .parse()
+            .map_err(|_| bad("bad region".to_string()))?;
+        let name = game
+            .split_once("<!--")
+            .and_then(|(_, comment)| comment.split_once("-->"))
+            .map_or("", |(name, _)| name.trim());
+
+        entries.push(GameDbEntry {
+            crc32,
+            name: Cow::Owned(name.to_string()),
+            mapper,
+            mirroring,
+            battery,
+            region: Region::from_nes2_timing(region),
+        });
+    }
+    if games == 0 {
+        return Err(GameDbError::NoEntries);
+    }
+    Ok(entries)
+}
+
+/// Attribute text of the first `<tag .../>` element in `xml`.
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{} ", tag))? + tag.len() + 2;
+    let end = xml[start..].find('>')? + start;
+    Some(&xml[start..end])
+}
+
+/// Value of `name="..."` among an element's attributes.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("{}=\"", name);
+    let start = attributes
+        .match_indices(&key)
+        .map(|(index, _)| index)
+        .find(|&index| index == 0 || attributes.as_bytes()[index - 1].is_ascii_whitespace())?
+        + key.len();
+    let end = attributes[start..].find('"')? + start;
+    Some(&attributes[start..end])
+}
+
+impl GameDbEntry {
+    /// Overwrite the header-derived board settings of `rom`.
+    pub fn apply(&self, rom: &mut Rom) {
+        rom.mapper = self.mapper;
+        rom.screen_mirroring = self.mirroring;
+        rom.has_battery = self.battery;
+        rom.region = self.region;
+    }
+
+    /// Header fields of `rom` that this entry would change, as
+    /// `(field, header value, database value)`.
+    pub fn differences(&self, rom: &Rom) -> Vec<(&'static str, String, String)> {
+        let mut diffs = Vec::new();
+        if rom.mapper != self.mapper {
+            diffs.push(("mapper", rom.mapper.to_string(), self.mapper.to_string()));
+        }
+        if rom.screen_mirroring != self.mirroring {
+            diffs.push((
+                "mirroring",
+                format!("{:?}", rom.screen_mirroring),
+                format!("{:?}", self.mirroring),
+            ));
+        }
+        if rom.has_battery != self.battery {
+            diffs.push((
+                "battery",
+                rom.has_battery.to_string(),
+                self.battery.to_string(),
+            ));
+        }
+        if rom.region != self.region {
+            diffs.push(("region", rom.region.to_string(), self.region.to_string()));
+        }
+        diffs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_unique() {
+        for (i, entry) in GAME_DB.iter().enumerate() {
+            assert_eq!(
+                lookup_crc32(entry.crc32).as_ref(),
+                Some(&GAME_DB[i]),
+                "{}",
+                entry.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_corrects_header() {
+        let mut rom = Rom {
+            prg_rom: vec![0; 0x8000],
+            chr_rom: vec![0; 0x2000],
+            mapper: 64,
+            screen_mirroring: Mirroring::Horizontal,
+            has_battery: true,
+            prg_ram_size: 8192,
+            region: Region::Pal,
+        };
+        let entry = &GAME_DB[0];
+        assert_eq!(entry.differences(&rom).len(), 4);
+        entry.apply(&mut rom);
+        assert!(entry.differences(&rom).is_empty());
+        assert_eq!(rom.mapper, 0);
+        assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+    }
+
+    const NES20DB: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nes20db date="2024-01-01">
+  <game>
+    <!-- Test Game (Europe).nes -->
+    <prgrom size="32768" crc32="11111111" sha1="00"/>
+    <chrrom size="8192" crc32="22222222" sha1="00"/>
+    <rom size="40960" crc32="0BADC0DE" sha1="00"/>
+    <pcb mapper="1" submapper="0" mirroring="H" battery="1"/>
+    <console type="0" region="1"/>
+  </game>
+  <game>
+    <!-- Big Mapper.nes -->
+    <rom size="40960" crc32="12345678" sha1="00"/>
+    <pcb mapper="300" submapper="0" mirroring="V" battery="0"/>
+    <console type="0" region="0"/>
+  </game>
+</nes20db>
+"#;
+
+    #[test]
+    fn test_parse_nes20db() {
+        let entries = parse_nes20db(NES20DB).unwrap();
+        assert_eq!(
+            entries,
+            [GameDbEntry {
+                crc32: 0x0BAD_C0DE,
+                name: Cow::Borrowed("Test Game (Europe).nes"),
+                mapper: 1,
+                mirroring: Mirroring::Horizontal,
+                battery: true,
+                region: Region::Pal,
+            }]
+        );
+
+        let broken = NES20DB.replace("mirroring=\"H\"", "mirroring=\"X\"");
+        assert_eq!(
+            parse_nes20db(&broken),
+            Err(GameDbError::BadEntry {
+                index: 0,
+                reason: "unknown mirroring 'X'".to_string(),
+            })
+        );
+        let broken = NES20DB.replace("<rom size=\"40960\" crc32=\"0BADC0DE\"", "<rom");
+        assert!(matches!(
+            parse_nes20db(&broken),
+            Err(GameDbError::BadEntry { index: 0, .. })
+        ));
+        assert_eq!(parse_nes20db("<nes20db/>"), Err(GameDbError::NoEntries));
+    }
+
+    #[test]
+    fn test_loaded_entries_correct_headers() {
+        let xml = NES20DB.replace("0BADC0DE", "5EED5EED");
+        assert_eq!(lookup_crc32(0x5EED_5EED), None);
+        assert_eq!(load_nes20db(&xml), Ok(1));
+        let entry = lookup_crc32(0x5EED_5EED).unwrap();
+        assert_eq!(entry.mapper, 1);
+        assert_eq!(entry.region, Region::Pal);
+        // Built-in entries are still found.
+        assert!(lookup_crc32(GAME_DB[0].crc32).is_some());
+    }
+}
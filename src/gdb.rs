@@ -0,0 +1,739 @@
+use crate::debugger::{AddressSpace, Debugger, StopReason, Watchpoint};
+use crate::Nes;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/// Target description so GDB knows the register layout of the `g` packet:
+/// A, X, Y, P, SP (8-bit) followed by PC (16-bit, little-endian).
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.rust_emu.mos6502">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8" regnum="1"/>
+    <reg name="y" bitsize="8" regnum="2"/>
+    <reg name="p" bitsize="8" regnum="3"/>
+    <reg name="sp" bitsize="8" regnum="4"/>
+    <reg name="pc" bitsize="16" regnum="5" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+const PACKET_SIZE: usize = 0x4000;
+
+const SIGTRAP: u8 = 5;
+const SIGINT: u8 = 2;
+
+/// Kind of a GDB `Z`/`z` breakpoint packet.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum BreakKind {
+    Software,
+    Hardware,
+    Write,
+    Read,
+    Access,
+}
+
+impl BreakKind {
+    fn from_packet(kind: u8) -> Option<Self> {
+        match kind {
+            b'0' => Some(BreakKind::Software),
+            b'1' => Some(BreakKind::Hardware),
+            b'2' => Some(BreakKind::Write),
+            b'3' => Some(BreakKind::Read),
+            b'4' => Some(BreakKind::Access),
+            _ => None,
+        }
+    }
+
+    /// Stop-reply key reported when this kind triggers.
+    fn stop_key(self) -> &'static str {
+        match self {
+            BreakKind::Software => "swbreak",
+            BreakKind::Hardware => "hwbreak",
+            BreakKind::Write => "watch",
+            BreakKind::Read => "rwatch",
+            BreakKind::Access => "awatch",
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    input: Vec<u8>,
+    /// Bytes the socket hasn't accepted yet, flushed on every `poll`.
+    output: Vec<u8>,
+    no_ack: bool,
+    /// A `c` packet is outstanding; the stop reply is sent when emulation halts.
+    running: bool,
+    closed: bool,
+}
+
+/// GDB remote serial protocol server for the 6502 core.
+///
+/// The server never blocks: frontends call [`GdbServer::poll`] from their main
+/// loop, run emulation through the shared [`Debugger`] while it isn't paused,
+/// and pass stop reasons back with [`GdbServer::notify_stop`].
+pub struct GdbServer {
+    listener: TcpListener,
+    client: Option<Connection>,
+    /// Breakpoints and watchpoints created by the client, so `z` packets
+    /// remove only what GDB itself inserted.
+    inserted: HashMap<(BreakKind, u16), u32>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+/// Parse `addr,len` as sent by `m`, `M` and `Z` packets.
+fn parse_addr_len(text: &str) -> Option<(u16, u32)> {
+    let (addr, len) = text.split_once(',')?;
+    let addr = parse_hex(addr).filter(|a| *a <= 0xFFFF)?;
+    Some((addr as u16, parse_hex(len)?))
+}
+
+fn frame_packet(data: &str) -> Vec<u8> {
+    let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    format!("${}#{:02x}", data, checksum).into_bytes()
+}
+
+impl GdbServer {
+    /// Listen on `addr` (e.g. `127.0.0.1:2345`).
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            client: None,
+            inserted: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Accept a pending connection and handle any packets that have arrived.
+    pub fn poll(&mut self, nes: &mut Nes, debugger: &mut Debugger) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(Connection {
+                        stream,
+                        input: Vec::new(),
+                        output: Vec::new(),
+                        no_ack: false,
+                        running: false,
+                        closed: false,
+                    });
+                    // GDB expects the target to be halted when it attaches.
+                    debugger.paused = true;
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut buf = [0u8; 1024];
+        loop {
+            let Some(client) = self.client.as_mut() else {
+                return Ok(());
+            };
+            match client.stream.read(&mut buf) {
+                Ok(0) => {
+                    client.closed = true;
+                    break;
+                }
+                Ok(n) => client.input.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.detach(debugger);
+                    return Err(err);
+                }
+            }
+        }
+
+        self.process_input(nes, debugger)?;
+
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+        match Self::flush(client) {
+            Err(err) => {
+                let closed = client.closed;
+                self.detach(debugger);
+                if !closed {
+                    return Err(err);
+                }
+            }
+            // Hold a closing connection until its last reply is out.
+            Ok(()) if client.closed && client.output.is_empty() => self.detach(debugger),
+            Ok(()) => {}
+        }
+        Ok(())
+    }
+
+    /// Report that emulation halted. Only sends a reply while a `c` packet is
+    /// outstanding.
+    pub fn notify_stop(&mut self, reason: &StopReason) -> io::Result<()> {
+        let reply = self.stop_reply(reason);
+        match self.client.as_mut() {
+            Some(client) if client.running => {
+                client.running = false;
+                Self::send(client, &reply)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn detach(&mut self, debugger: &mut Debugger) {
+        for id in self.inserted.values() {
+            debugger.remove(*id);
+        }
+        self.inserted.clear();
+        self.client = None;
+        debugger.paused = false;
+    }
+
+    fn send(client: &mut Connection, data: &str) -> io::Result<()> {
+        client.output.extend_from_slice(&frame_packet(data));
+        Self::flush(client)
+    }
+
+    /// Write queued output until the non-blocking socket is full; the rest
+    /// stays queued for the next `poll`.
+    fn flush(client: &mut Connection) -> io::Result<()> {
+        while !client.output.is_empty() {
+            match client.stream.write(&client.output) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    client.output.drain(..n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn process_input(&mut self, nes: &mut Nes, debugger: &mut Debugger) -> io::Result<()> {
+        loop {
+            let Some(client) = self.client.as_mut() else {
+                return Ok(());
+            };
+            let Some(&first) = client.input.first() else {
+                return Ok(());
+            };
+            match first {
+                b'+' | b'-' => {
+                    // Acks for our replies; retransmission isn't needed over TCP.
+                    client.input.remove(0);
+                }
+                0x03 => {
+                    client.input.remove(0);
+                    debugger.paused = true;
+                    if client.running {
+                        client.running = false;
+                        Self::send(client, &format!("S{:02x}", SIGINT))?;
+                    }
+                }
+                b'$' => {
+                    let Some(hash) = client.input.iter().position(|&b| b == b'#') else {
+                        return Ok(());
+                    };
+                    if client.input.len() < hash + 3 {
+                        return Ok(());
+                    }
+                    let packet: Vec<u8> = client.input.drain(..hash + 3).collect();
+                    let data = String::from_utf8_lossy(&packet[1..hash]).into_owned();
+                    let expected = std::str::from_utf8(&packet[hash + 1..])
+                        .ok()
+                        .and_then(|cs| u8::from_str_radix(cs, 16).ok());
+                    let actual = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+                    if !client.no_ack {
+                        let ack: &[u8] = if expected == Some(actual) { b"+" } else { b"-" };
+                        client.output.extend_from_slice(ack);
+                    }
+                    if expected == Some(actual) {
+                        if let Some(reply) = self.handle_packet(&data, nes, debugger) {
+                            if let Some(client) = self.client.as_mut() {
+                                Self::send(client, &reply)?;
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    client.input.remove(0);
+                }
+            }
+        }
+    }
+
+    fn stop_reply(&self, reason: &StopReason) -> String {
+        let find_kind = |id: u32| {
+            self.inserted
+                .iter()
+                .find(|(_, inserted_id)| **inserted_id == id)
+                .map(|((kind, _), _)| *kind)
+        };
+        match reason {
+            StopReason::Breakpoint { id, .. } | StopReason::Executed { id, .. } => {
+                match find_kind(*id) {
+                    Some(kind) => format!("T{:02x}{}:;", SIGTRAP, kind.stop_key()),
+                    None => format!("S{:02x}", SIGTRAP),
+                }
+            }
+            StopReason::Watchpoint { id, access, .. } => match find_kind(*id) {
+                Some(kind) => format!("T{:02x}{}:{:x};", SIGTRAP, kind.stop_key(), access.addr),
+                None => format!("S{:02x}", SIGTRAP),
+            },
+            StopReason::Paused => format!("S{:02x}", SIGINT),
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    fn registers(nes: &Nes) -> String {
+        let cpu = &nes.cpu;
+        to_hex(&[
+            cpu.a,
+            cpu.x,
+            cpu.y,
+            cpu.st,
+            cpu.sp,
+            cpu.pc as u8,
+            (cpu.pc >> 8) as u8,
+        ])
+    }
+
+    fn write_register(nes: &mut Nes, index: u32, bytes: &[u8]) -> bool {
+        let cpu = &mut nes.cpu;
+        match (index, bytes) {
+            (0, [value]) => cpu.a = *value,
+            (1, [value]) => cpu.x = *value,
+            (2, [value]) => cpu.y = *value,
+            (3, [value]) => cpu.st = *value,
+            (4, [value]) => cpu.sp = *value,
+            (5, [lo, hi]) => cpu.pc = u16::from_le_bytes([*lo, *hi]),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handle one packet and return the reply, or `None` when the reply is
+    /// deferred (`c`) or the session ended.
+    fn handle_packet(
+        &mut self,
+        data: &str,
+        nes: &mut Nes,
+        debugger: &mut Debugger,
+    ) -> Option<String> {
+        let error = || Some("E01".to_string());
+        let (command, args) = data.split_at(1.min(data.len()));
+
+        match command {
+            "?" => Some(format!("S{:02x}", SIGTRAP)),
+            "g" => Some(Self::registers(nes)),
+            "G" => match from_hex(args) {
+                Some(bytes) if bytes.len() == 7 => {
+                    let cpu = &mut nes.cpu;
+                    cpu.a = bytes[0];
+                    cpu.x = bytes[1];
+                    cpu.y = bytes[2];
+                    cpu.st = bytes[3];
+                    cpu.sp = bytes[4];
+                    cpu.pc = u16::from_le_bytes([bytes[5], bytes[6]]);
+                    Some("OK".to_string())
+                }
+                _ => error(),
+            },
+            "p" => {
+                let registers = from_hex(&Self::registers(nes))?;
+                match parse_hex(args) {
+                    Some(index @ 0..=4) => {
+                        Some(to_hex(&registers[index as usize..=index as usize]))
+                    }
+                    Some(5) => Some(to_hex(&registers[5..7])),
+                    _ => error(),
+                }
+            }
+            "P" => {
+                let (index, value) = args.split_once('=')?;
+                match (parse_hex(index), from_hex(value)) {
+                    (Some(index), Some(bytes)) if Self::write_register(nes, index, &bytes) => {
+                        Some("OK".to_string())
+                    }
+                    _ => error(),
+                }
+            }
+            "m" => match parse_addr_len(args) {
+                Some((addr, len)) => {
+                    let bytes: Vec<u8> = (0..len.min(PACKET_SIZE as u32 / 2))
+                        .map(|offset| nes.bus.peek(addr.wrapping_add(offset as u16)))
+                        .collect();
+                    Some(to_hex(&bytes))
+                }
+                None => error(),
+            },
+            "M" => {
+                let (range, payload) = args.split_once(':')?;
+                match (parse_addr_len(range), from_hex(payload)) {
+                    (Some((addr, len)), Some(bytes)) if bytes.len() == len as usize => {
+                        // Only RAM is patched; registers and ROM report an error.
+                        let mut stored = true;
+                        for (offset, byte) in bytes.into_iter().enumerate() {
+                            stored &= nes.bus.poke(addr.wrapping_add(offset as u16), byte);
+                        }
+                        if stored {
+                            Some("OK".to_string())
+                        } else {
+                            error()
+                        }
+                    }
+                    _ => error(),
+                }
+            }
+            "c" | "s" => {
+                if !args.is_empty() {
+                    match parse_hex(args) {
+                        Some(addr) => nes.cpu.pc = addr as u16,
+                        None => return error(),
+                    }
+                }
+                if command == "s" {
+                    let reason = debugger.step_into(nes);
+                    Some(self.stop_reply(&reason))
+                } else {
+                    debugger.paused = false;
+                    if let Some(client) = self.client.as_mut() {
+                        client.running = true;
+                    }
+                    None
+                }
+            }
+            "Z" | "z" => self.handle_breakpoint(command == "Z", args, debugger),
+            "k" | "D" => {
+                let reply = (command == "D").then(|| "OK".to_string());
+                if let Some(client) = self.client.as_mut() {
+                    if let Some(reply) = reply.as_deref() {
+                        let _ = Self::send(client, reply);
+                    }
+                    client.closed = true;
+                }
+                None
+            }
+            "H" | "T" => Some("OK".to_string()),
+            "q" | "Q" => self.handle_query(data),
+            _ => Some(String::new()),
+        }
+    }
+
+    fn handle_breakpoint(
+        &mut self,
+        insert: bool,
+        args: &str,
+        debugger: &mut Debugger,
+    ) -> Option<String> {
+        let mut fields = args.splitn(3, ',');
+        let kind = fields
+            .next()
+            .and_then(|k| BreakKind::from_packet(*k.as_bytes().first()?));
+        let addr = fields.next().and_then(parse_hex).filter(|a| *a <= 0xFFFF);
+        let len = fields
+            .next()
+            .and_then(|l| parse_hex(l.split(';').next()?))
+            .filter(|len| *len <= 0xFFFF);
+        let (Some(kind), Some(addr), Some(len)) = (kind, addr, len) else {
+            return Some("E01".to_string());
+        };
+        let addr = addr as u16;
+        let key = (kind, addr);
+
+        if !insert {
+            if let Some(id) = self.inserted.remove(&key) {
+                debugger.remove(id);
+            }
+            return Some("OK".to_string());
+        }
+        if self.inserted.contains_key(&key) {
+            return Some("OK".to_string());
+        }
+
+        let id = match kind {
+            BreakKind::Software | BreakKind::Hardware => debugger.add_breakpoint(addr, None),
+            BreakKind::Write | BreakKind::Read | BreakKind::Access => {
+                debugger.add_watchpoint(Watchpoint {
+                    id: 0,
+                    space: AddressSpace::Cpu,
+                    start: addr,
+                    end: addr.saturating_add(len.saturating_sub(1) as u16),
+                    read: kind != BreakKind::Write,
+                    write: kind != BreakKind::Read,
+                    execute: false,
+                    condition: None,
+                })
+            }
+        };
+        self.inserted.insert(key, id);
+        Some("OK".to_string())
+    }
+
+    fn handle_query(&mut self, data: &str) -> Option<String> {
+        if data.starts_with("qSupported") {
+            return Some(format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+",
+                PACKET_SIZE
+            ));
+        }
+        if data == "QStartNoAckMode" {
+            // The OK itself is still acked by the client; we stop acking afterwards.
+            if let Some(client) = self.client.as_mut() {
+                client.no_ack = true;
+            }
+            return Some("OK".to_string());
+        }
+        if data == "qAttached" {
+            return Some("1".to_string());
+        }
+        if data == "qC" {
+            return Some("QC1".to_string());
+        }
+        if data == "qfThreadInfo" {
+            return Some("m1".to_string());
+        }
+        if data == "qsThreadInfo" {
+            return Some("l".to_string());
+        }
+        if let Some(range) = data.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, len) = range.split_once(',')?;
+            let offset = (parse_hex(offset)? as usize).min(TARGET_XML.len());
+            let end = offset
+                .saturating_add(parse_hex(len)? as usize)
+                .min(TARGET_XML.len());
+            let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+            return Some(format!("{}{}", prefix, &TARGET_XML[offset..end]));
+        }
+        Some(String::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_nrom;
+    use std::time::{Duration, Instant};
+
+    const PROGRAM: &[u8] = &[
+        0xA9, 0x05, // C000 LDA #$05
+        0x8D, 0x00, 0x03, // C002 STA $0300
+        0xE8, // C005 INX
+        0x4C, 0x05, 0xC0, // C006 JMP $C005
+    ];
+
+    /// Stands in for the frontend: a scripted client on one side, and on the
+    /// other the server plus an emulation loop that runs while unpaused.
+    struct Harness {
+        nes: Nes,
+        debugger: Debugger,
+        server: GdbServer,
+        client: TcpStream,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let mut nes = Nes::new_with_rom(&test_nrom(1, PROGRAM)).unwrap();
+            nes.reset();
+            let server = GdbServer::bind("127.0.0.1:0").unwrap();
+            let client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+            client.set_nonblocking(true).unwrap();
+            Self {
+                nes,
+                debugger: Debugger::new(),
+                server,
+                client,
+            }
+        }
+
+        fn pump(&mut self) {
+            self.server.poll(&mut self.nes, &mut self.debugger).unwrap();
+            for _ in 0..1000 {
+                if self.debugger.paused {
+                    break;
+                }
+                if let (_, Some(reason)) = self.debugger.run_instruction(&mut self.nes) {
+                    self.server.notify_stop(&reason).unwrap();
+                }
+            }
+        }
+
+        fn pump_until(&mut self, done: impl Fn(&Self) -> bool) {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !done(self) {
+                assert!(Instant::now() < deadline, "timed out");
+                self.pump();
+            }
+        }
+
+        /// Send a packet and wait for the reply (acks are stripped).
+        fn request(&mut self, data: &str) -> String {
+            self.client.write_all(&frame_packet(data)).unwrap();
+            self.read_reply()
+        }
+
+        fn read_reply(&mut self) -> String {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut received = Vec::new();
+            let mut buf = [0u8; 1024];
+            loop {
+                assert!(Instant::now() < deadline, "no reply: {:?}", received);
+                self.pump();
+                match self.client.read(&mut buf) {
+                    Ok(n) => received.extend_from_slice(&buf[..n]),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(err) => panic!("{}", err),
+                }
+                let text = String::from_utf8_lossy(&received).into_owned();
+                let text = text.trim_start_matches('+');
+                if let (Some(start), Some(hash)) = (text.find('$'), text.find('#')) {
+                    if text.len() >= hash + 3 {
+                        self.client.write_all(b"+").unwrap();
+                        return text[start + 1..hash].to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_packet_framing() {
+        assert_eq!(frame_packet("OK"), b"$OK#9a".to_vec());
+        assert_eq!(from_hex("0aff"), Some(vec![0x0A, 0xFF]));
+        assert_eq!(parse_addr_len("c000,10"), Some((0xC000, 0x10)));
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let mut h = Harness::new();
+        assert!(h.request("qSupported:swbreak+").contains("PacketSize"));
+        assert_eq!(h.request("?"), "S05");
+        assert!(h.debugger.paused);
+        let expected = format!("000000{:02x}{:02x}00c0", h.nes.cpu.st, h.nes.cpu.sp);
+        assert_eq!(h.request("g"), expected);
+
+        assert_eq!(h.request("mc000,3"), "a9058d");
+        let (cycles, dot) = (h.nes.bus.cycle_count, h.nes.bus.ppu.cycle);
+        assert_eq!(h.request("M0300,2:abcd"), "OK");
+        assert_eq!(h.nes.bus.peek(0x0301), 0xCD);
+        // Patching memory doesn't advance time or reach registers and ROM.
+        assert_eq!((h.nes.bus.cycle_count, h.nes.bus.ppu.cycle), (cycles, dot));
+        assert_eq!(h.request("M2006,1:3f"), "E01");
+        assert_eq!(h.request("Mc000,1:ea"), "E01");
+        assert_eq!(h.nes.bus.peek(0xC000), 0xA9);
+        assert_eq!(h.request("P1=42"), "OK");
+        assert_eq!(h.request("p1"), "42");
+        assert_eq!(h.request("p5"), "00c0");
+        assert_eq!(h.request("G0102030405a0c1"), "OK");
+        assert_eq!(h.nes.cpu.pc, 0xC1A0);
+        assert!(h
+            .request("qXfer:features:read:target.xml:0,fff")
+            .starts_with("l<?xml"));
+    }
+
+    #[test]
+    fn test_step_and_breakpoints() {
+        let mut h = Harness::new();
+        assert_eq!(h.request("s"), "S05");
+        assert_eq!(h.nes.cpu.pc, 0xC002);
+        assert_eq!(h.nes.cpu.a, 0x05);
+
+        assert_eq!(h.request("Z0,c005,1"), "OK");
+        assert_eq!(h.request("c"), "T05swbreak:;");
+        assert_eq!(h.nes.cpu.pc, 0xC005);
+        assert_eq!(h.request("z0,c005,1"), "OK");
+        assert!(h.debugger.breakpoints.is_empty());
+
+        assert_eq!(h.request("Z2,0300,10000"), "E01");
+        assert_eq!(h.request("Z2,0300,1"), "OK");
+        h.nes.cpu.pc = 0xC002;
+        assert_eq!(h.request("c"), "T05watch:300;");
+    }
+
+    #[test]
+    fn test_interrupt_and_detach() {
+        let mut h = Harness::new();
+        h.request("?");
+        h.client.write_all(&frame_packet("c")).unwrap();
+        h.pump_until(|h| !h.debugger.paused);
+        h.client.write_all(&[0x03]).unwrap();
+        assert_eq!(h.read_reply(), "S02");
+        assert!(h.debugger.paused);
+
+        assert_eq!(h.request("D"), "OK");
+        h.pump_until(|h| !h.server.is_attached());
+        assert!(!h.debugger.paused);
+    }
+
+    #[test]
+    fn test_full_socket_queues_replies() {
+        let mut h = Harness::new();
+        h.request("?");
+        // Fill the socket towards the client, which isn't reading, with
+        // filler bytes; the next reply must be queued instead of blocking.
+        let filler = vec![b'+'; 0x10000];
+        let mut filled = 0;
+        h.client.write_all(&frame_packet("mc000,3")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while h.server.client.as_ref().unwrap().output.is_empty() {
+            assert!(Instant::now() < deadline, "reply was never queued");
+            let stream = &mut h.server.client.as_mut().unwrap().stream;
+            loop {
+                match stream.write(&filler) {
+                    Ok(n) => filled += n,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => panic!("{}", err),
+                }
+            }
+            h.pump();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut received = Vec::new();
+        let mut buf = [0u8; 0x10000];
+        let reply = frame_packet("a9058d");
+        // The filler, the ack for the request, then the reply.
+        while received.len() < filled + 1 + reply.len() {
+            assert!(Instant::now() < deadline, "got {} bytes", received.len());
+            h.pump();
+            match h.client.read(&mut buf) {
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+        assert!(received.ends_with(&reply));
+        assert!(h.server.client.as_ref().unwrap().output.is_empty());
+    }
+}
@@ -21,14 +21,37 @@ pub fn get_version() -> String {
 pub mod apu;
 pub mod bus;
 pub mod cartridge;
+pub mod checksum;
+pub mod clock;
 pub mod cpu;
+pub mod debug_view;
+pub mod debugger;
+pub mod disasm;
+pub mod game_db;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gdb;
 pub mod joypad;
+pub mod nestest;
+pub mod ntsc;
 pub mod opcodes;
+pub mod palette;
 pub mod ppu;
+pub mod region;
+pub mod rom_info;
+pub mod scaler;
+pub mod test_rom;
 
 use bus::Bus;
+use cartridge::{Rom, RomError};
 use cpu::Cpu;
+use debug_view::Overlays;
+use palette::Palette;
 use ppu::Ppu;
+use region::Region;
+use scaler::Scaler;
+
+/// Called with the new scanline number each time the PPU starts a scanline.
+pub type ScanlineCallback = Box<dyn FnMut(u16, &Bus)>;
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub enum JoypadButtonWasm {
@@ -58,11 +81,16 @@ pub struct Nes {
     apu_count: u32,
     prev_apu_sample: f32,
     filtered_sample: f32,
+
+    scanline_callback: Option<ScanlineCallback>,
+    last_scanline: u16,
+    scaler: Scaler,
+    overlays: Overlays,
 }
 
 impl Nes {
-    pub fn new_with_rom(rom_data: &[u8]) -> Self {
-        let rom = crate::cartridge::Rom::new(&rom_data.to_vec()).unwrap();
+    pub fn new_with_rom(rom_data: &[u8]) -> Result<Self, RomError> {
+        let rom = Rom::new(rom_data)?;
         let mut ppu = Ppu::new(rom.screen_mirroring, rom.chr_rom);
         ppu.mapper = rom.mapper;
         let bus = Bus::new(
@@ -73,7 +101,7 @@ impl Nes {
             rom.has_battery,
         );
         let cpu = Cpu::new();
-        Self {
+        let mut nes = Self {
             cpu,
             bus,
             audio_samples: Vec::with_capacity(4096),
@@ -83,6 +111,112 @@ impl Nes {
             apu_count: 0,
             prev_apu_sample: 0.0,
             filtered_sample: 0.0,
+            scanline_callback: None,
+            last_scanline: 0,
+            scaler: Scaler::None,
+            overlays: Overlays::default(),
+        };
+        nes.set_region(rom.region);
+        Ok(nes)
+    }
+
+    /// Swap in a new cartridge and reset. On error the current game keeps running.
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), RomError> {
+        let rom = Rom::new(rom_data)?;
+        self.bus.prg_rom = rom.prg_rom;
+        self.bus.ppu.chr_rom = rom.chr_rom;
+        self.bus.ppu.mirroring = rom.screen_mirroring;
+        self.bus.ppu.cartridge_vram = Ppu::cartridge_vram_for(rom.screen_mirroring);
+        self.bus.ppu.nametable_map = None;
+        self.bus.mapper = rom.mapper;
+        self.bus.ppu.mapper = rom.mapper;
+        self.bus.prg_ram = vec![0; rom.prg_ram_size.max(0x2000)];
+        self.bus.has_battery = rom.has_battery;
+        self.bus.reset_mapper_state();
+        self.set_region(rom.region);
+        self.reset();
+        Ok(())
+    }
+
+    pub fn region(&self) -> Region {
+        self.bus.ppu.region
+    }
+
+    /// Switch CPU clock, PPU frame layout and APU tables to `region`,
+    /// overriding the timing from the ROM header. The built-in palette
+    /// follows too, since PAL PPUs swap the red and green emphasis bits; a
+    /// palette from `set_palette` is kept.
+    pub fn set_region(&mut self, region: Region) {
+        if self.bus.ppu.output_palette == Palette::for_region(self.region()) {
+            self.bus.ppu.output_palette = Palette::for_region(region);
+        }
+        self.bus.master_clock = region.master_clock();
+        self.bus.ppu.region = region;
+        self.bus.apu.set_region(region);
+    }
+
+    /// Upscaler used by `scaled_frame`.
+    pub fn set_scaler(&mut self, scaler: Scaler) {
+        self.scaler = scaler;
+    }
+
+    pub fn scaler(&self) -> Scaler {
+        self.scaler
+    }
+
+    /// Debug marks drawn by `overlay`.
+    pub fn set_overlays(&mut self, overlays: Overlays) {
+        self.overlays = overlays;
+    }
+
+    pub fn overlays(&self) -> Overlays {
+        self.overlays
+    }
+
+    /// Colors used for the frame buffer and the debug views.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.bus.ppu.output_palette = palette;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.bus.ppu.output_palette
+    }
+
+    /// The 256x240 RGBA image drawn so far, without copying it.
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.bus.ppu.frame_buffer
+    }
+
+    /// The same 256x240 image as palette indices (color plus emphasis << 6),
+    /// unaffected by the output palette.
+    pub fn frame_indices(&self) -> &[u16] {
+        &self.bus.ppu.frame_indices
+    }
+
+    /// Color subcarrier phase (0-11) at the start of each visible line, for
+    /// `ntsc::NtscFilter`.
+    pub fn scanline_phases(&self) -> &[u8] {
+        &self.bus.ppu.scanline_phases
+    }
+
+    /// Install or remove a hook that runs as each scanline begins, e.g. for
+    /// raster-effect debugging. It runs after the instruction that crossed
+    /// the scanline boundary.
+    pub fn set_scanline_callback(&mut self, callback: Option<ScanlineCallback>) {
+        self.last_scanline = self.bus.ppu.scanline;
+        self.scanline_callback = callback;
+    }
+
+    fn notify_scanlines(&mut self) {
+        let Some(callback) = self.scanline_callback.as_mut() else {
+            return;
+        };
+        let scanlines = self.bus.ppu.region.scanlines();
+        // A region switch can leave the PPU briefly past the new frame length.
+        let target = self.bus.ppu.scanline.min(scanlines - 1);
+        while self.last_scanline != target {
+            self.last_scanline = (self.last_scanline + 1) % scanlines;
+            callback(self.last_scanline, &self.bus);
         }
     }
 
@@ -116,21 +250,30 @@ impl Nes {
         full_rom.extend(dummy_rom);
         full_rom.extend(vec![0; 0x2000]); // CHR ROM
 
-        Self::new_with_rom(&full_rom)
+        Self::new_with_rom(&full_rom).expect("built-in ROM is valid")
     }
 
-    pub fn load_rom(&mut self, rom_data: &[u8]) {
-        if let Ok(rom) = crate::cartridge::Rom::new(&rom_data.to_vec()) {
-            self.bus.prg_rom = rom.prg_rom;
-            self.bus.ppu.chr_rom = rom.chr_rom;
-            self.bus.ppu.mirroring = rom.screen_mirroring;
-            self.bus.mapper = rom.mapper;
-            self.bus.ppu.mapper = rom.mapper;
-            self.bus.prg_ram = vec![0; rom.prg_ram_size.max(0x2000)];
-            self.bus.has_battery = rom.has_battery;
-            self.bus.reset_mapper_state();
-            self.reset();
-        }
+    /// `load_rom` for JS: a rejected file throws instead of leaving a dead tab.
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = load_rom)]
+    pub fn load_rom_js(&mut self, rom_data: &[u8]) -> Result<(), JsError> {
+        self.load_rom(rom_data)?;
+        Ok(())
+    }
+
+    /// Use a 64-, 512- or 1536-color `.pal` file for the current region;
+    /// an invalid file throws.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_palette(&mut self, data: &[u8]) -> Result<(), JsError> {
+        self.set_palette(Palette::from_pal_file(data, self.region())?);
+        Ok(())
+    }
+
+    /// Load `nes20db.xml` so later ROMs get their headers corrected from it;
+    /// returns the number of entries, or throws on a malformed file.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_game_db(xml: &str) -> Result<usize, JsError> {
+        Ok(game_db::load_nes20db(xml)?)
     }
 
     pub fn reset(&mut self) {
@@ -138,43 +281,19 @@ impl Nes {
     }
 
     pub fn tick(&mut self) -> usize {
-        self.bus.ppu_cycles_advanced = 0;
+        // The PPU and APU are clocked by the bus on every CPU and DMA cycle,
+        // and NMI/IRQ are polled by the CPU itself during each instruction.
         let cycles = self.cpu.step(&mut self.bus);
-
-        // PPU catch-up: the PPU was partially advanced during bus.read()/write() calls.
-        // Advance the remaining PPU cycles for this instruction.
-        let total_ppu_cycles = (cycles as u16) * 3;
-        let remaining = total_ppu_cycles.saturating_sub(self.bus.ppu_cycles_advanced);
-        self.bus.ppu.tick(remaining);
-
-        self.bus.tick_apu(cycles as u16);
-
-        // NMI is checked via the persistent nmi_interrupt flag, which is set
-        // by tick() during both catch-up (bus.read/write) and remaining cycles.
-        if self.bus.ppu.nmi_interrupt {
-            self.cpu.nmi(&mut self.bus);
-            self.bus.ppu.nmi_interrupt = false;
-        }
-
-        // Handle IRQ from APU (frame counter IRQ / DMC IRQ)
-        if self.bus.apu.is_irq_pending() {
-            self.cpu.irq(&mut self.bus);
-        }
-
-        // Handle IRQ from MMC3 scanline counter
-        if self.bus.ppu.mmc3_irq_pending {
-            self.cpu.irq(&mut self.bus);
-            self.bus.ppu.mmc3_irq_pending = false;
-        }
+        self.notify_scanlines();
 
         // Audio logic
         let step_cycles = cycles as u32;
-        let current_output = self.bus.apu.output();
+        let current_output = self.bus.audio_output();
         self.apu_sum += current_output * step_cycles as f32;
         self.apu_count += step_cycles;
 
         self.audio_samples_needed +=
-            step_cycles as f64 * (self.audio_sample_rate as f64 / 1789773.0);
+            step_cycles as f64 * (self.audio_sample_rate as f64 / self.bus.master_clock.cpu_hz());
         if self.audio_samples_needed >= 1.0 {
             let num_samples = self.audio_samples_needed as i32;
             for _ in 0..num_samples {
@@ -205,6 +324,58 @@ impl Nes {
         cycles as usize
     }
 
+    /// Run until the PPU enters VBlank and return the CPU cycles taken.
+    pub fn run_frame(&mut self) -> usize {
+        let frame = self.frame_count();
+        let mut cycles = 0;
+        while self.frame_count() == frame {
+            cycles += self.tick();
+        }
+        cycles
+    }
+
+    /// Frames completed since power-on.
+    pub fn frame_count(&self) -> u64 {
+        self.bus.ppu.frame_count
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        self.region().frame_rate()
+    }
+
+    /// Address of the RGBA frame buffer in WASM memory, so JS can view it
+    /// without the copy made by `draw`.
+    pub fn frame_buffer_ptr(&self) -> *const u8 {
+        self.bus.ppu.frame_buffer.as_ptr()
+    }
+
+    /// The frame buffer run through the current scaler, as a
+    /// `scaled_width` x `scaled_height` RGBA image.
+    pub fn scaled_frame(&self) -> Vec<u8> {
+        self.scaler.apply(&self.bus.ppu.frame_buffer, 256, 240)
+    }
+
+    pub fn scaled_width(&self) -> usize {
+        256 * self.scaler.factor()
+    }
+
+    pub fn scaled_height(&self) -> usize {
+        240 * self.scaler.factor()
+    }
+
+    /// Select a scaler by name (`scale2x`, `xbr`, ...); unknown names throw.
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = set_scaler)]
+    pub fn set_scaler_js(&mut self, name: &str) -> Result<(), JsError> {
+        self.set_scaler(name.parse().map_err(|err: String| JsError::new(&err))?);
+        Ok(())
+    }
+
+    /// Address of the `frame_indices` buffer (256x240 u16) in WASM memory.
+    pub fn frame_indices_ptr(&self) -> *const u16 {
+        self.bus.ppu.frame_indices.as_ptr()
+    }
+
     pub fn get_audio_samples(&mut self) -> Vec<f32> {
         let mut samples = Vec::new();
         std::mem::swap(&mut samples, &mut self.audio_samples);
@@ -215,6 +386,89 @@ impl Nes {
         self.bus.ppu.draw(frame);
     }
 
+    /// Both pattern tables as a 256x128 RGBA image in `palette` (0-7).
+    pub fn pattern_tables(&self, palette: u8) -> Vec<u8> {
+        debug_view::pattern_tables(&self.bus.ppu, palette)
+    }
+
+    /// All four nametables as a 512x480 RGBA image with the scroll window outlined.
+    pub fn nametables(&self) -> Vec<u8> {
+        debug_view::nametables(&self.bus.ppu)
+    }
+
+    /// The 64 sprites as a 64x128 RGBA image, eight per row.
+    pub fn oam_sprites(&self) -> Vec<u8> {
+        debug_view::oam_sprites(&self.bus.ppu)
+    }
+
+    /// Palette RAM as a 256x32 RGBA image, background row first.
+    pub fn palette_ram(&self) -> Vec<u8> {
+        debug_view::palette_ram(&self.bus.ppu)
+    }
+
+    /// Draw all sprites on each line instead of the hardware's eight, to
+    /// remove flicker. Game-visible behaviour (sprite overflow, sprite 0
+    /// hit) is unchanged.
+    pub fn set_unlimited_sprites(&mut self, enabled: bool) {
+        self.bus.ppu.unlimited_sprites = enabled;
+    }
+
+    pub fn unlimited_sprites(&self) -> bool {
+        self.bus.ppu.unlimited_sprites
+    }
+
+    /// Leave the background out of the picture. Like the sprite switches
+    /// below, this is display only: sprite 0 hit still sees the background.
+    pub fn set_hide_background(&mut self, hidden: bool) {
+        self.bus.ppu.hide_background = hidden;
+    }
+
+    pub fn hide_background(&self) -> bool {
+        self.bus.ppu.hide_background
+    }
+
+    pub fn set_hide_sprites(&mut self, hidden: bool) {
+        self.bus.ppu.hide_sprites = hidden;
+    }
+
+    pub fn hide_sprites(&self) -> bool {
+        self.bus.ppu.hide_sprites
+    }
+
+    /// Hide or show the sprite at OAM `index` (0-63); sprites behind it
+    /// show through. Other indices are ignored.
+    pub fn set_sprite_hidden(&mut self, index: u8, hidden: bool) {
+        let Some(bit) = 1u64.checked_shl(index as u32) else {
+            return;
+        };
+        if hidden {
+            self.bus.ppu.hidden_sprites |= bit;
+        } else {
+            self.bus.ppu.hidden_sprites &= !bit;
+        }
+    }
+
+    pub fn sprite_hidden(&self, index: u8) -> bool {
+        1u64.checked_shl(index as u32)
+            .is_some_and(|bit| self.bus.ppu.hidden_sprites & bit != 0)
+    }
+
+    /// `set_overlays` for JavaScript: sprite boxes, the sprite 0 hit mark
+    /// and MMC3 IRQ scanlines.
+    pub fn set_overlay_marks(&mut self, sprite_boxes: bool, sprite_zero_hit: bool, mmc3_irq: bool) {
+        self.set_overlays(Overlays {
+            sprite_boxes,
+            sprite_zero_hit,
+            mmc3_irq,
+        });
+    }
+
+    /// The enabled overlays for the last frame as a transparent 256x240
+    /// RGBA image, to draw over the picture.
+    pub fn overlay(&self) -> Vec<u8> {
+        debug_view::overlay(&self.bus.ppu, self.overlays)
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
     pub fn set_joypad_button_wasm(&mut self, button: JoypadButtonWasm, status: bool) {
         let btn = match button {
@@ -230,3 +484,126 @@ impl Nes {
         self.set_joypad_button(btn, status);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_run_frame_stops_at_vblank() {
+        let mut nes = Nes::new();
+        nes.reset();
+        let start = nes.frame_count();
+        let cycles = nes.run_frame();
+        assert_eq!(nes.frame_count(), start + 1);
+        assert_eq!(nes.bus.ppu.scanline, 241);
+        assert!(cycles < 29781);
+
+        // A full frame later the PPU is back at the start of vblank.
+        let cycles = nes.run_frame();
+        assert_eq!(nes.bus.ppu.scanline, 241);
+        // Frames are 29780.5 cycles; run_frame returns after whole instructions.
+        assert!((29774..=29788).contains(&cycles), "{}", cycles);
+        assert_eq!(nes.frame_buffer().len(), 256 * 240 * 4);
+        assert_eq!(nes.frame_indices().len(), 256 * 240);
+    }
+
+    #[test]
+    fn test_frame_indices_ignore_palette() {
+        // Opaque tile 0 everywhere in color $16, with red emphasis.
+        let render = |palette: Option<Palette>| {
+            let mut nes = Nes::new();
+            if let Some(palette) = palette {
+                nes.set_palette(palette);
+            }
+            nes.reset();
+            nes.bus.ppu.chr_rom[..16].fill(0xFF);
+            nes.bus.ppu.palette[3] = 0x16;
+            nes.bus.ppu.mask = 0x2A;
+            nes.run_frame();
+            nes.run_frame();
+            nes
+        };
+        let nes = render(None);
+        let indices = nes.frame_indices().to_vec();
+        assert!(indices.iter().all(|&index| index == 0x16 | (1 << 6)));
+        let palette = &nes.bus.ppu.output_palette;
+        assert_ne!(palette.colors[0x56], palette.colors[0x16]);
+        for (pixel, &index) in nes.frame_buffer().chunks_exact(4).zip(&indices) {
+            let (r, g, b) = palette.colors[index as usize];
+            assert_eq!(pixel, &[r, g, b, 0xFF]);
+        }
+
+        // A different palette changes the RGB output but not the indices.
+        let nes2 = render(Some(Palette::from_colors(&[(0, 0, 0); 64], Region::Ntsc)));
+        assert_eq!(nes2.frame_indices(), &indices[..]);
+        assert!(nes2
+            .frame_buffer()
+            .chunks_exact(4)
+            .all(|p| p == [0, 0, 0, 0xFF]));
+    }
+
+    #[test]
+    fn test_region_switches_default_palette() {
+        let mut nes = Nes::new();
+        nes.set_region(Region::Pal);
+        assert_eq!(nes.bus.ppu.output_palette, Palette::for_region(Region::Pal));
+        nes.set_region(Region::Ntsc);
+        assert_eq!(nes.bus.ppu.output_palette, Palette::default());
+
+        let custom = Palette::from_colors(&[(1, 2, 3); 64], Region::Ntsc);
+        nes.set_palette(custom.clone());
+        nes.set_region(Region::Dendy);
+        assert_eq!(nes.bus.ppu.output_palette, custom);
+    }
+
+    #[test]
+    fn test_hidden_layers_keep_sprite_zero_hit() {
+        let mut nes = Nes::new();
+        nes.reset();
+        // An opaque tile 0 everywhere, with sprite 0 on top of it.
+        nes.bus.ppu.chr_rom[..16].fill(0xFF);
+        nes.bus.ppu.palette[3] = 0x16;
+        nes.bus.ppu.palette[0x13] = 0x2A;
+        nes.bus.ppu.oam.fill(0xFF);
+        nes.bus.ppu.oam[..4].copy_from_slice(&[49, 0, 0, 100]);
+        nes.set_hide_background(true);
+        nes.set_sprite_hidden(0, true);
+        assert!(nes.sprite_hidden(0) && !nes.sprite_hidden(1));
+        nes.set_sprite_hidden(64, true);
+        assert!(!nes.sprite_hidden(64));
+        assert_eq!(nes.bus.ppu.hidden_sprites, 1);
+        nes.run_frame();
+        nes.bus.ppu.mask = 0x1E;
+        nes.run_frame();
+
+        // Neither layer is drawn, but the game still sees the hit.
+        let (x, y) = nes.bus.ppu.sprite_zero_hit_dot.expect("sprite 0 hit");
+        assert_eq!(y, 50);
+        let pixel = y as usize * 256 + x as usize;
+        assert_eq!(nes.frame_indices()[pixel], nes.bus.ppu.palette[0] as u16);
+
+        nes.set_sprite_hidden(0, false);
+        nes.run_frame();
+        assert_eq!(nes.frame_indices()[pixel], 0x2A);
+    }
+
+    #[test]
+    fn test_scanline_callback_sees_every_line() {
+        let mut nes = Nes::new();
+        nes.reset();
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&lines);
+        nes.set_scanline_callback(Some(Box::new(move |line, _| seen.borrow_mut().push(line))));
+        for _ in 0..3 {
+            nes.run_frame();
+        }
+
+        let lines = lines.borrow();
+        let frame_start = lines.iter().position(|&line| line == 0).unwrap();
+        let expected: Vec<u16> = (0..262).collect();
+        assert_eq!(lines[frame_start..frame_start + 262], expected[..]);
+    }
+}
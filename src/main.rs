@@ -1,16 +1,24 @@
 use anyhow::{Error, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use log::error;
+use log::{error, info};
 use pixels::{Pixels, SurfaceTexture};
+use rust_emu::debug_view;
+use rust_emu::debugger::Debugger;
+use rust_emu::gdb::GdbServer;
 use rust_emu::joypad::JoypadButton;
+use rust_emu::ntsc::{NtscFilter, NtscPreset, NTSC_HEIGHT, NTSC_WIDE_WIDTH, NTSC_WIDTH};
+use rust_emu::palette::{Palette, PaletteParams};
+use rust_emu::scaler::Scaler;
 use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use winit::dpi::LogicalSize;
-use winit::event::{Event, VirtualKeyCode};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use winit::event_loop::EventLoop;
-use winit::window::WindowBuilder;
+use winit::window::{Window, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
 
 const _SAMPLE_RATE: u32 = 44100;
@@ -18,19 +26,309 @@ const _SAMPLE_RATE: u32 = 44100;
 const WIDTH: u32 = 256;
 const HEIGHT: u32 = 240;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViewerKind {
+    Patterns,
+    Nametables,
+    Sprites,
+    Palettes,
+}
+
+impl ViewerKind {
+    /// Image width, height, window scale and title.
+    fn layout(self) -> (usize, usize, f64, &'static str) {
+        match self {
+            ViewerKind::Patterns => (
+                debug_view::PATTERN_VIEW_WIDTH,
+                debug_view::PATTERN_VIEW_HEIGHT,
+                3.0,
+                "Pattern tables (P: next palette)",
+            ),
+            ViewerKind::Nametables => (
+                debug_view::NAMETABLE_VIEW_WIDTH,
+                debug_view::NAMETABLE_VIEW_HEIGHT,
+                1.5,
+                "Nametables",
+            ),
+            ViewerKind::Sprites => (
+                debug_view::OAM_VIEW_WIDTH,
+                debug_view::OAM_VIEW_HEIGHT,
+                4.0,
+                "OAM",
+            ),
+            ViewerKind::Palettes => (
+                debug_view::PALETTE_VIEW_WIDTH,
+                debug_view::PALETTE_VIEW_HEIGHT,
+                2.0,
+                "Palette RAM",
+            ),
+        }
+    }
+}
+
+/// A debug window (`--viewers`) showing one of the `debug_view` images.
+struct Viewer {
+    kind: ViewerKind,
+    window: Window,
+    pixels: Pixels,
+    /// Palette (0-7) the pattern tables are drawn in.
+    palette: u8,
+}
+
+impl Viewer {
+    fn open(event_loop: &EventLoop<()>, kind: ViewerKind) -> Result<Self> {
+        let (width, height, scale, title) = kind.layout();
+        let window = WindowBuilder::new()
+            .with_title(title)
+            .with_inner_size(LogicalSize::new(
+                width as f64 * scale,
+                height as f64 * scale,
+            ))
+            .build(event_loop)
+            .map_err(Error::msg)?;
+        let size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(size.width, size.height, &window);
+        let pixels =
+            Pixels::new(width as u32, height as u32, surface_texture).map_err(Error::msg)?;
+        Ok(Self {
+            kind,
+            window,
+            pixels,
+            palette: 0,
+        })
+    }
+
+    fn render(&mut self, nes: &rust_emu::Nes) -> std::result::Result<(), pixels::Error> {
+        let image = match self.kind {
+            ViewerKind::Patterns => nes.pattern_tables(self.palette),
+            ViewerKind::Nametables => nes.nametables(),
+            ViewerKind::Sprites => nes.oam_sprites(),
+            ViewerKind::Palettes => nes.palette_ram(),
+        };
+        self.pixels.frame_mut().copy_from_slice(&image);
+        self.pixels.render()
+    }
+
+    /// Handle an event for this window. Returns false once it is closed.
+    fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CloseRequested => return false,
+            WindowEvent::Resized(size) => {
+                if let Err(err) = self.pixels.resize_surface(size.width, size.height) {
+                    error!("pixels.resize_surface() failed: {}", err);
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::P),
+                        ..
+                    },
+                ..
+            } if self.kind == ViewerKind::Patterns => {
+                self.palette = (self.palette + 1) % 8;
+                self.window.request_redraw();
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
 fn write_save_if_needed(nes: &rust_emu::Nes, save_path: &Option<PathBuf>) {
     if let (Some(path), Some(save_data)) = (save_path, nes.battery_ram_data()) {
         let _ = std::fs::write(path, save_data);
     }
 }
 
+/// Read debugger commands from stdin on a background thread so the window
+/// keeps rendering while waiting for input.
+fn spawn_debug_console() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn print_debug_prompt() {
+    print!("(debug) ");
+    let _ = std::io::stdout().flush();
+}
+
+/// Compare execution from $C000 against a nestest golden log and stop at the
+/// first divergence.
+fn run_nestest(rom_path: Option<&Path>, log_path: &Path) -> Result<()> {
+    let rom_path = rom_path.ok_or_else(|| Error::msg("--nestest requires a ROM path"))?;
+    let rom_data = std::fs::read(rom_path).map_err(Error::msg)?;
+    let reference = std::fs::read_to_string(log_path).map_err(Error::msg)?;
+
+    let mut nes = rust_emu::Nes::new_with_rom(&rom_data)?;
+    rust_emu::nestest::prepare_automation(&mut nes);
+    match rust_emu::nestest::compare_with_log(&mut nes, &reference) {
+        Ok(lines) => {
+            println!("nestest: all {} lines match", lines);
+            Ok(())
+        }
+        Err(divergence) => {
+            println!("{}", divergence);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--palette`: a `.pal` file for `region`, or generator settings such as
+/// `ntsc,hue=-10,saturation=1.2`.
+fn load_palette(arg: &str, region: rust_emu::region::Region) -> Result<Palette> {
+    if Path::new(arg).is_file() {
+        Ok(Palette::from_pal_file(&std::fs::read(arg)?, region)?)
+    } else {
+        let params: PaletteParams = arg.parse().map_err(Error::msg)?;
+        Ok(Palette::generate(&params))
+    }
+}
+
 fn main() -> Result<()> {
     env_logger::init();
+
+    // Load ROM
+    let args: Vec<String> = std::env::args().collect();
+    let mut rom_path: Option<PathBuf> = None;
+    let mut tracing = false;
+    let mut mmc1_logging = false;
+    let mut nestest_log: Option<PathBuf> = None;
+    let mut debugging = false;
+    let mut gdb_port: Option<u16> = None;
+    let mut region: Option<rust_emu::region::Region> = None;
+    let mut patch_path: Option<PathBuf> = None;
+    let mut game_db_path: Option<PathBuf> = None;
+    let mut show_info = false;
+    let mut show_viewers = false;
+    let mut unlimited_sprites = false;
+    let mut palette_arg: Option<String> = None;
+    let mut ntsc_preset: Option<NtscPreset> = None;
+    let mut ntsc_width = NTSC_WIDTH;
+    let mut scaler = Scaler::None;
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        if arg == "--trace" {
+            tracing = true;
+        } else if arg == "--mmc1-log" {
+            mmc1_logging = true;
+        } else if arg == "--debug" {
+            debugging = true;
+        } else if arg == "--gdb" {
+            gdb_port = Some(
+                arg_iter
+                    .next()
+                    .and_then(|port| port.parse().ok())
+                    .ok_or_else(|| Error::msg("--gdb expects a port number"))?,
+            );
+        } else if arg == "--region" {
+            region = Some(
+                arg_iter
+                    .next()
+                    .ok_or_else(|| Error::msg("--region expects ntsc, pal or dendy"))?
+                    .parse()
+                    .map_err(Error::msg)?,
+            );
+        } else if arg == "--patch" {
+            patch_path = Some(PathBuf::from(
+                arg_iter
+                    .next()
+                    .ok_or_else(|| Error::msg("--patch expects an IPS file"))?,
+            ));
+        } else if arg == "--game-db" {
+            game_db_path =
+                Some(PathBuf::from(arg_iter.next().ok_or_else(|| {
+                    Error::msg("--game-db expects an NES 2.0 DB XML file")
+                })?));
+        } else if arg == "--viewers" {
+            show_viewers = true;
+        } else if arg == "--palette" {
+            palette_arg = Some(
+                arg_iter
+                    .next()
+                    .ok_or_else(|| Error::msg("--palette expects a .pal file, ntsc or pal"))?
+                    .clone(),
+            );
+        } else if arg == "--ntsc" {
+            ntsc_preset = Some(
+                arg_iter
+                    .next()
+                    .ok_or_else(|| Error::msg("--ntsc expects composite, svideo or rgb"))?
+                    .parse()
+                    .map_err(Error::msg)?,
+            );
+        } else if arg == "--scaler" {
+            scaler = arg_iter
+                .next()
+                .ok_or_else(|| Error::msg("--scaler expects a scaler name"))?
+                .parse()
+                .map_err(Error::msg)?;
+        } else if arg == "--ntsc-width" {
+            ntsc_width = arg_iter
+                .next()
+                .and_then(|width| width.parse().ok())
+                .filter(|&width| width == NTSC_WIDTH || width == NTSC_WIDE_WIDTH)
+                .ok_or_else(|| Error::msg("--ntsc-width expects 602 or 640"))?;
+        } else if arg == "--no-sprite-limit" {
+            unlimited_sprites = true;
+        } else if arg == "--info" {
+            show_info = true;
+        } else if arg == "--nestest" {
+            nestest_log = arg_iter.next().map(PathBuf::from);
+        } else if !arg.starts_with("--") && rom_path.is_none() {
+            rom_path = Some(PathBuf::from(arg));
+        }
+    }
+
+    if let Some(path) = game_db_path.as_ref() {
+        let xml = std::fs::read_to_string(path).map_err(Error::msg)?;
+        let count = rust_emu::game_db::load_nes20db(&xml)?;
+        info!(
+            "Loaded {} game database entries from {}",
+            count,
+            path.display()
+        );
+    }
+
+    if show_info {
+        let rom_path = rom_path.ok_or_else(|| Error::msg("--info requires a ROM path"))?;
+        let rom_data = std::fs::read(rom_path).map_err(Error::msg)?;
+        print!("{}", rust_emu::rom_info::rom_info(&rom_data)?);
+        return Ok(());
+    }
+
+    if let Some(log_path) = nestest_log.as_ref() {
+        return run_nestest(rom_path.as_deref(), log_path);
+    }
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
 
+    if ntsc_preset.is_some() && scaler != Scaler::None {
+        return Err(Error::msg("--ntsc and --scaler cannot be combined"));
+    }
+    let mut ntsc_filter = ntsc_preset.map(|preset| NtscFilter::new(preset, ntsc_width));
+    let (buffer_width, buffer_height) = match ntsc_filter.as_ref() {
+        Some(filter) => (filter.width() as u32, NTSC_HEIGHT as u32),
+        None => {
+            let factor = scaler.factor() as u32;
+            (WIDTH * factor, HEIGHT * factor)
+        }
+    };
+
     let window = {
-        let size = LogicalSize::new(WIDTH as f64 * 3.0, HEIGHT as f64 * 3.0);
+        // Open at three times the NES height, or at 1:1 for larger buffers.
+        let scale = (3 * HEIGHT).max(buffer_height) as f64 / buffer_height as f64;
+        let size = LogicalSize::new(buffer_width as f64 * scale, buffer_height as f64 * scale);
         WindowBuilder::new()
             .with_title(format!("Rust NES Emulator v{}", env!("CARGO_PKG_VERSION")))
             .with_inner_size(size)
@@ -42,21 +340,18 @@ fn main() -> Result<()> {
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture).map_err(Error::msg)?
+        Pixels::new(buffer_width, buffer_height, surface_texture).map_err(Error::msg)?
     };
 
-    // Load ROM
-    let args: Vec<String> = std::env::args().collect();
-    let mut rom_path: Option<PathBuf> = None;
-    let mut tracing = false;
-    let mut mmc1_logging = false;
-    for arg in args.iter().skip(1) {
-        if arg == "--trace" {
-            tracing = true;
-        } else if arg == "--mmc1-log" {
-            mmc1_logging = true;
-        } else if !arg.starts_with("--") && rom_path.is_none() {
-            rom_path = Some(PathBuf::from(arg));
+    let mut viewers = Vec::new();
+    if show_viewers {
+        for kind in [
+            ViewerKind::Patterns,
+            ViewerKind::Nametables,
+            ViewerKind::Sprites,
+            ViewerKind::Palettes,
+        ] {
+            viewers.push(Viewer::open(&event_loop, kind)?);
         }
     }
 
@@ -80,12 +375,27 @@ fn main() -> Result<()> {
         full_rom
     };
 
+    let rom_data = match patch_path.as_ref() {
+        Some(path) => rust_emu::cartridge::apply_ips(&rom_data, &std::fs::read(path)?)?,
+        None => rom_data,
+    };
+
     let save_path = rom_path.as_ref().map(|path| path.with_extension("sav"));
 
-    let mut nes = rust_emu::Nes::new_with_rom(&rom_data);
+    let mut nes = rust_emu::Nes::new_with_rom(&rom_data)?;
+    if let Some(region) = region {
+        nes.set_region(region);
+    }
     if mmc1_logging {
         nes.bus.set_mmc1_debug(true);
     }
+    nes.set_unlimited_sprites(unlimited_sprites);
+    if let Some(arg) = palette_arg.as_deref() {
+        nes.set_palette(load_palette(arg, nes.region())?);
+    }
+    if let Some(filter) = ntsc_filter.as_mut() {
+        filter.set_palette(nes.palette().clone());
+    }
     if let Some(path) = save_path.as_ref() {
         if let Ok(save_data) = std::fs::read(path) {
             nes.load_battery_ram(&save_data);
@@ -129,21 +439,44 @@ fn main() -> Result<()> {
     stream.play().unwrap();
 
     let mut audio_samples_needed = 0.0;
-    let samples_per_cpu_cycle = sample_rate as f64 / 1_789_773.0; // CPU clock rate
+    let samples_per_cpu_cycle = sample_rate as f64 / nes.bus.master_clock.cpu_hz();
 
     let mut last_frame_time = Instant::now();
-    let frame_duration = Duration::from_nanos(16639267); // NES NTSC ~60.098 Hz
+    // ~60.099 Hz on NTSC, ~50.007 Hz on PAL and Dendy
+    let frame_duration = Duration::from_secs_f64(1.0 / nes.region().frame_rate());
 
     // Simple High-pass filter (DC blocker) state
     let mut prev_apu_sample = 0.0;
     let mut filtered_sample = 0.0;
 
+    // Debugger REPL on stdin (--debug). Emulation starts paused.
+    let mut debugger = None;
+    let mut debug_commands = None;
+    if debugging {
+        let mut dbg = Debugger::new();
+        dbg.paused = true;
+        println!("{}", dbg.execute_command(&mut nes, "pause"));
+        println!("Type 'help' for debugger commands.");
+        print_debug_prompt();
+        debugger = Some(dbg);
+        debug_commands = Some(spawn_debug_console());
+    }
+
+    // GDB remote stub (--gdb <port>). The game keeps running until a client attaches.
+    let mut gdb_server = None;
+    if let Some(port) = gdb_port {
+        let server = GdbServer::bind(("127.0.0.1", port)).map_err(Error::msg)?;
+        println!("GDB server listening on {}", server.local_addr()?);
+        gdb_server = Some(server);
+        debugger.get_or_insert_with(Debugger::new);
+    }
+
     if tracing {
         // Run in headless mode for tracing
         nes.reset();
 
         loop {
-            println!("{}", nes.cpu.trace(&mut nes.bus));
+            println!("{}", nes.cpu.trace(&nes.bus));
             nes.tick();
 
             // Optional: Break on infinite loop or specific PC
@@ -153,10 +486,43 @@ fn main() -> Result<()> {
         event_loop.run(move |event, _, control_flow| {
             control_flow.set_poll();
 
+            // Viewer windows handle their own events; the emulator's input
+            // helper only sees the main window.
+            if let Event::WindowEvent { window_id, event } = &event {
+                if let Some(index) = viewers.iter().position(|v| v.window.id() == *window_id) {
+                    if !viewers[index].handle_event(event) {
+                        viewers.remove(index);
+                    }
+                    return;
+                }
+            }
+            if let Event::RedrawRequested(window_id) = event {
+                if let Some(viewer) = viewers.iter_mut().find(|v| v.window.id() == window_id) {
+                    if let Err(err) = viewer.render(&nes) {
+                        error!("viewer render failed: {}", err);
+                    }
+                    return;
+                }
+            }
+
             // Handle redraw requests
             if let Event::RedrawRequested(_) = event {
                 let frame = pixels.frame_mut();
-                nes.draw(frame);
+                match ntsc_filter.as_mut() {
+                    Some(filter) => frame
+                        .copy_from_slice(filter.apply(nes.frame_indices(), nes.scanline_phases())),
+                    None if scaler != Scaler::None => {
+                        scaler.apply_into(nes.frame_buffer(), 256, 240, frame)
+                    }
+                    None => nes.draw(frame),
+                }
+                if nes.overlays().any() {
+                    let (width, height) = (buffer_width as usize, buffer_height as usize);
+                    debug_view::blend_overlay(frame, width, height, &nes.overlay());
+                }
+                for viewer in &viewers {
+                    viewer.window.request_redraw();
+                }
 
                 if let Err(err) = pixels.render() {
                     error!("pixels.render() failed: {}", err);
@@ -183,6 +549,39 @@ fn main() -> Result<()> {
                     }
                 }
 
+                if input.key_pressed(VirtualKeyCode::L) {
+                    nes.set_unlimited_sprites(!nes.unlimited_sprites());
+                    info!(
+                        "Sprite limit {}",
+                        if nes.unlimited_sprites() { "off" } else { "on" }
+                    );
+                }
+
+                // F1/F2 hide layers, F3-F5 toggle debug overlays
+                let shown = |hidden: bool| if hidden { "hidden" } else { "shown" };
+                if input.key_pressed(VirtualKeyCode::F1) {
+                    nes.set_hide_background(!nes.hide_background());
+                    info!("Background {}", shown(nes.hide_background()));
+                }
+                if input.key_pressed(VirtualKeyCode::F2) {
+                    nes.set_hide_sprites(!nes.hide_sprites());
+                    info!("Sprites {}", shown(nes.hide_sprites()));
+                }
+                let mut overlays = nes.overlays();
+                if input.key_pressed(VirtualKeyCode::F3) {
+                    overlays.sprite_boxes = !overlays.sprite_boxes;
+                }
+                if input.key_pressed(VirtualKeyCode::F4) {
+                    overlays.sprite_zero_hit = !overlays.sprite_zero_hit;
+                }
+                if input.key_pressed(VirtualKeyCode::F5) {
+                    overlays.mmc3_irq = !overlays.mmc3_irq;
+                }
+                if overlays != nes.overlays() {
+                    info!("Overlays: {:?}", overlays);
+                    nes.set_overlays(overlays);
+                }
+
                 nes.set_joypad_button(JoypadButton::BUTTON_A, input.key_held(VirtualKeyCode::Z));
                 nes.set_joypad_button(JoypadButton::BUTTON_B, input.key_held(VirtualKeyCode::X));
                 nes.set_joypad_button(JoypadButton::SELECT, input.key_held(VirtualKeyCode::RShift));
@@ -193,18 +592,64 @@ fn main() -> Result<()> {
                 nes.set_joypad_button(JoypadButton::RIGHT, input.key_held(VirtualKeyCode::Right));
             }
 
+            // Handle debugger commands
+            if let (Some(dbg), Some(commands)) = (debugger.as_mut(), debug_commands.as_ref()) {
+                loop {
+                    match commands.try_recv() {
+                        Ok(line) => {
+                            let output = dbg.execute_command(&mut nes, &line);
+                            if !output.is_empty() {
+                                println!("{}", output);
+                            }
+                            print_debug_prompt();
+                            window.request_redraw();
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            debug_commands = None;
+                            break;
+                        }
+                    }
+                }
+            }
+            if let (Some(server), Some(dbg)) = (gdb_server.as_mut(), debugger.as_mut()) {
+                if let Err(err) = server.poll(&mut nes, dbg) {
+                    error!("GDB connection error: {}", err);
+                }
+            }
+            let debugger_paused = debugger.as_ref().is_some_and(|dbg| dbg.paused);
+
             // Step emulator for one frame if it's time
-            if last_frame_time.elapsed() >= frame_duration {
-                let mut cycles = 0;
+            if debugger_paused {
+                last_frame_time = Instant::now();
+            } else if last_frame_time.elapsed() >= frame_duration {
+                let frame = nes.frame_count();
                 let mut apu_sum = 0.0;
                 let mut apu_count = 0;
 
-                while cycles < 29781 {
-                    let step_cycles = nes.tick();
-                    cycles += step_cycles;
+                while nes.frame_count() == frame {
+                    let step_cycles = match debugger.as_mut() {
+                        Some(dbg) => {
+                            let (step_cycles, stop) = dbg.run_instruction(&mut nes);
+                            if let Some(reason) = stop {
+                                if let Some(server) = gdb_server.as_mut() {
+                                    if let Err(err) = server.notify_stop(&reason) {
+                                        error!("GDB connection error: {}", err);
+                                    }
+                                }
+                                if debug_commands.is_some() {
+                                    println!("\nStopped: {}\n{}", reason, nes.cpu.trace(&nes.bus));
+                                    print_debug_prompt();
+                                }
+                                break;
+                            }
+                            step_cycles
+                        }
+                        None => nes.tick(),
+                    };
 
                     // Accumulate APU output for averaging (Oversampling)
-                    let current_output = nes.bus.apu.output();
+                    let current_output = nes.bus.audio_output();
                     apu_sum += current_output * step_cycles as f32;
                     apu_count += step_cycles as i32;
 
@@ -0,0 +1,188 @@
+use crate::Nes;
+
+/// Number of already-matching lines shown before a divergence.
+const CONTEXT_LINES: usize = 5;
+
+/// The machine state columns of one nestest-style trace line.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct TraceState {
+    pc: String,
+    bytes: String,
+    registers: Vec<(&'static str, String)>,
+}
+
+/// First point where the emulator disagrees with the reference log.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// 1-based line number in the reference log.
+    pub line: usize,
+    /// Column that differs (e.g. `PC`, `A`, `PPU`, `CYC`).
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+    /// Reference lines leading up to the divergence.
+    pub context: Vec<String>,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Divergence at line {} ({} differs)",
+            self.line, self.field
+        )?;
+        for line in &self.context {
+            writeln!(f, "    {}", line)?;
+        }
+        writeln!(f, "Expected: {}", self.expected)?;
+        write!(f, "Actual:   {}", self.actual)
+    }
+}
+
+fn parse_trace_line(line: &str) -> Option<TraceState> {
+    let pc = line.get(0..4)?.to_string();
+    let bytes = line.get(6..15).unwrap_or("").trim().to_string();
+
+    let regs_start = line.find(" A:")?;
+    let regs = &line[regs_start..];
+    let mut registers = Vec::new();
+    for name in ["A", "X", "Y", "P", "SP"] {
+        let key = format!(" {}:", name);
+        let start = regs.find(&key)? + key.len();
+        let value = regs[start..].split_whitespace().next()?.to_string();
+        registers.push((name, value));
+    }
+    // "PPU:  0, 21" pads with spaces, so normalise it before comparing.
+    if let Some(start) = regs.find("PPU:") {
+        let rest = &regs[start + 4..];
+        let end = rest.find("CYC:").unwrap_or(rest.len());
+        let value: String = rest[..end].chars().filter(|c| !c.is_whitespace()).collect();
+        registers.push(("PPU", value));
+    }
+    if let Some(start) = regs.find("CYC:") {
+        let value = regs[start + 4..].split_whitespace().next()?.to_string();
+        registers.push(("CYC", value));
+    }
+
+    Some(TraceState {
+        pc,
+        bytes,
+        registers,
+    })
+}
+
+/// Name of the first column where `actual` differs from `expected`. Columns
+/// that the reference log does not contain are ignored.
+fn first_difference(expected: &TraceState, actual: &TraceState) -> Option<String> {
+    if expected.pc != actual.pc {
+        return Some("PC".to_string());
+    }
+    if expected.bytes != actual.bytes {
+        return Some("opcode bytes".to_string());
+    }
+    for (name, value) in &expected.registers {
+        let actual_value = actual
+            .registers
+            .iter()
+            .find(|(actual_name, _)| actual_name == name)
+            .map(|(_, value)| value);
+        if actual_value != Some(value) {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Put a NES into the nestest "automation" state: PC=$C000, P=$24, and the
+/// seven reset cycles already elapsed (CYC:7, PPU dot 21).
+pub fn prepare_automation(nes: &mut Nes) {
+    nes.cpu.pc = 0xC000;
+    nes.cpu.st = 0x24;
+    nes.cpu.sp = 0xFD;
+    nes.cpu.cycles = 7;
+    nes.bus.ppu.scanline = 0;
+    nes.bus.ppu.cycle = 21;
+}
+
+/// Step `nes` once per line of `reference` and compare the PC, opcode bytes,
+/// registers, PPU position and cycle count. Returns the number of matching
+/// lines, or the first divergence.
+pub fn compare_with_log(nes: &mut Nes, reference: &str) -> Result<usize, Divergence> {
+    let mut context: Vec<String> = Vec::with_capacity(CONTEXT_LINES);
+    let mut matched = 0;
+
+    for (index, expected_line) in reference.lines().enumerate() {
+        let expected_line = expected_line.trim_end();
+        let Some(expected) = parse_trace_line(expected_line) else {
+            continue;
+        };
+
+        let actual_line = nes.cpu.trace(&nes.bus);
+        let difference = match parse_trace_line(&actual_line) {
+            Some(actual) => first_difference(&expected, &actual),
+            None => Some("trace format".to_string()),
+        };
+        if let Some(field) = difference {
+            return Err(Divergence {
+                line: index + 1,
+                field,
+                expected: expected_line.to_string(),
+                actual: actual_line,
+                context,
+            });
+        }
+
+        if context.len() == CONTEXT_LINES {
+            context.remove(0);
+        }
+        context.push(expected_line.to_string());
+        matched += 1;
+        nes.tick();
+    }
+
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_nrom;
+
+    fn nestest_like_rom() -> Vec<u8> {
+        // $C000: JMP $C005; $C005: LDX #$00; STX $01
+        test_nrom(1, &[0x4C, 0x05, 0xC0, 0xEA, 0xEA, 0xA2, 0x00, 0x86, 0x01])
+    }
+
+    const REFERENCE: &str = "\
+C000  4C 05 C0  JMP $C005                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+C005  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10
+C007  86 01     STX $01 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12
+";
+
+    #[test]
+    fn test_matching_log() {
+        let mut nes = Nes::new_with_rom(&nestest_like_rom()).unwrap();
+        prepare_automation(&mut nes);
+        assert_eq!(compare_with_log(&mut nes, REFERENCE).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_reports_first_divergence() {
+        let mut nes = Nes::new_with_rom(&nestest_like_rom()).unwrap();
+        prepare_automation(&mut nes);
+        let reference = REFERENCE.replace("P:26", "P:A4");
+        let divergence = compare_with_log(&mut nes, &reference).unwrap_err();
+        assert_eq!(divergence.line, 3);
+        assert_eq!(divergence.field, "P");
+        assert_eq!(divergence.context.len(), 2);
+    }
+
+    #[test]
+    fn test_parses_ppu_column() {
+        let state = parse_trace_line(REFERENCE.lines().next().unwrap()).unwrap();
+        assert_eq!(state.pc, "C000");
+        assert_eq!(state.bytes, "4C 05 C0");
+        assert!(state.registers.contains(&("PPU", "0,21".to_string())));
+        assert!(state.registers.contains(&("CYC", "7".to_string())));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::palette::{composite_level, Palette, PaletteParams};
+use crate::region::Region;
+use std::fmt;
+use std::str::FromStr;
+
+/// Output width matching the usual NES NTSC filter output.
+pub const NTSC_WIDTH: usize = 602;
+/// Output width for square-pixel 640x480 displays.
+pub const NTSC_WIDE_WIDTH: usize = 640;
+/// Every scanline is output twice, for roughly a 4:3 picture.
+pub const NTSC_HEIGHT: usize = 480;
+
+/// A dot lasts 8 of the 12 phases of one color subcarrier cycle.
+const SAMPLES_PER_DOT: usize = 8;
+const LINE_SAMPLES: usize = 256 * SAMPLES_PER_DOT;
+/// Samples past each edge of the picture, so filters see the border color.
+/// A multiple of 12, so sample `n` of a line is at phase `phase + n`.
+const PADDING: usize = 24;
+
+/// How the TV receives the signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NtscPreset {
+    /// Luma and chroma share one wire: dot crawl, fringing and artifact colors.
+    Composite,
+    /// Separate luma and chroma: soft color edges without dot crawl.
+    SVideo,
+    /// No signal decoding at all; the palette colors, resampled.
+    Rgb,
+}
+
+impl NtscPreset {
+    /// Low-pass widths for luma and chroma, in samples.
+    fn filter_widths(self) -> (usize, usize) {
+        match self {
+            // A one-cycle luma window only cancels chroma where the color
+            // stays flat; edges and dithering leak into luma and crawl.
+            NtscPreset::Composite => (12, 24),
+            NtscPreset::SVideo => (4, 12),
+            NtscPreset::Rgb => (1, 1),
+        }
+    }
+}
+
+impl fmt::Display for NtscPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            NtscPreset::Composite => "composite",
+            NtscPreset::SVideo => "svideo",
+            NtscPreset::Rgb => "rgb",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for NtscPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "composite" => Ok(NtscPreset::Composite),
+            "svideo" | "s-video" => Ok(NtscPreset::SVideo),
+            "rgb" => Ok(NtscPreset::Rgb),
+            _ => Err(format!(
+                "Unknown NTSC preset '{}' (expected composite, svideo or rgb)",
+                s
+            )),
+        }
+    }
+}
+
+/// Software NTSC decoder: rebuilds each scanline's composite signal from
+/// palette indices and the PPU's subcarrier phase, then decodes it like a
+/// TV would. Runs entirely on the CPU.
+pub struct NtscFilter {
+    pub preset: NtscPreset,
+    /// Hue, saturation, contrast, brightness and gamma of the TV.
+    pub params: PaletteParams,
+    width: usize,
+    /// Colors shown by the RGB preset, which has no signal to decode.
+    palette: Palette,
+    /// Signal level of each color index at each subcarrier phase.
+    levels: Vec<[f32; 12]>,
+    /// Average level of each color index, the luma an S-Video cable carries.
+    lumas: Vec<f32>,
+    /// Running sums of luma, I and Q over a padded scanline.
+    sums: Vec<(f32, f32, f32)>,
+    output: Vec<u8>,
+}
+
+impl NtscFilter {
+    /// `width` is normally `NTSC_WIDTH` or `NTSC_WIDE_WIDTH`.
+    pub fn new(preset: NtscPreset, width: usize) -> Self {
+        let levels: Vec<[f32; 12]> = (0..512u16)
+            .map(|color| std::array::from_fn(|phase| composite_level(color, phase as i32)))
+            .collect();
+        let lumas = levels
+            .iter()
+            .map(|phases| phases.iter().sum::<f32>() / 12.0)
+            .collect();
+        let params = PaletteParams::new(Region::Ntsc);
+        NtscFilter {
+            preset,
+            params,
+            width,
+            palette: Palette::generate(&params),
+            levels,
+            lumas,
+            sums: vec![(0.0, 0.0, 0.0); LINE_SAMPLES + 2 * PADDING + 1],
+            output: vec![0; width * NTSC_HEIGHT * 4],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Use the emulator's active palette (`Nes::palette`) for the RGB
+    /// preset, such as one loaded with `--palette`.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Filter a 256x240 frame of 9-bit palette indices (`Nes::frame_indices`)
+    /// into a `width` x `NTSC_HEIGHT` RGBA image. `phases` holds the
+    /// subcarrier phase at the start of each line (`Nes::scanline_phases`).
+    pub fn apply(&mut self, indices: &[u16], phases: &[u8]) -> &[u8] {
+        let rgb = self.preset == NtscPreset::Rgb;
+        let row_bytes = self.width * 4;
+
+        for (line, row) in indices.chunks_exact(256).take(240).enumerate() {
+            let out = line * 2 * row_bytes;
+            if rgb {
+                for x in 0..self.width {
+                    let color = self.palette.colors[row[x * 256 / self.width] as usize];
+                    put_pixel(&mut self.output, out + x * 4, color);
+                }
+            } else {
+                self.decode_line(row, phases[line], out);
+            }
+            self.output
+                .copy_within(out..out + row_bytes, out + row_bytes);
+        }
+        &self.output
+    }
+
+    fn decode_line(&mut self, row: &[u16], phase: u8, out: usize) {
+        let carrier: [(f32, f32); 12] = std::array::from_fn(|p| self.params.carrier(p as i32));
+        let separate_luma = self.preset == NtscPreset::SVideo;
+
+        let mut total = (0.0, 0.0, 0.0);
+        self.sums[0] = total;
+        for n in 0..LINE_SAMPLES + 2 * PADDING {
+            let dot = (n.saturating_sub(PADDING) / SAMPLES_PER_DOT).min(255);
+            let color = row[dot] as usize;
+            let p = (phase as usize + n) % 12;
+            let level = self.levels[color][p];
+            let (luma, chroma) = if separate_luma {
+                (self.lumas[color], level - self.lumas[color])
+            } else {
+                (level, level)
+            };
+            total.0 += luma;
+            total.1 += chroma * carrier[p].0;
+            total.2 += chroma * carrier[p].1;
+            self.sums[n + 1] = total;
+        }
+
+        let (luma_width, chroma_width) = self.preset.filter_widths();
+        let average = |sums: &[(f32, f32, f32)], center: usize, width: usize| {
+            let (start, end) = (center - width / 2, center + width - width / 2);
+            let scale = 1.0 / width as f32;
+            (
+                (sums[end].0 - sums[start].0) * scale,
+                (sums[end].1 - sums[start].1) * scale,
+                (sums[end].2 - sums[start].2) * scale,
+            )
+        };
+        for x in 0..self.width {
+            let center = PADDING + (2 * x + 1) * LINE_SAMPLES / (2 * self.width);
+            let (y, _, _) = average(&self.sums, center, luma_width);
+            let (_, i, q) = average(&self.sums, center, chroma_width);
+            let rgb = self.params.yiq_to_rgb(y, i, q);
+            put_pixel(&mut self.output, out + x * 4, rgb);
+        }
+    }
+}
+
+fn put_pixel(buffer: &mut [u8], idx: usize, rgb: (u8, u8, u8)) {
+    buffer[idx..idx + 4].copy_from_slice(&[rgb.0, rgb.1, rgb.2, 0xFF]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+        let idx = (y * width + x) * 4;
+        image[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_flat_color_matches_palette() {
+        let expected = Palette::generate(&PaletteParams::new(Region::Ntsc)).colors[0x16];
+        let indices = vec![0x16; 256 * 240];
+        for preset in [NtscPreset::Composite, NtscPreset::SVideo, NtscPreset::Rgb] {
+            let mut filter = NtscFilter::new(preset, NTSC_WIDTH);
+            let image = filter.apply(&indices, &[0; 240]).to_vec();
+            assert_eq!(image.len(), NTSC_WIDTH * NTSC_HEIGHT * 4);
+            let [r, g, b, a] = pixel(&image, NTSC_WIDTH, 300, 200);
+            assert_eq!(a, 0xFF);
+            for (got, want) in [(r, expected.0), (g, expected.1), (b, expected.2)] {
+                assert!(got.abs_diff(want) <= 2, "{}: {:?}", preset, (r, g, b));
+            }
+            // Lines are doubled.
+            assert_eq!(
+                pixel(&image, NTSC_WIDTH, 300, 200),
+                pixel(&image, NTSC_WIDTH, 300, 201)
+            );
+        }
+    }
+
+    #[test]
+    fn test_composite_artifacts_follow_phase() {
+        // One-dot black and white stripes, like dithered waterfalls.
+        let indices: Vec<u16> = (0..256 * 240)
+            .map(|i| if i % 2 == 0 { 0x30 } else { 0x0F })
+            .collect();
+        let run = |preset: NtscPreset, phase: u8| {
+            let mut filter = NtscFilter::new(preset, NTSC_WIDE_WIDTH);
+            filter.apply(&indices, &[phase; 240]).to_vec()
+        };
+
+        // Composite decodes the stripes as color, which crawls with phase.
+        let composite = run(NtscPreset::Composite, 0);
+        let [r, g, b, _] = pixel(&composite, NTSC_WIDE_WIDTH, 320, 0);
+        assert!(r.max(g).max(b) - r.min(g).min(b) > 30, "{:?}", (r, g, b));
+        assert_ne!(composite, run(NtscPreset::Composite, 4));
+
+        // S-Video keeps luma out of chroma and RGB has no signal at all.
+        assert_eq!(run(NtscPreset::Rgb, 0), run(NtscPreset::Rgb, 4));
+        let [r, g, b, _] = pixel(&run(NtscPreset::SVideo, 0), NTSC_WIDE_WIDTH, 320, 0);
+        assert!(r.max(g).max(b) - r.min(g).min(b) < 30, "{:?}", (r, g, b));
+    }
+
+    #[test]
+    fn test_rgb_uses_given_palette() {
+        let mut palette = Palette::default();
+        palette.colors[0x16] = (1, 2, 3);
+        let mut filter = NtscFilter::new(NtscPreset::Rgb, NTSC_WIDTH);
+        filter.set_palette(palette);
+        let image = filter.apply(&vec![0x16; 256 * 240], &[0; 240]);
+        assert_eq!(pixel(image, NTSC_WIDTH, 300, 200), [1, 2, 3, 0xFF]);
+    }
+
+    #[test]
+    fn test_preset_names() {
+        for preset in [NtscPreset::Composite, NtscPreset::SVideo, NtscPreset::Rgb] {
+            assert_eq!(preset.to_string().parse::<NtscPreset>(), Ok(preset));
+        }
+        assert_eq!("S-Video".parse::<NtscPreset>(), Ok(NtscPreset::SVideo));
+        assert!("vga".parse::<NtscPreset>().is_err());
+    }
+}
@@ -1,327 +1,329 @@
-use crate::cpu::AddressingMode;
-use std::collections::HashMap;
+use crate::bus::Bus;
+use crate::cpu::AddressingMode::{self, *};
+use crate::cpu::Cpu;
+
+/// Executes one instruction after its opcode byte has been fetched.
+pub type Handler = fn(&mut Cpu, &mut Bus, AddressingMode);
 
 pub struct OpCode {
     pub code: u8,
     pub name: &'static str,
     pub len: u8,
+    /// Base cycle count, before page-cross and branch penalties.
     pub cycles: u8,
+    /// Whether crossing a page while indexing costs one extra cycle.
+    /// Stores and read-modify-write instructions always pay it, so it is
+    /// already in their base count.
+    pub page_cross: bool,
     pub mode: AddressingMode,
+    pub handler: Handler,
 }
 
-impl OpCode {
-    fn new(code: u8, name: &'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self {
-        OpCode {
-            code,
-            name,
-            len,
-            cycles,
-            mode,
-        }
+const fn op(
+    code: u8,
+    name: &'static str,
+    len: u8,
+    cycles: u8,
+    page_cross: bool,
+    mode: AddressingMode,
+    handler: Handler,
+) -> OpCode {
+    OpCode {
+        code,
+        name,
+        len,
+        cycles,
+        page_cross,
+        mode,
+        handler,
     }
 }
 
-lazy_static::lazy_static! {
-    pub static ref OPCODES_MAP: HashMap<u8, OpCode> = {
-        let mut map = HashMap::new();
-        // Add all 6502 opcodes here. This is a partial list for demonstration.
-        map.insert(0x00, OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NoneAddressing));
-        map.insert(0xAA, OpCode::new(0xAA, "TAX", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xE8, OpCode::new(0xE8, "INX", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xC8, OpCode::new(0xC8, "INY", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xCA, OpCode::new(0xCA, "DEX", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x88, OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xA8, OpCode::new(0xA8, "TAY", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x98, OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x8A, OpCode::new(0x8A, "TXA", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x9A, OpCode::new(0x9A, "TXS", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xBA, OpCode::new(0xBA, "TSX", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x48, OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NoneAddressing));
-        map.insert(0x68, OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NoneAddressing));
-        map.insert(0x08, OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NoneAddressing));
-        map.insert(0x28, OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NoneAddressing));
-
-        map.insert(0xA9, OpCode::new(0xA9, "LDA", 2, 2, AddressingMode::Immediate));
-        map.insert(0xA5, OpCode::new(0xA5, "LDA", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xB5, OpCode::new(0xB5, "LDA", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0xAD, OpCode::new(0xAD, "LDA", 3, 4, AddressingMode::Absolute));
-        map.insert(0xBD, OpCode::new(0xBD, "LDA", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0xB9, OpCode::new(0xB9, "LDA", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0xA1, OpCode::new(0xA1, "LDA", 2, 6, AddressingMode::IndirectX));
-        map.insert(0xB1, OpCode::new(0xB1, "LDA", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0xA2, OpCode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate));
-        map.insert(0xA6, OpCode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xB6, OpCode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPageY));
-        map.insert(0xAE, OpCode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute));
-        map.insert(0xBE, OpCode::new(0xBE, "LDX", 3, 4, AddressingMode::AbsoluteY));
-
-        map.insert(0xA0, OpCode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate));
-        map.insert(0xA4, OpCode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xB4, OpCode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0xAC, OpCode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute));
-        map.insert(0xBC, OpCode::new(0xBC, "LDY", 3, 4, AddressingMode::AbsoluteX));
-
-        map.insert(0x85, OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x95, OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x8D, OpCode::new(0x8D, "STA", 3, 4, AddressingMode::Absolute));
-        map.insert(0x9D, OpCode::new(0x9D, "STA", 3, 5, AddressingMode::AbsoluteX));
-        map.insert(0x99, OpCode::new(0x99, "STA", 3, 5, AddressingMode::AbsoluteY));
-        map.insert(0x81, OpCode::new(0x81, "STA", 2, 6, AddressingMode::IndirectX));
-        map.insert(0x91, OpCode::new(0x91, "STA", 2, 6, AddressingMode::IndirectY));
-
-        map.insert(0x86, OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x96, OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPageY));
-        map.insert(0x8E, OpCode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute));
-
-        map.insert(0x84, OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x94, OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x8C, OpCode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute));
-
-
-        map.insert(0x69, OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate));
-        map.insert(0x65, OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x75, OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x6D, OpCode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute));
-        map.insert(0x7D, OpCode::new(0x7D, "ADC", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x79, OpCode::new(0x79, "ADC", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0x61, OpCode::new(0x61, "ADC", 2, 6, AddressingMode::IndirectX));
-        map.insert(0x71, OpCode::new(0x71, "ADC", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0xE9, OpCode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate));
-        map.insert(0xE5, OpCode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xF5, OpCode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0xED, OpCode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute));
-        map.insert(0xFD, OpCode::new(0xFD, "SBC", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0xF9, OpCode::new(0xF9, "SBC", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0xE1, OpCode::new(0xE1, "SBC", 2, 6, AddressingMode::IndirectX));
-        map.insert(0xF1, OpCode::new(0xF1, "SBC", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0x29, OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate));
-        map.insert(0x25, OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x35, OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x2D, OpCode::new(0x2D, "AND", 3, 4, AddressingMode::Absolute));
-        map.insert(0x3D, OpCode::new(0x3D, "AND", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x39, OpCode::new(0x39, "AND", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0x21, OpCode::new(0x21, "AND", 2, 6, AddressingMode::IndirectX));
-        map.insert(0x31, OpCode::new(0x31, "AND", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0x49, OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate));
-        map.insert(0x45, OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x55, OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x4D, OpCode::new(0x4D, "EOR", 3, 4, AddressingMode::Absolute));
-        map.insert(0x5D, OpCode::new(0x5D, "EOR", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x59, OpCode::new(0x59, "EOR", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0x41, OpCode::new(0x41, "EOR", 2, 6, AddressingMode::IndirectX));
-        map.insert(0x51, OpCode::new(0x51, "EOR", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0x09, OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate));
-        map.insert(0x05, OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x15, OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x0D, OpCode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute));
-        map.insert(0x1D, OpCode::new(0x1D, "ORA", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x19, OpCode::new(0x19, "ORA", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0x01, OpCode::new(0x01, "ORA", 2, 6, AddressingMode::IndirectX));
-        map.insert(0x11, OpCode::new(0x11, "ORA", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0x24, OpCode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x2C, OpCode::new(0x2C, "BIT", 3, 4, AddressingMode::Absolute));
-
-        map.insert(0x0A, OpCode::new(0x0A, "ASL", 1, 2, AddressingMode::Accumulator));
-        map.insert(0x06, OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x16, OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x0E, OpCode::new(0x0E, "ASL", 3, 6, AddressingMode::Absolute));
-        map.insert(0x1E, OpCode::new(0x1E, "ASL", 3, 7, AddressingMode::AbsoluteX));
-
-        map.insert(0x4A, OpCode::new(0x4A, "LSR", 1, 2, AddressingMode::Accumulator));
-        map.insert(0x46, OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x56, OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x4E, OpCode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute));
-        map.insert(0x5E, OpCode::new(0x5E, "LSR", 3, 7, AddressingMode::AbsoluteX));
-
-        map.insert(0x2A, OpCode::new(0x2A, "ROL", 1, 2, AddressingMode::Accumulator));
-        map.insert(0x26, OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x36, OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x2E, OpCode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute));
-        map.insert(0x3E, OpCode::new(0x3E, "ROL", 3, 7, AddressingMode::AbsoluteX));
-
-        map.insert(0x6A, OpCode::new(0x6A, "ROR", 1, 2, AddressingMode::Accumulator));
-        map.insert(0x66, OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x76, OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x6E, OpCode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute));
-        map.insert(0x7E, OpCode::new(0x7E, "ROR", 3, 7, AddressingMode::AbsoluteX));
-
-        map.insert(0xE6, OpCode::new(0xE6, "INC", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0xF6, OpCode::new(0xF6, "INC", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0xEE, OpCode::new(0xEE, "INC", 3, 6, AddressingMode::Absolute));
-        map.insert(0xFE, OpCode::new(0xFE, "INC", 3, 7, AddressingMode::AbsoluteX));
-
-        map.insert(0xC6, OpCode::new(0xC6, "DEC", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0xD6, OpCode::new(0xD6, "DEC", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0xCE, OpCode::new(0xCE, "DEC", 3, 6, AddressingMode::Absolute));
-        map.insert(0xDE, OpCode::new(0xDE, "DEC", 3, 7, AddressingMode::AbsoluteX));
-        
-        map.insert(0xC9, OpCode::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate));
-        map.insert(0xC5, OpCode::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xD5, OpCode::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0xCD, OpCode::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute));
-        map.insert(0xDD, OpCode::new(0xDD, "CMP", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0xD9, OpCode::new(0xD9, "CMP", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0xC1, OpCode::new(0xC1, "CMP", 2, 6, AddressingMode::IndirectX));
-        map.insert(0xD1, OpCode::new(0xD1, "CMP", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0xE0, OpCode::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate));
-        map.insert(0xE4, OpCode::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xEC, OpCode::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute));
-
-        map.insert(0xC0, OpCode::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate));
-        map.insert(0xC4, OpCode::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xCC, OpCode::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute));
-
-        map.insert(0x4C, OpCode::new(0x4C, "JMP", 3, 3, AddressingMode::Absolute));
-        map.insert(0x6C, OpCode::new(0x6C, "JMP", 3, 5, AddressingMode::Indirect));
-
-        map.insert(0x20, OpCode::new(0x20, "JSR", 3, 6, AddressingMode::Absolute));
-        map.insert(0x60, OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NoneAddressing));
-
-        map.insert(0x40, OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NoneAddressing));
-
-        map.insert(0xD8, OpCode::new(0xD8, "CLD", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x58, OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xB8, OpCode::new(0xB8, "CLV", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x18, OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x38, OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x78, OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xF8, OpCode::new(0xF8, "SED", 1, 2, AddressingMode::NoneAddressing));
-
-        map.insert(0xEA, OpCode::new(0xEA, "NOP", 1, 2, AddressingMode::NoneAddressing));
-
-        map.insert(0x10, OpCode::new(0x10, "BPL", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0x30, OpCode::new(0x30, "BMI", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0x50, OpCode::new(0x50, "BVC", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0x70, OpCode::new(0x70, "BVS", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0x90, OpCode::new(0x90, "BCC", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0xB0, OpCode::new(0xB0, "BCS", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0xD0, OpCode::new(0xD0, "BNE", 2, 2, AddressingMode::NoneAddressing));
-        map.insert(0xF0, OpCode::new(0xF0, "BEQ", 2, 2, AddressingMode::NoneAddressing));
-        
-        // Unofficial Opcodes
-        map.insert(0x04, OpCode::new(0x04, "*NOP", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x14, OpCode::new(0x14, "*NOP", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x34, OpCode::new(0x34, "*NOP", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x44, OpCode::new(0x44, "*NOP", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x54, OpCode::new(0x54, "*NOP", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x64, OpCode::new(0x64, "*NOP", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x74, OpCode::new(0x74, "*NOP", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0x80, OpCode::new(0x80, "*NOP", 2, 2, AddressingMode::Immediate));
-        map.insert(0x82, OpCode::new(0x82, "*NOP", 2, 2, AddressingMode::Immediate));
-        map.insert(0x89, OpCode::new(0x89, "*NOP", 2, 2, AddressingMode::Immediate));
-        map.insert(0xC2, OpCode::new(0xC2, "*NOP", 2, 2, AddressingMode::Immediate));
-        map.insert(0xD4, OpCode::new(0xD4, "*NOP", 2, 4, AddressingMode::ZeroPageX));
-        map.insert(0xE2, OpCode::new(0xE2, "*NOP", 2, 2, AddressingMode::Immediate));
-        map.insert(0xF4, OpCode::new(0xF4, "*NOP", 2, 4, AddressingMode::ZeroPageX));
-
-        map.insert(0x0C, OpCode::new(0x0C, "*NOP", 3, 4, AddressingMode::Absolute));
-        map.insert(0x1C, OpCode::new(0x1C, "*NOP", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x3C, OpCode::new(0x3C, "*NOP", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x5C, OpCode::new(0x5C, "*NOP", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0x7C, OpCode::new(0x7C, "*NOP", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0xDC, OpCode::new(0xDC, "*NOP", 3, 4, AddressingMode::AbsoluteX));
-        map.insert(0xFC, OpCode::new(0xFC, "*NOP", 3, 4, AddressingMode::AbsoluteX));
-
-        map.insert(0x02, OpCode::new(0x02, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x12, OpCode::new(0x12, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x22, OpCode::new(0x22, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x32, OpCode::new(0x32, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x42, OpCode::new(0x42, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x52, OpCode::new(0x52, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x62, OpCode::new(0x62, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x72, OpCode::new(0x72, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x92, OpCode::new(0x92, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xB2, OpCode::new(0xB2, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xD2, OpCode::new(0xD2, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xF2, OpCode::new(0xF2, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-
-        map.insert(0x1A, OpCode::new(0x1A, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x3A, OpCode::new(0x3A, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x5A, OpCode::new(0x5A, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0x7A, OpCode::new(0x7A, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xDA, OpCode::new(0xDA, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-        map.insert(0xFA, OpCode::new(0xFA, "*NOP", 1, 2, AddressingMode::NoneAddressing));
-
-        map.insert(0x07, OpCode::new(0x07, "*SLO", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x17, OpCode::new(0x17, "*SLO", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x0F, OpCode::new(0x0F, "*SLO", 3, 6, AddressingMode::Absolute));
-        map.insert(0x1F, OpCode::new(0x1F, "*SLO", 3, 7, AddressingMode::AbsoluteX));
-        map.insert(0x1B, OpCode::new(0x1B, "*SLO", 3, 7, AddressingMode::AbsoluteY));
-        map.insert(0x03, OpCode::new(0x03, "*SLO", 2, 8, AddressingMode::IndirectX));
-        map.insert(0x13, OpCode::new(0x13, "*SLO", 2, 8, AddressingMode::IndirectY));
-
-        map.insert(0x27, OpCode::new(0x27, "*RLA", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x37, OpCode::new(0x37, "*RLA", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x2F, OpCode::new(0x2F, "*RLA", 3, 6, AddressingMode::Absolute));
-        map.insert(0x3F, OpCode::new(0x3F, "*RLA", 3, 7, AddressingMode::AbsoluteX));
-        map.insert(0x3B, OpCode::new(0x3B, "*RLA", 3, 7, AddressingMode::AbsoluteY));
-        map.insert(0x23, OpCode::new(0x23, "*RLA", 2, 8, AddressingMode::IndirectX));
-        map.insert(0x33, OpCode::new(0x33, "*RLA", 2, 8, AddressingMode::IndirectY));
-
-        map.insert(0x47, OpCode::new(0x47, "*SRE", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x57, OpCode::new(0x57, "*SRE", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x4F, OpCode::new(0x4F, "*SRE", 3, 6, AddressingMode::Absolute));
-        map.insert(0x5F, OpCode::new(0x5F, "*SRE", 3, 7, AddressingMode::AbsoluteX));
-        map.insert(0x5B, OpCode::new(0x5B, "*SRE", 3, 7, AddressingMode::AbsoluteY));
-        map.insert(0x43, OpCode::new(0x43, "*SRE", 2, 8, AddressingMode::IndirectX));
-        map.insert(0x53, OpCode::new(0x53, "*SRE", 2, 8, AddressingMode::IndirectY));
-
-        map.insert(0x67, OpCode::new(0x67, "*RRA", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0x77, OpCode::new(0x77, "*RRA", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0x6F, OpCode::new(0x6F, "*RRA", 3, 6, AddressingMode::Absolute));
-        map.insert(0x7F, OpCode::new(0x7F, "*RRA", 3, 7, AddressingMode::AbsoluteX));
-        map.insert(0x7B, OpCode::new(0x7B, "*RRA", 3, 7, AddressingMode::AbsoluteY));
-        map.insert(0x63, OpCode::new(0x63, "*RRA", 2, 8, AddressingMode::IndirectX));
-        map.insert(0x73, OpCode::new(0x73, "*RRA", 2, 8, AddressingMode::IndirectY));
-
-        map.insert(0x87, OpCode::new(0x87, "*SAX", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0x97, OpCode::new(0x97, "*SAX", 2, 4, AddressingMode::ZeroPageY));
-        map.insert(0x8F, OpCode::new(0x8F, "*SAX", 3, 4, AddressingMode::Absolute));
-        map.insert(0x83, OpCode::new(0x83, "*SAX", 2, 6, AddressingMode::IndirectX));
-
-        map.insert(0xA7, OpCode::new(0xA7, "*LAX", 2, 3, AddressingMode::ZeroPage));
-        map.insert(0xB7, OpCode::new(0xB7, "*LAX", 2, 4, AddressingMode::ZeroPageY));
-        map.insert(0xAF, OpCode::new(0xAF, "*LAX", 3, 4, AddressingMode::Absolute));
-        map.insert(0xBF, OpCode::new(0xBF, "*LAX", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0xA3, OpCode::new(0xA3, "*LAX", 2, 6, AddressingMode::IndirectX));
-        map.insert(0xB3, OpCode::new(0xB3, "*LAX", 2, 5, AddressingMode::IndirectY));
-
-        map.insert(0xC7, OpCode::new(0xC7, "*DCP", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0xD7, OpCode::new(0xD7, "*DCP", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0xCF, OpCode::new(0xCF, "*DCP", 3, 6, AddressingMode::Absolute));
-        map.insert(0xDF, OpCode::new(0xDF, "*DCP", 3, 7, AddressingMode::AbsoluteX));
-        map.insert(0xDB, OpCode::new(0xDB, "*DCP", 3, 7, AddressingMode::AbsoluteY));
-        map.insert(0xC3, OpCode::new(0xC3, "*DCP", 2, 8, AddressingMode::IndirectX));
-        map.insert(0xD3, OpCode::new(0xD3, "*DCP", 2, 8, AddressingMode::IndirectY));
+/// Every 6502 opcode, indexed by its byte. Unofficial opcodes are prefixed
+/// with `*` as in nestest logs. The twelve KIL/JAM opcodes are listed with
+/// 2 cycles so a jammed CPU still advances the clock.
+#[rustfmt::skip]
+pub static OPCODES: [OpCode; 256] = [
+    op(0x00, "BRK", 1, 7, false, NoneAddressing, |cpu, bus, _| cpu.brk(bus)),
+    op(0x01, "ORA", 2, 6, false, IndirectX, Cpu::ora),
+    op(0x02, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x03, "*SLO", 2, 8, false, IndirectX, Cpu::slo),
+    op(0x04, "*NOP", 2, 3, false, ZeroPage, Cpu::nop),
+    op(0x05, "ORA", 2, 3, false, ZeroPage, Cpu::ora),
+    op(0x06, "ASL", 2, 5, false, ZeroPage, Cpu::asl),
+    op(0x07, "*SLO", 2, 5, false, ZeroPage, Cpu::slo),
+    op(0x08, "PHP", 1, 3, false, NoneAddressing, |cpu, bus, _| cpu.php(bus)),
+    op(0x09, "ORA", 2, 2, false, Immediate, Cpu::ora),
+    op(0x0A, "ASL", 1, 2, false, Accumulator, |cpu, _, _| cpu.asl_acc()),
+    op(0x0B, "*ANC", 2, 2, false, Immediate, |cpu, bus, _| cpu.anc(bus)),
+    op(0x0C, "*NOP", 3, 4, false, Absolute, Cpu::nop),
+    op(0x0D, "ORA", 3, 4, false, Absolute, Cpu::ora),
+    op(0x0E, "ASL", 3, 6, false, Absolute, Cpu::asl),
+    op(0x0F, "*SLO", 3, 6, false, Absolute, Cpu::slo),
+    op(0x10, "BPL", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bpl(bus)),
+    op(0x11, "ORA", 2, 5, true, IndirectY, Cpu::ora),
+    op(0x12, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x13, "*SLO", 2, 8, false, IndirectY, Cpu::slo),
+    op(0x14, "*NOP", 2, 4, false, ZeroPageX, Cpu::nop),
+    op(0x15, "ORA", 2, 4, false, ZeroPageX, Cpu::ora),
+    op(0x16, "ASL", 2, 6, false, ZeroPageX, Cpu::asl),
+    op(0x17, "*SLO", 2, 6, false, ZeroPageX, Cpu::slo),
+    op(0x18, "CLC", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.clc()),
+    op(0x19, "ORA", 3, 4, true, AbsoluteY, Cpu::ora),
+    op(0x1A, "*NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0x1B, "*SLO", 3, 7, false, AbsoluteY, Cpu::slo),
+    op(0x1C, "*NOP", 3, 4, true, AbsoluteX, Cpu::nop),
+    op(0x1D, "ORA", 3, 4, true, AbsoluteX, Cpu::ora),
+    op(0x1E, "ASL", 3, 7, false, AbsoluteX, Cpu::asl),
+    op(0x1F, "*SLO", 3, 7, false, AbsoluteX, Cpu::slo),
+    op(0x20, "JSR", 3, 6, false, Absolute, |cpu, bus, _| cpu.jsr(bus)),
+    op(0x21, "AND", 2, 6, false, IndirectX, Cpu::and),
+    op(0x22, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x23, "*RLA", 2, 8, false, IndirectX, Cpu::rla),
+    op(0x24, "BIT", 2, 3, false, ZeroPage, Cpu::bit),
+    op(0x25, "AND", 2, 3, false, ZeroPage, Cpu::and),
+    op(0x26, "ROL", 2, 5, false, ZeroPage, Cpu::rol),
+    op(0x27, "*RLA", 2, 5, false, ZeroPage, Cpu::rla),
+    op(0x28, "PLP", 1, 4, false, NoneAddressing, |cpu, bus, _| cpu.plp(bus)),
+    op(0x29, "AND", 2, 2, false, Immediate, Cpu::and),
+    op(0x2A, "ROL", 1, 2, false, Accumulator, |cpu, _, _| cpu.rol_acc()),
+    op(0x2B, "*ANC", 2, 2, false, Immediate, |cpu, bus, _| cpu.anc(bus)),
+    op(0x2C, "BIT", 3, 4, false, Absolute, Cpu::bit),
+    op(0x2D, "AND", 3, 4, false, Absolute, Cpu::and),
+    op(0x2E, "ROL", 3, 6, false, Absolute, Cpu::rol),
+    op(0x2F, "*RLA", 3, 6, false, Absolute, Cpu::rla),
+    op(0x30, "BMI", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bmi(bus)),
+    op(0x31, "AND", 2, 5, true, IndirectY, Cpu::and),
+    op(0x32, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x33, "*RLA", 2, 8, false, IndirectY, Cpu::rla),
+    op(0x34, "*NOP", 2, 4, false, ZeroPageX, Cpu::nop),
+    op(0x35, "AND", 2, 4, false, ZeroPageX, Cpu::and),
+    op(0x36, "ROL", 2, 6, false, ZeroPageX, Cpu::rol),
+    op(0x37, "*RLA", 2, 6, false, ZeroPageX, Cpu::rla),
+    op(0x38, "SEC", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.sec()),
+    op(0x39, "AND", 3, 4, true, AbsoluteY, Cpu::and),
+    op(0x3A, "*NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0x3B, "*RLA", 3, 7, false, AbsoluteY, Cpu::rla),
+    op(0x3C, "*NOP", 3, 4, true, AbsoluteX, Cpu::nop),
+    op(0x3D, "AND", 3, 4, true, AbsoluteX, Cpu::and),
+    op(0x3E, "ROL", 3, 7, false, AbsoluteX, Cpu::rol),
+    op(0x3F, "*RLA", 3, 7, false, AbsoluteX, Cpu::rla),
+    op(0x40, "RTI", 1, 6, false, NoneAddressing, |cpu, bus, _| cpu.rti(bus)),
+    op(0x41, "EOR", 2, 6, false, IndirectX, Cpu::eor),
+    op(0x42, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x43, "*SRE", 2, 8, false, IndirectX, Cpu::sre),
+    op(0x44, "*NOP", 2, 3, false, ZeroPage, Cpu::nop),
+    op(0x45, "EOR", 2, 3, false, ZeroPage, Cpu::eor),
+    op(0x46, "LSR", 2, 5, false, ZeroPage, Cpu::lsr),
+    op(0x47, "*SRE", 2, 5, false, ZeroPage, Cpu::sre),
+    op(0x48, "PHA", 1, 3, false, NoneAddressing, |cpu, bus, _| cpu.pha(bus)),
+    op(0x49, "EOR", 2, 2, false, Immediate, Cpu::eor),
+    op(0x4A, "LSR", 1, 2, false, Accumulator, |cpu, _, _| cpu.lsr_acc()),
+    op(0x4B, "*ALR", 2, 2, false, Immediate, |cpu, bus, _| cpu.alr(bus)),
+    op(0x4C, "JMP", 3, 3, false, Absolute, Cpu::jmp),
+    op(0x4D, "EOR", 3, 4, false, Absolute, Cpu::eor),
+    op(0x4E, "LSR", 3, 6, false, Absolute, Cpu::lsr),
+    op(0x4F, "*SRE", 3, 6, false, Absolute, Cpu::sre),
+    op(0x50, "BVC", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bvc(bus)),
+    op(0x51, "EOR", 2, 5, true, IndirectY, Cpu::eor),
+    op(0x52, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x53, "*SRE", 2, 8, false, IndirectY, Cpu::sre),
+    op(0x54, "*NOP", 2, 4, false, ZeroPageX, Cpu::nop),
+    op(0x55, "EOR", 2, 4, false, ZeroPageX, Cpu::eor),
+    op(0x56, "LSR", 2, 6, false, ZeroPageX, Cpu::lsr),
+    op(0x57, "*SRE", 2, 6, false, ZeroPageX, Cpu::sre),
+    op(0x58, "CLI", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.cli()),
+    op(0x59, "EOR", 3, 4, true, AbsoluteY, Cpu::eor),
+    op(0x5A, "*NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0x5B, "*SRE", 3, 7, false, AbsoluteY, Cpu::sre),
+    op(0x5C, "*NOP", 3, 4, true, AbsoluteX, Cpu::nop),
+    op(0x5D, "EOR", 3, 4, true, AbsoluteX, Cpu::eor),
+    op(0x5E, "LSR", 3, 7, false, AbsoluteX, Cpu::lsr),
+    op(0x5F, "*SRE", 3, 7, false, AbsoluteX, Cpu::sre),
+    op(0x60, "RTS", 1, 6, false, NoneAddressing, |cpu, bus, _| cpu.rts(bus)),
+    op(0x61, "ADC", 2, 6, false, IndirectX, Cpu::adc),
+    op(0x62, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x63, "*RRA", 2, 8, false, IndirectX, Cpu::rra),
+    op(0x64, "*NOP", 2, 3, false, ZeroPage, Cpu::nop),
+    op(0x65, "ADC", 2, 3, false, ZeroPage, Cpu::adc),
+    op(0x66, "ROR", 2, 5, false, ZeroPage, Cpu::ror),
+    op(0x67, "*RRA", 2, 5, false, ZeroPage, Cpu::rra),
+    op(0x68, "PLA", 1, 4, false, NoneAddressing, |cpu, bus, _| cpu.pla(bus)),
+    op(0x69, "ADC", 2, 2, false, Immediate, Cpu::adc),
+    op(0x6A, "ROR", 1, 2, false, Accumulator, |cpu, _, _| cpu.ror_acc()),
+    op(0x6B, "*ARR", 2, 2, false, Immediate, |cpu, bus, _| cpu.arr(bus)),
+    op(0x6C, "JMP", 3, 5, false, Indirect, Cpu::jmp),
+    op(0x6D, "ADC", 3, 4, false, Absolute, Cpu::adc),
+    op(0x6E, "ROR", 3, 6, false, Absolute, Cpu::ror),
+    op(0x6F, "*RRA", 3, 6, false, Absolute, Cpu::rra),
+    op(0x70, "BVS", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bvs(bus)),
+    op(0x71, "ADC", 2, 5, true, IndirectY, Cpu::adc),
+    op(0x72, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x73, "*RRA", 2, 8, false, IndirectY, Cpu::rra),
+    op(0x74, "*NOP", 2, 4, false, ZeroPageX, Cpu::nop),
+    op(0x75, "ADC", 2, 4, false, ZeroPageX, Cpu::adc),
+    op(0x76, "ROR", 2, 6, false, ZeroPageX, Cpu::ror),
+    op(0x77, "*RRA", 2, 6, false, ZeroPageX, Cpu::rra),
+    op(0x78, "SEI", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.sei()),
+    op(0x79, "ADC", 3, 4, true, AbsoluteY, Cpu::adc),
+    op(0x7A, "*NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0x7B, "*RRA", 3, 7, false, AbsoluteY, Cpu::rra),
+    op(0x7C, "*NOP", 3, 4, true, AbsoluteX, Cpu::nop),
+    op(0x7D, "ADC", 3, 4, true, AbsoluteX, Cpu::adc),
+    op(0x7E, "ROR", 3, 7, false, AbsoluteX, Cpu::ror),
+    op(0x7F, "*RRA", 3, 7, false, AbsoluteX, Cpu::rra),
+    op(0x80, "*NOP", 2, 2, false, Immediate, Cpu::nop),
+    op(0x81, "STA", 2, 6, false, IndirectX, Cpu::sta),
+    op(0x82, "*NOP", 2, 2, false, Immediate, Cpu::nop),
+    op(0x83, "*SAX", 2, 6, false, IndirectX, Cpu::sax),
+    op(0x84, "STY", 2, 3, false, ZeroPage, Cpu::sty),
+    op(0x85, "STA", 2, 3, false, ZeroPage, Cpu::sta),
+    op(0x86, "STX", 2, 3, false, ZeroPage, Cpu::stx),
+    op(0x87, "*SAX", 2, 3, false, ZeroPage, Cpu::sax),
+    op(0x88, "DEY", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.dey()),
+    op(0x89, "*NOP", 2, 2, false, Immediate, Cpu::nop),
+    op(0x8A, "TXA", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.txa()),
+    op(0x8B, "*XAA", 2, 2, false, Immediate, |cpu, bus, _| cpu.xaa(bus)),
+    op(0x8C, "STY", 3, 4, false, Absolute, Cpu::sty),
+    op(0x8D, "STA", 3, 4, false, Absolute, Cpu::sta),
+    op(0x8E, "STX", 3, 4, false, Absolute, Cpu::stx),
+    op(0x8F, "*SAX", 3, 4, false, Absolute, Cpu::sax),
+    op(0x90, "BCC", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bcc(bus)),
+    op(0x91, "STA", 2, 6, false, IndirectY, Cpu::sta),
+    op(0x92, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0x93, "*AHX", 2, 6, false, IndirectY, Cpu::ahx),
+    op(0x94, "STY", 2, 4, false, ZeroPageX, Cpu::sty),
+    op(0x95, "STA", 2, 4, false, ZeroPageX, Cpu::sta),
+    op(0x96, "STX", 2, 4, false, ZeroPageY, Cpu::stx),
+    op(0x97, "*SAX", 2, 4, false, ZeroPageY, Cpu::sax),
+    op(0x98, "TYA", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.tya()),
+    op(0x99, "STA", 3, 5, false, AbsoluteY, Cpu::sta),
+    op(0x9A, "TXS", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.txs()),
+    op(0x9B, "*TAS", 3, 5, false, AbsoluteY, Cpu::tas),
+    op(0x9C, "*SHY", 3, 5, false, AbsoluteX, Cpu::shy),
+    op(0x9D, "STA", 3, 5, false, AbsoluteX, Cpu::sta),
+    op(0x9E, "*SHX", 3, 5, false, AbsoluteY, Cpu::shx),
+    op(0x9F, "*AHX", 3, 5, false, AbsoluteY, Cpu::ahx),
+    op(0xA0, "LDY", 2, 2, false, Immediate, Cpu::ldy),
+    op(0xA1, "LDA", 2, 6, false, IndirectX, Cpu::lda),
+    op(0xA2, "LDX", 2, 2, false, Immediate, Cpu::ldx),
+    op(0xA3, "*LAX", 2, 6, false, IndirectX, Cpu::lax),
+    op(0xA4, "LDY", 2, 3, false, ZeroPage, Cpu::ldy),
+    op(0xA5, "LDA", 2, 3, false, ZeroPage, Cpu::lda),
+    op(0xA6, "LDX", 2, 3, false, ZeroPage, Cpu::ldx),
+    op(0xA7, "*LAX", 2, 3, false, ZeroPage, Cpu::lax),
+    op(0xA8, "TAY", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.tay()),
+    op(0xA9, "LDA", 2, 2, false, Immediate, Cpu::lda),
+    op(0xAA, "TAX", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.tax()),
+    op(0xAB, "*LXA", 2, 2, false, Immediate, Cpu::lxa),
+    op(0xAC, "LDY", 3, 4, false, Absolute, Cpu::ldy),
+    op(0xAD, "LDA", 3, 4, false, Absolute, Cpu::lda),
+    op(0xAE, "LDX", 3, 4, false, Absolute, Cpu::ldx),
+    op(0xAF, "*LAX", 3, 4, false, Absolute, Cpu::lax),
+    op(0xB0, "BCS", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bcs(bus)),
+    op(0xB1, "LDA", 2, 5, true, IndirectY, Cpu::lda),
+    op(0xB2, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0xB3, "*LAX", 2, 5, true, IndirectY, Cpu::lax),
+    op(0xB4, "LDY", 2, 4, false, ZeroPageX, Cpu::ldy),
+    op(0xB5, "LDA", 2, 4, false, ZeroPageX, Cpu::lda),
+    op(0xB6, "LDX", 2, 4, false, ZeroPageY, Cpu::ldx),
+    op(0xB7, "*LAX", 2, 4, false, ZeroPageY, Cpu::lax),
+    op(0xB8, "CLV", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.clv()),
+    op(0xB9, "LDA", 3, 4, true, AbsoluteY, Cpu::lda),
+    op(0xBA, "TSX", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.tsx()),
+    op(0xBB, "*LAS", 3, 4, true, AbsoluteY, |cpu, bus, _| cpu.las(bus)),
+    op(0xBC, "LDY", 3, 4, true, AbsoluteX, Cpu::ldy),
+    op(0xBD, "LDA", 3, 4, true, AbsoluteX, Cpu::lda),
+    op(0xBE, "LDX", 3, 4, true, AbsoluteY, Cpu::ldx),
+    op(0xBF, "*LAX", 3, 4, true, AbsoluteY, Cpu::lax),
+    op(0xC0, "CPY", 2, 2, false, Immediate, Cpu::cpy),
+    op(0xC1, "CMP", 2, 6, false, IndirectX, Cpu::cmp),
+    op(0xC2, "*NOP", 2, 2, false, Immediate, Cpu::nop),
+    op(0xC3, "*DCP", 2, 8, false, IndirectX, Cpu::dcp),
+    op(0xC4, "CPY", 2, 3, false, ZeroPage, Cpu::cpy),
+    op(0xC5, "CMP", 2, 3, false, ZeroPage, Cpu::cmp),
+    op(0xC6, "DEC", 2, 5, false, ZeroPage, Cpu::dec),
+    op(0xC7, "*DCP", 2, 5, false, ZeroPage, Cpu::dcp),
+    op(0xC8, "INY", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.iny()),
+    op(0xC9, "CMP", 2, 2, false, Immediate, Cpu::cmp),
+    op(0xCA, "DEX", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.dex()),
+    op(0xCB, "*AXS", 2, 2, false, Immediate, |cpu, bus, _| cpu.axs(bus)),
+    op(0xCC, "CPY", 3, 4, false, Absolute, Cpu::cpy),
+    op(0xCD, "CMP", 3, 4, false, Absolute, Cpu::cmp),
+    op(0xCE, "DEC", 3, 6, false, Absolute, Cpu::dec),
+    op(0xCF, "*DCP", 3, 6, false, Absolute, Cpu::dcp),
+    op(0xD0, "BNE", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.bne(bus)),
+    op(0xD1, "CMP", 2, 5, true, IndirectY, Cpu::cmp),
+    op(0xD2, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0xD3, "*DCP", 2, 8, false, IndirectY, Cpu::dcp),
+    op(0xD4, "*NOP", 2, 4, false, ZeroPageX, Cpu::nop),
+    op(0xD5, "CMP", 2, 4, false, ZeroPageX, Cpu::cmp),
+    op(0xD6, "DEC", 2, 6, false, ZeroPageX, Cpu::dec),
+    op(0xD7, "*DCP", 2, 6, false, ZeroPageX, Cpu::dcp),
+    op(0xD8, "CLD", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.cld()),
+    op(0xD9, "CMP", 3, 4, true, AbsoluteY, Cpu::cmp),
+    op(0xDA, "*NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0xDB, "*DCP", 3, 7, false, AbsoluteY, Cpu::dcp),
+    op(0xDC, "*NOP", 3, 4, true, AbsoluteX, Cpu::nop),
+    op(0xDD, "CMP", 3, 4, true, AbsoluteX, Cpu::cmp),
+    op(0xDE, "DEC", 3, 7, false, AbsoluteX, Cpu::dec),
+    op(0xDF, "*DCP", 3, 7, false, AbsoluteX, Cpu::dcp),
+    op(0xE0, "CPX", 2, 2, false, Immediate, Cpu::cpx),
+    op(0xE1, "SBC", 2, 6, false, IndirectX, Cpu::sbc),
+    op(0xE2, "*NOP", 2, 2, false, Immediate, Cpu::nop),
+    op(0xE3, "*ISC", 2, 8, false, IndirectX, Cpu::isc),
+    op(0xE4, "CPX", 2, 3, false, ZeroPage, Cpu::cpx),
+    op(0xE5, "SBC", 2, 3, false, ZeroPage, Cpu::sbc),
+    op(0xE6, "INC", 2, 5, false, ZeroPage, Cpu::inc),
+    op(0xE7, "*ISC", 2, 5, false, ZeroPage, Cpu::isc),
+    op(0xE8, "INX", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.inx()),
+    op(0xE9, "SBC", 2, 2, false, Immediate, Cpu::sbc),
+    op(0xEA, "NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0xEB, "*SBC", 2, 2, false, Immediate, Cpu::sbc),
+    op(0xEC, "CPX", 3, 4, false, Absolute, Cpu::cpx),
+    op(0xED, "SBC", 3, 4, false, Absolute, Cpu::sbc),
+    op(0xEE, "INC", 3, 6, false, Absolute, Cpu::inc),
+    op(0xEF, "*ISC", 3, 6, false, Absolute, Cpu::isc),
+    op(0xF0, "BEQ", 2, 2, false, NoneAddressing, |cpu, bus, _| cpu.beq(bus)),
+    op(0xF1, "SBC", 2, 5, true, IndirectY, Cpu::sbc),
+    op(0xF2, "*KIL", 1, 2, false, NoneAddressing, Cpu::jam),
+    op(0xF3, "*ISC", 2, 8, false, IndirectY, Cpu::isc),
+    op(0xF4, "*NOP", 2, 4, false, ZeroPageX, Cpu::nop),
+    op(0xF5, "SBC", 2, 4, false, ZeroPageX, Cpu::sbc),
+    op(0xF6, "INC", 2, 6, false, ZeroPageX, Cpu::inc),
+    op(0xF7, "*ISC", 2, 6, false, ZeroPageX, Cpu::isc),
+    op(0xF8, "SED", 1, 2, false, NoneAddressing, |cpu, _, _| cpu.sed()),
+    op(0xF9, "SBC", 3, 4, true, AbsoluteY, Cpu::sbc),
+    op(0xFA, "*NOP", 1, 2, false, NoneAddressing, Cpu::nop),
+    op(0xFB, "*ISC", 3, 7, false, AbsoluteY, Cpu::isc),
+    op(0xFC, "*NOP", 3, 4, true, AbsoluteX, Cpu::nop),
+    op(0xFD, "SBC", 3, 4, true, AbsoluteX, Cpu::sbc),
+    op(0xFE, "INC", 3, 7, false, AbsoluteX, Cpu::inc),
+    op(0xFF, "*ISC", 3, 7, false, AbsoluteX, Cpu::isc),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_indexed_by_opcode() {
+        for (index, op) in OPCODES.iter().enumerate() {
+            assert_eq!(op.code as usize, index, "{}", op.name);
+            assert!((1..=3).contains(&op.len), "{:02X}", op.code);
+        }
+    }
 
-        map.insert(0xE7, OpCode::new(0xE7, "*ISC", 2, 5, AddressingMode::ZeroPage));
-        map.insert(0xF7, OpCode::new(0xF7, "*ISC", 2, 6, AddressingMode::ZeroPageX));
-        map.insert(0xEF, OpCode::new(0xEF, "*ISC", 3, 6, AddressingMode::Absolute));
-        map.insert(0xFF, OpCode::new(0xFF, "*ISC", 3, 7, AddressingMode::AbsoluteX));
-        map.insert(0xFB, OpCode::new(0xFB, "*ISC", 3, 7, AddressingMode::AbsoluteY));
-        map.insert(0xE3, OpCode::new(0xE3, "*ISC", 2, 8, AddressingMode::IndirectX));
-        map.insert(0xF3, OpCode::new(0xF3, "*ISC", 2, 8, AddressingMode::IndirectY));
-        
-        map.insert(0x4B, OpCode::new(0x4B, "*ALR", 2, 2, AddressingMode::Immediate));
-        map.insert(0x0B, OpCode::new(0x0B, "*ANC", 2, 2, AddressingMode::Immediate));
-        map.insert(0x2B, OpCode::new(0x2B, "*ANC", 2, 2, AddressingMode::Immediate));
-        map.insert(0x6B, OpCode::new(0x6B, "*ARR", 2, 2, AddressingMode::Immediate));
-        map.insert(0xCB, OpCode::new(0xCB, "*AXS", 2, 2, AddressingMode::Immediate));
-        map.insert(0xBB, OpCode::new(0xBB, "*LAS", 3, 4, AddressingMode::AbsoluteY));
-        map.insert(0x8B, OpCode::new(0x8B, "*XAA", 2, 2, AddressingMode::Immediate));
-        map.insert(0x93, OpCode::new(0x93, "*AHX", 2, 6, AddressingMode::IndirectY));
-        map.insert(0x9F, OpCode::new(0x9F, "*AHX", 3, 5, AddressingMode::AbsoluteY));
-        map.insert(0x9C, OpCode::new(0x9C, "*SHY", 3, 5, AddressingMode::AbsoluteX));
-        map.insert(0x9E, OpCode::new(0x9E, "*SHX", 3, 5, AddressingMode::AbsoluteY));
-        map.insert(0x9B, OpCode::new(0x9B, "*TAS", 3, 5, AddressingMode::AbsoluteY));
-        
-        map
-    };
+    #[test]
+    fn test_page_cross_only_on_indexed_reads() {
+        for op in OPCODES.iter().filter(|op| op.page_cross) {
+            assert!(
+                matches!(op.mode, AbsoluteX | AbsoluteY | IndirectY),
+                "{:02X} {}",
+                op.code,
+                op.name
+            );
+            assert!(!op.name.starts_with("ST"), "{}", op.name);
+        }
+    }
 }
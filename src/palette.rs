@@ -0,0 +1,359 @@
+use crate::ppu::SYSTEM_PALETTE;
+use crate::region::Region;
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Output RGB for every combination of color index and PPUMASK emphasis
+/// bits: entry `emphasis << 6 | index`, where `emphasis` is PPUMASK bits 5-7.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [(u8, u8, u8); 512],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PaletteError {
+    /// The file is not 64, 512 or 1536 RGB triples long.
+    BadSize(usize),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::BadSize(len) => write!(
+                f,
+                "Palette file must hold 64, 512 or 1536 RGB colors \
+                 (192, 1536 or 4608 bytes), not {} bytes",
+                len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+/// How much a channel drops when another channel is emphasized.
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+impl Palette {
+    /// Extend 64 base colors to all emphasis combinations. An emphasis bit
+    /// dims the two channels it does not name: bit 5 red, 6 green, 7 blue,
+    /// with red and green swapped on the PAL and Dendy PPUs.
+    pub fn from_colors(base: &[(u8, u8, u8); 64], region: Region) -> Self {
+        let mut colors = [(0, 0, 0); 512];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let (r, g, b) = base[i & 0x3F];
+            let emphasis = ntsc_emphasis((i >> 6) as u8, region);
+            let dim = |value: u8, own_bit: u8| {
+                if emphasis & !own_bit != 0 {
+                    (value as f32 * EMPHASIS_ATTENUATION).round() as u8
+                } else {
+                    value
+                }
+            };
+            *color = (dim(r, 0b001), dim(g, 0b010), dim(b, 0b100));
+        }
+        Palette { colors }
+    }
+
+    /// Read an FCEUX-style `.pal` file of RGB triples for `region`'s PPU:
+    /// 64 colors; 512 colors with the emphasis variants precomputed; or
+    /// 1536 colors, one 512-color table each for NTSC, PAL and Dendy.
+    pub fn from_pal_file(data: &[u8], region: Region) -> Result<Self, PaletteError> {
+        let rgb = |chunk: &[u8]| (chunk[0], chunk[1], chunk[2]);
+        let table = match data.len() {
+            192 => {
+                let mut base = [(0, 0, 0); 64];
+                for (color, chunk) in base.iter_mut().zip(data.chunks_exact(3)) {
+                    *color = rgb(chunk);
+                }
+                return Ok(Palette::from_colors(&base, region));
+            }
+            1536 => data,
+            4608 => {
+                let index = match region {
+                    Region::Ntsc => 0,
+                    Region::Pal => 1,
+                    Region::Dendy => 2,
+                };
+                &data[index * 1536..(index + 1) * 1536]
+            }
+            len => return Err(PaletteError::BadSize(len)),
+        };
+        let mut colors = [(0, 0, 0); 512];
+        for (color, chunk) in colors.iter_mut().zip(table.chunks_exact(3)) {
+            *color = rgb(chunk);
+        }
+        Ok(Palette { colors })
+    }
+
+    /// Decode the PPU's composite signal for every color index and emphasis
+    /// combination, the way a TV set with `params` would show it.
+    pub fn generate(params: &PaletteParams) -> Self {
+        let mut colors = [(0, 0, 0); 512];
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = params.decode(i as u16);
+        }
+        Palette { colors }
+    }
+}
+
+impl Palette {
+    /// `SYSTEM_PALETTE` with the emphasis bits of `region`'s PPU.
+    pub fn for_region(region: Region) -> Self {
+        Palette::from_colors(&SYSTEM_PALETTE, region)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::for_region(Region::Ntsc)
+    }
+}
+
+/// PPUMASK emphasis bits 5-7 (shifted down) in NTSC order: red, green,
+/// blue. The 2C07 and Dendy PPUs swap the red and green bits.
+fn ntsc_emphasis(emphasis: u8, region: Region) -> u8 {
+    if region == Region::Ntsc {
+        emphasis
+    } else {
+        (emphasis & 0b100) | ((emphasis & 0b001) << 1) | ((emphasis & 0b010) >> 1)
+    }
+}
+
+/// Signal voltages of the four luma levels, low then high half of the
+/// chroma wave (relative to sync, in volts).
+const LOW_LEVELS: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const HIGH_LEVELS: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+/// Signal scale while an emphasized phase is being output.
+const EMPHASIS_LEVEL: f32 = 0.746;
+/// Decoder phase that lines hue 8 (the color burst) up with a TV's
+/// reference, in degrees.
+const BURST_PHASE: f32 = 111.0;
+/// Chroma gain that makes saturation 1.0 match common palettes.
+const CHROMA_GAIN: f32 = 1.5;
+
+/// TV settings for `Palette::generate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteParams {
+    /// NTSC, or PAL decoding with the 2C07's swapped red/green emphasis
+    /// (Dendy clones use the PAL signal).
+    pub region: Region,
+    /// Tint in degrees. PAL sets cancel phase errors line by line, so it
+    /// only applies to NTSC.
+    pub hue: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    pub brightness: f32,
+    pub gamma: f32,
+}
+
+impl PaletteParams {
+    pub fn new(region: Region) -> Self {
+        PaletteParams {
+            region,
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 1.0,
+        }
+    }
+
+    /// RGB of `color`: PPU color index in bits 0-5, emphasis in bits 6-8.
+    fn decode(&self, color: u16) -> (u8, u8, u8) {
+        let emphasis = ntsc_emphasis((color >> 6) as u8, self.region) as u16;
+        let color = (color & 0x3F) | (emphasis << 6);
+        let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+        for phase in 0..12 {
+            let level = composite_level(color, phase);
+            let (cos, sin) = self.carrier(phase);
+            y += level;
+            i += level * cos;
+            q += level * sin;
+        }
+        self.yiq_to_rgb(y / 12.0, i / 12.0, q / 12.0)
+    }
+
+    /// Decoder reference (cos, sin) at subcarrier `phase` (0-11).
+    pub(crate) fn carrier(&self, phase: i32) -> (f32, f32) {
+        let tint = if self.region == Region::Ntsc {
+            self.hue
+        } else {
+            0.0
+        };
+        let angle = PI * (phase as f32 + 0.5) / 6.0 + (BURST_PHASE + tint).to_radians();
+        (angle.cos(), angle.sin())
+    }
+
+    /// RGB from demodulated luma and raw I/Q averages, applying the
+    /// saturation, contrast, brightness and gamma settings.
+    pub(crate) fn yiq_to_rgb(&self, y: f32, i: f32, q: f32) -> (u8, u8, u8) {
+        let chroma = CHROMA_GAIN * self.saturation * self.contrast;
+        let y = y * self.contrast + self.brightness;
+        let (i, q) = (i * chroma, q * chroma);
+
+        let channel = |value: f32| {
+            let value = value.clamp(0.0, 1.0).powf(1.0 / self.gamma);
+            (value * 255.0).round() as u8
+        };
+        (
+            channel(y + 0.946_882 * i + 0.623_557 * q),
+            channel(y - 0.274_788 * i - 0.635_691 * q),
+            channel(y - 1.108_545 * i + 1.709_007 * q),
+        )
+    }
+}
+
+/// Composite signal of `color` (index plus NTSC-order emphasis bits) at
+/// subcarrier `phase`, scaled so black is 0.0 and white 1.0.
+pub(crate) fn composite_level(color: u16, phase: i32) -> f32 {
+    let hue = (color & 0x0F) as i32;
+    let luma = if hue < 0x0E {
+        ((color >> 4) & 0x03) as usize
+    } else {
+        1
+    };
+    let low = if hue == 0x00 {
+        HIGH_LEVELS[luma]
+    } else {
+        LOW_LEVELS[luma]
+    };
+    let high = if hue < 0x0D {
+        HIGH_LEVELS[luma]
+    } else {
+        LOW_LEVELS[luma]
+    };
+    let emphasis = color >> 6;
+
+    // Hue h is high for half of the 12 subcarrier phases, starting at
+    // phase -h; emphasis bits attenuate the red, green and blue phases.
+    let in_phase = |hue: i32| (hue + phase).rem_euclid(12) < 6;
+    let mut signal = if in_phase(hue) { high } else { low };
+    if (emphasis & 0b001 != 0 && in_phase(0x0C))
+        || (emphasis & 0b010 != 0 && in_phase(0x04))
+        || (emphasis & 0b100 != 0 && in_phase(0x08))
+    {
+        signal *= EMPHASIS_LEVEL;
+    }
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+/// `ntsc` or `pal`, optionally followed by `,name=value` settings, e.g.
+/// `ntsc,hue=-10,saturation=1.2,gamma=1.1`.
+impl FromStr for PaletteParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut parts = s.split(',');
+        let region = parts.next().unwrap_or_default().parse()?;
+        let mut params = PaletteParams::new(region);
+        for part in parts {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected name=value, got '{}'", part))?;
+            let value: f32 = value
+                .parse()
+                .map_err(|_| format!("Invalid number '{}' for {}", value, name))?;
+            match name {
+                "hue" => params.hue = value,
+                "saturation" => params.saturation = value,
+                "contrast" => params.contrast = value,
+                "brightness" => params.brightness = value,
+                "gamma" if value <= 0.0 => return Err("gamma must be positive".to_string()),
+                "gamma" => params.gamma = value,
+                _ => return Err(format!("Unknown palette setting '{}'", part)),
+            }
+        }
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+        (a.0 as i32 - b.0 as i32).abs()
+            + (a.1 as i32 - b.1 as i32).abs()
+            + (a.2 as i32 - b.2 as i32).abs()
+    }
+
+    #[test]
+    fn test_generated_ntsc_palette() {
+        let palette = Palette::generate(&PaletteParams::new(Region::Ntsc));
+        let grey = palette.colors[0x00];
+        assert!(grey.0 == grey.1 && grey.1 == grey.2);
+        assert_eq!(palette.colors[0x0F], (0, 0, 0));
+        assert_eq!(palette.colors[0x20], (255, 255, 255));
+        // Close to the usual red, blue and orange.
+        assert!(distance(palette.colors[0x16], (181, 49, 32)) < 40);
+        assert!(distance(palette.colors[0x12], (64, 81, 208)) < 80);
+        assert!(distance(palette.colors[0x27], (247, 153, 55)) < 40);
+
+        // Red emphasis keeps red and dims blue.
+        let (r, _, b) = palette.colors[0x20 | 0x40];
+        assert!(r > b);
+        // PAL swaps the red and green emphasis bits.
+        let pal = Palette::generate(&PaletteParams::new(Region::Pal));
+        let (r, g, _) = pal.colors[0x20 | 0x40];
+        assert!(g > r);
+    }
+
+    #[test]
+    fn test_pal_file_sizes() {
+        let data: Vec<u8> = (0..192).map(|i| i as u8).collect();
+        let palette = Palette::from_pal_file(&data, Region::Ntsc).unwrap();
+        assert_eq!(palette.colors[1], (3, 4, 5));
+        // Green emphasis dims red and blue only.
+        assert_eq!(palette.colors[0x80 | 21], (51, 64, 53));
+        // On PAL the same bit is red emphasis.
+        let palette = Palette::from_pal_file(&data, Region::Pal).unwrap();
+        assert_eq!(palette.colors[0x40 | 21], (51, 64, 53));
+
+        let data: Vec<u8> = (0..1536).map(|i| (i / 3) as u8).collect();
+        let palette = Palette::from_pal_file(&data, Region::Pal).unwrap();
+        assert_eq!(palette.colors[0x1FF], (255, 255, 255));
+
+        // 1536 colors: the NTSC, PAL and Dendy tables in turn.
+        let data: Vec<u8> = (0..4608).map(|i| (i / 1536) as u8).collect();
+        for (region, value) in [(Region::Ntsc, 0), (Region::Pal, 1), (Region::Dendy, 2)] {
+            let palette = Palette::from_pal_file(&data, region).unwrap();
+            assert!(palette.colors.iter().all(|&c| c == (value, value, value)));
+        }
+
+        assert_eq!(
+            Palette::from_pal_file(&[0; 100], Region::Ntsc),
+            Err(PaletteError::BadSize(100))
+        );
+    }
+
+    #[test]
+    fn test_pal_emphasis_swaps_red_and_green() {
+        let white = [(255, 255, 255); 64];
+        // PPUMASK bit 5 emphasizes red on NTSC and green on PAL and Dendy.
+        assert_eq!(
+            Palette::from_colors(&white, Region::Ntsc).colors[0x40],
+            (255, 208, 208)
+        );
+        for region in [Region::Pal, Region::Dendy] {
+            let palette = Palette::from_colors(&white, region);
+            assert_eq!(palette.colors[0x40], (208, 255, 208));
+            assert_eq!(palette.colors[0x80], (255, 208, 208));
+            assert_eq!(palette.colors[0x100], (208, 208, 255));
+        }
+    }
+
+    #[test]
+    fn test_params_from_str() {
+        let params: PaletteParams = "pal,hue=-10,gamma=1.2".parse().unwrap();
+        assert_eq!(params.region, Region::Pal);
+        assert_eq!(params.hue, -10.0);
+        assert_eq!(params.gamma, 1.2);
+        assert!("ntsc,tint=5".parse::<PaletteParams>().is_err());
+        assert!("ntsc,gamma=0".parse::<PaletteParams>().is_err());
+        assert!("secam".parse::<PaletteParams>().is_err());
+    }
+}
@@ -1,9 +1,25 @@
+use crate::bus::{AccessKind, MemoryAccess};
 use crate::cartridge::Mirroring;
+use crate::palette::Palette;
+use crate::region::Region;
+
+/// Memory behind one 1 KB nametable slot ($2000, $2400, $2800 or $2C00).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NametableSource {
+    /// Page 0 or 1 of the console's 2 KB `vram` (CIRAM).
+    Ciram(u8),
+    /// A 1 KB page of `cartridge_vram`, e.g. four-screen boards or MMC5 ExRAM.
+    CartridgeVram(u8),
+    /// A 1 KB page of CHR ROM, or CHR RAM on boards without ROM (Namco 163).
+    Chr(u16),
+}
 
 pub struct Ppu {
     pub vram: [u8; 2048],
     pub oam: [u8; 256],
     pub palette: [u8; 32],
+    /// RGB output for palette RAM values, indexed with the emphasis bits.
+    pub output_palette: Palette,
 
     // Registers
     pub ctrl: u8,     // $2000
@@ -23,11 +39,22 @@ pub struct Ppu {
 
     pub io_databus: u8,    // Internal data bus latch (for reading/writing)
     pub buffered_data: u8, // For PPUDATA read buffer
+    /// `dot_count` when each `io_databus` bit was last driven high.
+    pub io_refreshed: [u64; 8],
+    /// PPU dots since power-on, the clock for open bus decay.
+    pub dot_count: u64,
 
     pub mirroring: Mirroring,
+    /// Nametable memory on the cartridge: 2 KB for four-screen boards.
+    pub cartridge_vram: Vec<u8>,
+    /// Set by mappers that route nametables themselves (MMC5, Namco 163,
+    /// TxSROM); overrides `mirroring` while present.
+    pub nametable_map: Option<[NametableSource; 4]>,
     pub chr_rom: Vec<u8>,
     pub chr_ram: [u8; 8192],
     pub mapper: u8,
+    /// Frame layout: scanline count, vblank start and odd-frame dot skip.
+    pub region: Region,
     pub chr_bank: usize,
     pub mmc1_control: u8,
     pub mmc1_chr_bank0: u8,
@@ -62,18 +89,62 @@ pub struct Ppu {
     // Sprite Rendering State
     pub secondary_oam: [u8; 32],
     pub sprite_count: u8,
-    pub sprite_shifter_pattern_lo: [u8; 8],
-    pub sprite_shifter_pattern_hi: [u8; 8],
-    pub sprite_latch_x: [u8; 8],
-    pub sprite_latch_attr: [u8; 8],
+    /// Draw every sprite on a line instead of the first eight. Display only:
+    /// secondary OAM, the overflow flag and sprite 0 hit are unaffected.
+    pub unlimited_sprites: bool,
+    /// Sprites fetched for the next line, in priority order.
+    pub line_sprites: [u8; 256],
+    /// OAM index of each entry in `line_sprites`.
+    pub line_sprite_indices: [u8; 64],
+    pub sprite_shifter_pattern_lo: [u8; 64],
+    pub sprite_shifter_pattern_hi: [u8; 64],
+    pub sprite_latch_x: [u8; 64],
+    pub sprite_latch_attr: [u8; 64],
     pub b_sprite_zero_hit_possible: bool,
     pub b_sprite_zero_being_rendered: bool,
+    // Sprite evaluation state (dots 65-256)
+    eval_start: u8,
+    eval_addr: u16,
+    eval_data: u8,
+    eval_copy_remaining: u8,
+    eval_found: u8,
+    eval_done: bool,
+    eval_indices: [u8; 8],
     pub odd_frame: bool,
+    /// Frames completed since power-on, incremented as VBlank begins.
+    pub frame_count: u64,
 
     pub frame_buffer: Vec<u8>,
+    /// The same frame as 9-bit palette indices: color in bits 0-5, PPUMASK
+    /// emphasis in bits 6-8. Independent of `output_palette`.
+    pub frame_indices: Vec<u16>,
+    /// Color subcarrier phase (0-11) at the current dot. A dot lasts 8 of the
+    /// 12 phases, so the phase drifts from line to line and frame to frame.
+    pub dot_phase: u8,
+    /// `dot_phase` at the first visible dot of each line of the frame.
+    pub scanline_phases: [u8; 240],
+
+    /// When set, every PPUDATA ($2007) access to PPU memory is appended here.
+    pub vram_access_log: Option<Vec<MemoryAccess>>,
+
+    // Debug display switches. Like `unlimited_sprites` they only change
+    // what is drawn, never sprite 0 hit or other game-visible state.
+    pub hide_background: bool,
+    pub hide_sprites: bool,
+    /// Bit n hides the sprite at OAM index n.
+    pub hidden_sprites: u64,
+    /// Dot (x, scanline) where sprite 0 hit was set this frame.
+    pub sprite_zero_hit_dot: Option<(u8, u8)>,
+    /// Scanlines where the MMC3 IRQ fired this frame.
+    pub mmc3_irq_scanlines: Vec<u16>,
 }
 
-const SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
+/// How long the PPU I/O latch holds a bit without refresh: about 600 ms at
+/// the NTSC dot rate of 5.37 MHz.
+pub const OPEN_BUS_DECAY_DOTS: u64 = 3_220_000;
+
+/// RGB for each of the 64 NES color indices.
+pub const SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
     (0x54, 0x54, 0x54),
     (0x00, 0x1E, 0x74),
     (0x08, 0x10, 0x90),
@@ -146,6 +217,7 @@ impl Ppu {
             vram: [0; 2048],
             oam: [0; 256],
             palette: [0; 32],
+            output_palette: Palette::default(),
             ctrl: 0,
             mask: 0,
             status: 0,
@@ -159,11 +231,16 @@ impl Ppu {
             x: 0,
             w: false,
             io_databus: 0,
+            io_refreshed: [0; 8],
+            dot_count: 0,
             buffered_data: 0,
             mirroring,
+            cartridge_vram: Self::cartridge_vram_for(mirroring),
+            nametable_map: None,
             chr_rom,
             chr_ram: [0; 8192],
             mapper: 0,
+            region: Region::Ntsc,
             chr_bank: 0,
             mmc1_control: 0x0C,
             mmc1_chr_bank0: 0,
@@ -190,32 +267,58 @@ impl Ppu {
 
             secondary_oam: [0; 32],
             sprite_count: 0,
-            sprite_shifter_pattern_lo: [0; 8],
-            sprite_shifter_pattern_hi: [0; 8],
-            sprite_latch_x: [0; 8],
-            sprite_latch_attr: [0; 8],
+            unlimited_sprites: false,
+            line_sprites: [0xFF; 256],
+            line_sprite_indices: [0; 64],
+            sprite_shifter_pattern_lo: [0; 64],
+            sprite_shifter_pattern_hi: [0; 64],
+            sprite_latch_x: [0; 64],
+            sprite_latch_attr: [0; 64],
             b_sprite_zero_hit_possible: false,
             b_sprite_zero_being_rendered: false,
+            eval_start: 0,
+            eval_addr: 0,
+            eval_data: 0,
+            eval_copy_remaining: 0,
+            eval_found: 0,
+            eval_done: false,
+            eval_indices: [0; 8],
             odd_frame: false,
+            frame_count: 0,
 
             frame_buffer: vec![0; 256 * 240 * 4],
+            frame_indices: vec![0; 256 * 240],
+            dot_phase: 0,
+            scanline_phases: [0; 240],
+            vram_access_log: None,
+            hide_background: false,
+            hide_sprites: false,
+            hidden_sprites: 0,
+            sprite_zero_hit_dot: None,
+            mmc3_irq_scanlines: Vec::new(),
         }
     }
 
     pub fn tick(&mut self, cycles: u16) -> bool {
         let mut nmi_triggered = false;
 
+        let pre_render = self.region.pre_render_scanline();
+        let vblank = self.region.vblank_scanline();
+
         for _ in 0..cycles {
             // Cycle/Scanline management
             if self.cycle >= 340 {
                 self.cycle = 0;
                 self.scanline += 1;
 
-                if self.scanline >= 262 {
+                if self.scanline >= self.region.scanlines() {
                     self.scanline = 0;
                     self.odd_frame = !self.odd_frame;
                     // Skip cycle 0 on odd frames if background or sprite rendering is enabled
-                    if self.odd_frame && (self.mask & 0x18) != 0 {
+                    if self.odd_frame
+                        && (self.mask & 0x18) != 0
+                        && self.region.skips_odd_frame_dot()
+                    {
                         self.cycle = 1;
                     }
                 }
@@ -223,11 +326,24 @@ impl Ppu {
                 self.cycle += 1;
             }
 
+            self.dot_count += 1;
+            self.dot_phase = (self.dot_phase + 8) % 12;
+            if self.cycle == 1 && self.scanline < 240 {
+                self.scanline_phases[self.scanline as usize] = self.dot_phase;
+            }
+
             // Background Rendering
             if self.mask & 0x18 != 0 {
-                if self.scanline < 240 || self.scanline == 261 {
+                if self.scanline < 240 || self.scanline == pre_render {
                     // Visible lines + Pre-render line
 
+                    if self.scanline < 240 {
+                        self.step_sprite_evaluation();
+                    }
+                    if self.cycle >= 257 && self.cycle <= 320 {
+                        self.oam_addr = 0; // Reset during sprite tile fetches
+                    }
+
                     if self.cycle > 0 && self.cycle <= 256 {
                         self.update_shifters();
                         self.update_sprite_shifters();
@@ -251,12 +367,12 @@ impl Ppu {
                         self.load_background_shifters();
                         self.transfer_address_x();
 
-                        // Sprite Evaluation
-                        if self.scanline < 240 {
-                            self.evaluate_sprites();
+                        // Sprites found by evaluation are drawn on the next line
+                        self.sprite_count = if self.scanline < 240 {
+                            self.collect_line_sprites()
                         } else {
-                            self.sprite_count = 0;
-                        }
+                            0
+                        };
                     } else if self.cycle > 320 && self.cycle <= 336 {
                         if self.cycle == 321 {
                             // Load sprites for next line
@@ -281,8 +397,8 @@ impl Ppu {
                         // Unused fetches
                     }
 
-                    // Specific logic for Scanline 261 (Pre-render)
-                    if self.scanline == 261 {
+                    // Specific logic for the pre-render scanline (261 on NTSC)
+                    if self.scanline == pre_render {
                         if self.cycle == 1 {
                             self.b_sprite_zero_hit_possible = false;
                             self.b_sprite_zero_being_rendered = false;
@@ -305,14 +421,17 @@ impl Ppu {
             }
 
             // VBlank / NMI Logic
-            if self.scanline == 241 && self.cycle == 1 {
+            if self.scanline == vblank && self.cycle == 1 {
                 self.status |= 0x80; // Set VBlank flag
+                self.frame_count += 1;
             }
 
-            if self.scanline == 261 && self.cycle == 1 {
+            if self.scanline == pre_render && self.cycle == 1 {
                 self.status &= !0x80; // Clear VBlank flag
                 self.status &= !0x40; // Clear Sprite 0 Hit
                 self.status &= !0x20; // Clear Sprite Overflow
+                self.sprite_zero_hit_dot = None;
+                self.mmc3_irq_scanlines.clear();
             }
 
             // NMI edge detection: fire NMI only on rising edge (false→true)
@@ -334,26 +453,73 @@ impl Ppu {
 
     // Register Read/Write
     pub fn read_register(&mut self, addr: u16) -> u8 {
+        self.io_databus = self.decayed_io_databus();
         match addr {
-            0x2002 => self.read_status(),
-            0x2004 => self.read_oam_data(),
-            0x2007 => self.read_data(),
-            _ => 0, // Write-only registers typically return open bus or last written value
+            0x2002 => {
+                // Only the top three bits are driven; the rest is open bus
+                let status = self.read_status();
+                self.drive_io_databus(status, 0xE0)
+            }
+            0x2004 => {
+                let data = self.read_oam_data();
+                self.drive_io_databus(data, 0xFF)
+            }
+            0x2007 => {
+                // Palette reads only drive the low six bits
+                let mask = if self.v % 0x4000 >= 0x3F00 {
+                    0x3F
+                } else {
+                    0xFF
+                };
+                let data = self.read_data();
+                self.drive_io_databus(data, mask)
+            }
+            _ => self.io_databus, // Write-only registers read back the latch
         }
     }
 
     /// Non-side-effecting register read for trace/debug.
     pub fn peek_register(&self, addr: u16) -> u8 {
+        let io = self.decayed_io_databus();
         match addr {
-            0x2002 => self.status, // Return status without clearing VBlank
+            0x2002 => (self.status & 0xE0) | (io & 0x1F), // Without clearing VBlank
             0x2004 => self.oam[self.oam_addr as usize],
             0x2007 => self.buffered_data, // Return buffered data without advancing address
-            _ => 0,
+            _ => io,
         }
     }
 
+    /// The I/O latch after decay: a bit driven high reads back as 0 once
+    /// `OPEN_BUS_DECAY_DOTS` pass without it being refreshed.
+    fn decayed_io_databus(&self) -> u8 {
+        let mut value = self.io_databus;
+        for (bit, &refreshed) in self.io_refreshed.iter().enumerate() {
+            if self.dot_count.saturating_sub(refreshed) > OPEN_BUS_DECAY_DOTS {
+                value &= !(1 << bit);
+            }
+        }
+        value
+    }
+
+    /// Put `data` on the I/O latch for the bits in `mask`, and return the
+    /// byte the CPU sees: those bits plus the latch for the rest.
+    fn drive_io_databus(&mut self, data: u8, mask: u8) -> u8 {
+        self.io_databus = (self.io_databus & !mask) | (data & mask);
+        for bit in 0..8 {
+            if mask & (1 << bit) != 0 {
+                self.io_refreshed[bit] = self.dot_count;
+            }
+        }
+        self.io_databus
+    }
+
+    /// Non-side-effecting read of PPU address space ($0000-$3FFF) for debugging.
+    pub fn peek_vram(&self, addr: u16) -> u8 {
+        self.read_vram(addr)
+    }
+
     pub fn write_register(&mut self, addr: u16, data: u8) {
-        self.io_databus = data;
+        self.drive_io_databus(data, 0xFF);
         match addr {
             0x2000 => self.write_ctrl(data),
             0x2001 => self.write_mask(data),
@@ -410,10 +576,33 @@ impl Ppu {
 
     // $2004 OAMDATA (Read/Write)
     fn read_oam_data(&self) -> u8 {
-        self.oam[self.oam_addr as usize]
+        if !self.is_rendering() {
+            return self.oam[self.oam_addr as usize];
+        }
+        // While rendering, the read sees whatever is on the sprite
+        // evaluation bus.
+        match self.cycle {
+            // Secondary OAM is being cleared with $FF
+            1..=64 if self.scanline < 240 => 0xFF,
+            // The OAM byte evaluation last fetched
+            65..=256 if self.scanline < 240 => self.eval_data,
+            1..=256 => self.oam[self.oam_addr as usize],
+            // Sprite fetches read Y, tile, attribute, then X for five dots
+            257..=320 => {
+                let dot = (self.cycle - 257) as usize;
+                self.secondary_oam[dot / 8 * 4 + (dot % 8).min(3)]
+            }
+            _ => self.secondary_oam[0],
+        }
     }
 
     fn write_oam_data(&mut self, data: u8) {
+        if self.is_rendering() {
+            // The write is dropped, but OAMADDR is bumped to the next
+            // sprite as if its high six bits were incremented.
+            self.oam_addr = self.oam_addr.wrapping_add(4);
+            return;
+        }
         self.oam[self.oam_addr as usize] = data;
         self.oam_addr = self.oam_addr.wrapping_add(1);
     }
@@ -457,6 +646,7 @@ impl Ppu {
     // $2007 PPUDATA
     fn read_data(&mut self) -> u8 {
         let mut value = self.read_vram(self.v);
+        self.log_vram_access(self.v, value, AccessKind::Read);
 
         // Buffered read for VRAM (up to 0x3EFF)
         // Palette read (0x3F00+) is immediate
@@ -473,11 +663,29 @@ impl Ppu {
     }
 
     fn write_data(&mut self, data: u8) {
+        self.log_vram_access(self.v, data, AccessKind::Write);
         self.write_vram(self.v, data);
         self.increment_vram_addr();
     }
 
+    fn log_vram_access(&mut self, addr: u16, value: u8, kind: AccessKind) {
+        if let Some(log) = self.vram_access_log.as_mut() {
+            log.push(MemoryAccess {
+                addr: addr & 0x3FFF,
+                value,
+                kind,
+            });
+        }
+    }
+
     fn increment_vram_addr(&mut self) {
+        if self.is_rendering() {
+            // v is the rendering address now, and the access bumps coarse X
+            // and Y at once instead of adding 1 or 32.
+            self.increment_scroll_x();
+            self.increment_scroll_y();
+            return;
+        }
         // Increment determined by bit 2 of PPUCTRL (0: +1, 1: +32)
         let increment = if (self.ctrl & 0x04) == 0 { 1 } else { 32 };
         self.v = (self.v + increment) & 0x7FFF; // 15-bit wrap
@@ -558,13 +766,44 @@ impl Ppu {
     }
 
     fn read_nametable(&self, addr: u16) -> u8 {
-        let addr = self.mirror_vram_addr(addr);
-        self.vram[addr as usize]
+        let (source, offset) = self.nametable_slot(addr);
+        match source {
+            NametableSource::Ciram(page) => self.vram[(page as usize & 1) * 0x400 + offset],
+            NametableSource::CartridgeVram(page) => {
+                let index = page as usize * 0x400 + offset;
+                self.cartridge_vram.get(index).copied().unwrap_or(0)
+            }
+            NametableSource::Chr(page) => {
+                let index = page as usize * 0x400 + offset;
+                if self.chr_rom.is_empty() {
+                    self.chr_ram[index % self.chr_ram.len()]
+                } else {
+                    self.chr_rom[index % self.chr_rom.len()]
+                }
+            }
+        }
     }
 
     fn write_nametable(&mut self, addr: u16, data: u8) {
-        let addr = self.mirror_vram_addr(addr);
-        self.vram[addr as usize] = data;
+        let (source, offset) = self.nametable_slot(addr);
+        match source {
+            NametableSource::Ciram(page) => {
+                self.vram[(page as usize & 1) * 0x400 + offset] = data;
+            }
+            NametableSource::CartridgeVram(page) => {
+                let index = page as usize * 0x400 + offset;
+                if let Some(byte) = self.cartridge_vram.get_mut(index) {
+                    *byte = data;
+                }
+            }
+            NametableSource::Chr(page) => {
+                if self.chr_rom.is_empty() {
+                    let index = page as usize * 0x400 + offset;
+                    let len = self.chr_ram.len();
+                    self.chr_ram[index % len] = data;
+                }
+            }
+        }
     }
 
     fn read_palette(&self, addr: u16) -> u8 {
@@ -583,29 +822,38 @@ impl Ppu {
         self.palette[addr as usize] = data;
     }
 
-    fn mirror_vram_addr(&self, addr: u16) -> u16 {
-        // Nametables at 0x2000, size 0x1000 (4KB space), but physical VRAM is 2KB.
-        let addr = (addr - 0x2000) % 0x1000; // 0x0000 - 0x0FFF inside Nametable space
-
-        // 0x2000, 0x2400, 0x2800, 0x2C00
-        let table = addr / 0x400; // 0, 1, 2, 3
-        let offset = addr % 0x400;
-
-        match (self.mirroring, table) {
-            (Mirroring::Vertical, 2) | (Mirroring::Vertical, 3) => (table - 2) * 0x400 + offset,
-            (Mirroring::Vertical, _) => table * 0x400 + offset,
-
-            (Mirroring::Horizontal, 2) | (Mirroring::Horizontal, 1) => (table - 1) * 0x400 + offset, // 1->0, 2->1
-            (Mirroring::Horizontal, 3) => (table - 2) * 0x400 + offset, // 3->1
-            (Mirroring::Horizontal, _) => table * 0x400 + offset,
-
-            (Mirroring::OneScreenLower, _) => offset,
-            (Mirroring::OneScreenUpper, _) => 0x400 + offset,
+    /// Where nametable `table` (0-3) is stored.
+    pub fn nametable_source(&self, table: u16) -> NametableSource {
+        if let Some(map) = self.nametable_map {
+            return map[table as usize & 3];
+        }
+        let table = (table & 3) as u8;
+        match self.mirroring {
+            Mirroring::Vertical => NametableSource::Ciram(table & 1),
+            Mirroring::Horizontal => NametableSource::Ciram(table >> 1),
+            Mirroring::OneScreenLower => NametableSource::Ciram(0),
+            Mirroring::OneScreenUpper => NametableSource::Ciram(1),
+            // The console's 2 KB holds $2000/$2400, the cartridge the rest.
+            Mirroring::FourScreen if table < 2 => NametableSource::Ciram(table),
+            Mirroring::FourScreen => NametableSource::CartridgeVram(table - 2),
+        }
+    }
 
-            _ => addr & 0x7FF, // Fallback?
+    /// Cartridge nametable RAM a board with `mirroring` carries.
+    pub fn cartridge_vram_for(mirroring: Mirroring) -> Vec<u8> {
+        match mirroring {
+            Mirroring::FourScreen => vec![0; 0x800],
+            _ => Vec::new(),
         }
     }
 
+    /// Source and offset within it of nametable address `addr`.
+    fn nametable_slot(&self, addr: u16) -> (NametableSource, usize) {
+        // 0x2000-0x2FFF, mirrored up to 0x3EFF
+        let addr = (addr - 0x2000) % 0x1000;
+        (self.nametable_source(addr / 0x400), (addr % 0x400) as usize)
+    }
+
     fn mmc1_chr_offset(&self, addr: usize, total_len: usize) -> usize {
         if total_len == 0 {
             return 0;
@@ -677,9 +925,17 @@ impl Ppu {
         }
         if self.mmc3_irq_counter == 0 && self.mmc3_irq_enabled {
             self.mmc3_irq_pending = true;
+            self.mmc3_irq_scanlines.push(self.scanline);
         }
     }
 
+    /// Whether the PPU is fetching and rendering: rendering enabled on a
+    /// visible or pre-render line. Register accesses behave differently then.
+    fn is_rendering(&self) -> bool {
+        (self.mask & 0x18) != 0
+            && (self.scanline < 240 || self.scanline == self.region.pre_render_scanline())
+    }
+
     // Scrolling Helpers
     fn increment_scroll_x(&mut self) {
         if (self.mask & 0x18) == 0 {
@@ -754,48 +1010,109 @@ impl Ppu {
 
     // ... logic inside tick ...
 
-    fn evaluate_sprites(&mut self) {
-        // Clear secondary OAM and count
-        self.secondary_oam.fill(0xFF);
-        self.sprite_count = 0;
-        self.b_sprite_zero_hit_possible = false;
+    /// Clear secondary OAM over dots 1-64, then evaluate sprites for the next
+    /// scanline over dots 65-256: OAM is read on odd dots and the result
+    /// written to secondary OAM on even dots.
+    fn step_sprite_evaluation(&mut self) {
+        let odd_dot = self.cycle & 1 == 1;
+        match self.cycle {
+            1..=64 if !odd_dot => {
+                self.secondary_oam[(self.cycle / 2 - 1) as usize] = 0xFF;
+            }
+            65..=256 => {
+                if self.cycle == 65 {
+                    // Evaluation starts wherever OAMADDR points, so a
+                    // non-zero OAMADDR makes another entry act as sprite 0.
+                    self.eval_start = self.oam_addr;
+                    self.eval_addr = self.oam_addr as u16;
+                    self.eval_copy_remaining = 0;
+                    self.eval_found = 0;
+                    self.eval_done = false;
+                    self.b_sprite_zero_hit_possible = false;
+                }
+                if odd_dot {
+                    self.eval_data = self.oam[(self.eval_addr & 0xFF) as usize];
+                } else {
+                    self.evaluate_sprite_byte();
+                }
+            }
+            _ => {}
+        }
+    }
 
+    fn evaluate_sprite_byte(&mut self) {
+        if self.eval_done {
+            return;
+        }
+        let value = self.eval_data;
         let sprite_height = if (self.ctrl & 0x20) != 0 { 16 } else { 8 };
-        // Scan OAM for sprites on this scanline
-        // Primary OAM: 64 sprites * 4 bytes
-        for i in 0..64 {
-            let n = i * 4;
-            let y = self.oam[n] as u16; // Y-position
-
-            // Check if sprite is on this scanline
-            // Note: Sprite Y is delayed by one scanline in rendering?
-            // The scanline variable represents the line *currently being rendered*.
-            // We are evaluating for the *next* line (for fetching).
-            // But usually logic is: if scanline is inside [y, y+height).
-            // Actually, sprite Y in OAM is top coordinate - 1? No, it's just top.
-            // But sprites are rendered one line late.
-            // If internal `scanline` matches `y`, it will be rendered on `scanline`.
-
-            let diff = (self.scanline as i16) - (y as i16);
-
-            if diff >= 0 && diff < sprite_height {
-                if self.sprite_count < 8 {
-                    // Copy to secondary OAM
-                    // OAM: Y, Box (Tile), Attr, X
-                    // Secondary: same? structure varies but simplified emulator often keeps same.
-                    if i == 0 {
+        let diff = (self.scanline as i16) - (value as i16);
+        let in_range = diff >= 0 && diff < sprite_height;
+
+        if self.eval_found < 8 {
+            let slot = self.eval_found as usize * 4;
+            if self.eval_copy_remaining == 0 {
+                // Y is copied even when out of range; the slot is reused.
+                self.secondary_oam[slot] = value;
+                if in_range {
+                    self.eval_indices[self.eval_found as usize] = (self.eval_addr >> 2) as u8;
+                    if self.cycle == 66 {
                         self.b_sprite_zero_hit_possible = true;
                     }
+                    self.eval_copy_remaining = 3;
+                    self.eval_addr += 1;
+                } else {
+                    self.eval_addr += 4;
+                }
+            } else {
+                self.secondary_oam[slot + 4 - self.eval_copy_remaining as usize] = value;
+                self.eval_copy_remaining -= 1;
+                self.eval_addr += 1;
+                if self.eval_copy_remaining == 0 {
+                    self.eval_found += 1;
+                }
+            }
+        } else if in_range {
+            self.status |= 0x20; // Sprite overflow
+            self.eval_done = true;
+        } else {
+            // Hardware bug: after eight sprites are found, a miss increments
+            // both the sprite index and the byte index, so the scan walks
+            // diagonally through OAM and compares tile/attribute/X bytes as Y.
+            self.eval_addr = ((self.eval_addr + 4) & !3) | ((self.eval_addr + 1) & 3);
+        }
 
-                    self.secondary_oam[self.sprite_count as usize * 4 + 0] = self.oam[n + 0];
-                    self.secondary_oam[self.sprite_count as usize * 4 + 1] = self.oam[n + 1];
-                    self.secondary_oam[self.sprite_count as usize * 4 + 2] = self.oam[n + 2];
-                    self.secondary_oam[self.sprite_count as usize * 4 + 3] = self.oam[n + 3];
+        if self.eval_addr >= 0x100 && self.eval_copy_remaining == 0 {
+            self.eval_done = true;
+        }
+    }
 
-                    self.sprite_count += 1;
-                }
+    /// Copy the sprites to draw on the next line into `line_sprites`: secondary
+    /// OAM, followed by every further in-range sprite if the limit is off.
+    fn collect_line_sprites(&mut self) -> u8 {
+        let found = self.eval_found as usize;
+        self.line_sprites[..found * 4].copy_from_slice(&self.secondary_oam[..found * 4]);
+        self.line_sprite_indices[..found].copy_from_slice(&self.eval_indices[..found]);
+        if !self.unlimited_sprites || found < 8 {
+            return self.eval_found;
+        }
+
+        // With a misaligned OAMADDR, evaluation copies bytes that straddle
+        // entries, so only the indices it recorded are skipped here.
+        let sprite_height = if (self.ctrl & 0x20) != 0 { 16 } else { 8 };
+        let mut count = found;
+        for (index, sprite) in self.oam.chunks_exact(4).enumerate() {
+            let diff = (self.scanline as i16) - (sprite[0] as i16);
+            if diff >= 0
+                && diff < sprite_height
+                && !self.eval_indices[..found].contains(&(index as u8))
+            {
+                self.line_sprites[count * 4..count * 4 + 4].copy_from_slice(sprite);
+                self.line_sprite_indices[count] = index as u8;
+                count += 1;
             }
         }
+        count as u8
     }
 
     fn prepare_sprite_shifters(&mut self) {
@@ -803,10 +1120,10 @@ impl Ppu {
 
         for i in 0..self.sprite_count {
             let n = i as usize * 4;
-            let y = self.secondary_oam[n + 0] as u16;
-            let tile_id = self.secondary_oam[n + 1];
-            let attr = self.secondary_oam[n + 2];
-            let x = self.secondary_oam[n + 3];
+            let y = self.line_sprites[n + 0] as u16;
+            let tile_id = self.line_sprites[n + 1];
+            let attr = self.line_sprites[n + 2];
+            let x = self.line_sprites[n + 3];
 
             self.sprite_latch_x[i as usize] = x;
             self.sprite_latch_attr[i as usize] = attr;
@@ -980,9 +1297,9 @@ impl Ppu {
 
         // --- Sprite Pixel ---
         let mut fg_pixel = 0;
-        let mut fg_palette = 0;
-        let mut fg_priority = 0; // 0: Front, 1: Back
         let mut fg_is_sprite_zero = false;
+        // The front-most sprite pixel that is not hidden for debugging
+        let mut shown_sprite = None;
 
         if mask_spr {
             if mask_spr_left || self.cycle > 8 {
@@ -1001,16 +1318,24 @@ impl Ppu {
                         let pixel = p0 | p1;
 
                         // Transparency check: if pixel is 0, it's transparent, look for next sprite
-                        if pixel != 0 {
+                        if pixel == 0 {
+                            continue;
+                        }
+                        if fg_pixel == 0 {
                             fg_pixel = pixel;
-                            fg_palette = (self.sprite_latch_attr[i as usize] & 0x03) + 4; // Sprites use palettes 4-7
-                            fg_priority = (self.sprite_latch_attr[i as usize] & 0x20) >> 5;
 
                             // Check for Sprite 0
                             if i == 0 && self.b_sprite_zero_being_rendered {
                                 fg_is_sprite_zero = true;
                             }
+                        }
 
+                        let index = self.line_sprite_indices[i as usize];
+                        if !self.hide_sprites && self.hidden_sprites & (1 << index) == 0 {
+                            let attr = self.sprite_latch_attr[i as usize];
+                            let palette = (attr & 0x03) + 4; // Sprites use palettes 4-7
+                            let priority = (attr & 0x20) >> 5; // 0: Front, 1: Back
+                            shown_sprite = Some((pixel, palette, priority));
                             break; // Priority to lower index sprites
                         }
                     }
@@ -1018,6 +1343,22 @@ impl Ppu {
             }
         }
 
+        // Sprite 0 Hit
+        if fg_is_sprite_zero && bg_pixel > 0 {
+            // Must actuate on visible pixels (bg & fg) - done
+            // Must be at cycle != 255 (255 is right edge, 6502 treats 255 weirdly?)
+            // Actually if x < 255. cycle is x+1. So if cycle < 256.
+            if self.cycle != 256 && (self.status & 0x40) == 0 {
+                // Avoid right edge edge-case?
+                self.status |= 0x40; // Set Sprite 0 Hit
+                self.sprite_zero_hit_dot = Some(((self.cycle - 1) as u8, self.scanline as u8));
+            }
+        }
+
+        // Hidden layers and sprites drop out of the mix from here on
+        let bg_pixel = if self.hide_background { 0 } else { bg_pixel };
+        let (fg_pixel, fg_palette, fg_priority) = shown_sprite.unwrap_or((0, 0, 0));
+
         // --- Mixing & Priority ---
         let mut final_pixel = 0;
         let mut final_palette = 0;
@@ -1034,18 +1375,6 @@ impl Ppu {
             final_palette = bg_palette;
         } else if bg_pixel > 0 && fg_pixel > 0 {
             // Both opaque
-
-            // Sprite 0 Hit
-            if fg_is_sprite_zero {
-                // Must actuate on visible pixels (bg & fg) - done
-                // Must be at cycle != 255 (255 is right edge, 6502 treats 255 weirdly?)
-                // Actually if x < 255. cycle is x+1. So if cycle < 256.
-                if self.cycle != 256 {
-                    // Avoid right edge edge-case?
-                    self.status |= 0x40; // Set Sprite 0 Hit
-                }
-            }
-
             if fg_priority == 0 {
                 // Foreground Priority
                 final_pixel = fg_pixel;
@@ -1066,7 +1395,9 @@ impl Ppu {
             color_byte &= 0x30;
         }
 
-        let color = SYSTEM_PALETTE[color_byte as usize];
+        // Emphasis bits select one of eight tinted copies of the palette
+        let index = ((self.mask as u16 & 0xE0) << 1) | color_byte as u16;
+        let color = self.output_palette.colors[index as usize];
 
         // Plot to frame buffer
         let x = (self.cycle - 1) as usize;
@@ -1074,6 +1405,7 @@ impl Ppu {
         let idx = (y * 256 + x) * 4;
 
         if idx < self.frame_buffer.len() {
+            self.frame_indices[y * 256 + x] = index;
             self.frame_buffer[idx] = color.0;
             self.frame_buffer[idx + 1] = color.1;
             self.frame_buffer[idx + 2] = color.2;
@@ -1131,4 +1463,317 @@ mod tests {
         assert_eq!(ppu.status & 0x80, 0x00);
         assert_eq!(ppu.scanline, 261);
     }
+    fn write_ppu(ppu: &mut Ppu, addr: u16, data: u8) {
+        ppu.write_register(0x2006, (addr >> 8) as u8);
+        ppu.write_register(0x2006, addr as u8);
+        ppu.write_register(0x2007, data);
+    }
+
+    #[test]
+    fn test_four_screen_nametables() {
+        let mut ppu = Ppu::new(Mirroring::FourScreen, vec![]);
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+            write_ppu(&mut ppu, addr + 5, i as u8 + 1);
+        }
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+            assert_eq!(ppu.peek_vram(addr + 5), i as u8 + 1);
+            assert_eq!(ppu.peek_vram(addr + 0x1000 + 5), i as u8 + 1); // $3000 mirror
+        }
+        assert_eq!((ppu.vram[5], ppu.vram[0x405]), (1, 2));
+        assert_eq!((ppu.cartridge_vram[5], ppu.cartridge_vram[0x405]), (3, 4));
+    }
+
+    #[test]
+    fn test_nametable_map_overrides_mirroring() {
+        let mut chr = vec![0; 8192];
+        chr[0x400 + 7] = 0xAB;
+        let mut ppu = Ppu::new(Mirroring::Vertical, chr);
+        ppu.cartridge_vram = vec![0; 0x400];
+        ppu.nametable_map = Some([
+            NametableSource::Ciram(1),
+            NametableSource::Ciram(1),
+            NametableSource::CartridgeVram(0),
+            NametableSource::Chr(1),
+        ]);
+        write_ppu(&mut ppu, 0x2000, 0x11);
+        assert_eq!(ppu.vram[0x400], 0x11);
+        assert_eq!(ppu.peek_vram(0x2400), 0x11);
+        write_ppu(&mut ppu, 0x2800, 0x22);
+        assert_eq!(ppu.cartridge_vram[0], 0x22);
+        // CHR ROM pages are read-only.
+        assert_eq!(ppu.peek_vram(0x2C07), 0xAB);
+        write_ppu(&mut ppu, 0x2C07, 0x33);
+        assert_eq!(ppu.peek_vram(0x2C07), 0xAB);
+    }
+
+    #[test]
+    fn test_region_frame_layout() {
+        for (region, vblank) in [(Region::Pal, 241), (Region::Dendy, 291)] {
+            let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+            ppu.region = region;
+            ppu.mask = 0x18; // Rendering on, which would skip a dot on NTSC
+            let run_lines = |ppu: &mut Ppu, lines: u16| {
+                for _ in 0..lines {
+                    ppu.tick(341);
+                }
+            };
+
+            run_lines(&mut ppu, vblank);
+            ppu.tick(1);
+            assert_eq!((ppu.scanline, ppu.cycle), (vblank, 1));
+            assert_eq!(ppu.status & 0x80, 0x80);
+
+            // The pre-render line is 311 and clears VBlank.
+            run_lines(&mut ppu, 311 - vblank);
+            assert_eq!((ppu.scanline, ppu.cycle), (311, 1));
+            assert_eq!(ppu.status & 0x80, 0x00);
+
+            // Frames are 312 lines long on both odd and even frames.
+            run_lines(&mut ppu, 2 * 312);
+            assert_eq!((ppu.scanline, ppu.cycle), (311, 1));
+        }
+    }
+
+    #[test]
+    fn test_emphasis_selects_palette_copy() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.palette[0] = 0x20;
+        ppu.scanline = 0;
+        ppu.cycle = 1;
+        ppu.mask = 0x08 | 0x80; // Background on, blue emphasis
+        ppu.render_pixel();
+        let (r, g, b) = ppu.output_palette.colors[0x100 | 0x20];
+        assert_eq!(&ppu.frame_buffer[..4], &[r, g, b, 0xFF]);
+        assert_eq!(ppu.frame_indices[0], 0x120);
+        assert!(b > r && b > g);
+
+        ppu.mask = 0x08 | 0x01 | 0xE0; // Greyscale with all emphasis bits
+        ppu.palette[0] = 0x16;
+        ppu.render_pixel();
+        let (r, g, b) = ppu.output_palette.colors[0x1C0 | 0x10];
+        assert_eq!(&ppu.frame_buffer[..4], &[r, g, b, 0xFF]);
+    }
+
+    #[test]
+    fn test_scanline_phases_drift() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        for _ in 0..262 {
+            ppu.tick(341);
+        }
+        // 341 dots of 8 phases each leave every line 4 phases further on.
+        for line in 1..240 {
+            let step = (12 + ppu.scanline_phases[line] - ppu.scanline_phases[line - 1]) % 12;
+            assert_eq!(step, 4);
+        }
+    }
+
+    /// Run sprite evaluation for `scanline` up to dot 257 with `oam`.
+    fn evaluate_line(oam: &[u8], scanline: u16) -> Ppu {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.oam[..oam.len()].copy_from_slice(oam);
+        ppu.mask = 0x18;
+        ppu.scanline = scanline;
+        ppu.cycle = 0;
+        ppu.tick(257);
+        ppu
+    }
+
+    #[test]
+    fn test_sprite_overflow() {
+        let mut oam = [0xF0; 256];
+        for sprite in oam.chunks_exact_mut(4).take(9) {
+            sprite.copy_from_slice(&[10, 1, 0, 0]);
+        }
+        let ppu = evaluate_line(&oam, 12);
+        assert_eq!(ppu.sprite_count, 8);
+        assert_eq!(ppu.status & 0x20, 0x20);
+        assert!(ppu.b_sprite_zero_hit_possible);
+
+        // Eight sprites fill secondary OAM without overflowing.
+        oam[32] = 0xF0;
+        let ppu = evaluate_line(&oam, 12);
+        assert_eq!(ppu.sprite_count, 8);
+        assert_eq!(ppu.status & 0x20, 0);
+    }
+
+    #[test]
+    fn test_unlimited_sprites_keep_hardware_flags() {
+        let mut oam = [0xF0; 256];
+        for (i, sprite) in oam.chunks_exact_mut(4).take(12).enumerate() {
+            sprite.copy_from_slice(&[10, i as u8, 0, 8 * i as u8]);
+        }
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.oam.copy_from_slice(&oam);
+        ppu.unlimited_sprites = true;
+        ppu.mask = 0x18;
+        ppu.scanline = 12;
+        ppu.cycle = 0;
+        ppu.tick(257);
+        assert_eq!(ppu.sprite_count, 12);
+        assert_eq!(&ppu.line_sprites[44..48], &[10, 11, 0, 88]);
+        assert_eq!(&ppu.secondary_oam[28..32], &[10, 7, 0, 56]);
+        assert_eq!(ppu.status & 0x20, 0x20);
+        assert!(ppu.b_sprite_zero_hit_possible);
+    }
+
+    #[test]
+    fn test_unlimited_sprites_with_misaligned_oam_addr() {
+        // From OAMADDR 1, evaluation reads tile bytes as Y: entries 0-7
+        // fill secondary OAM through their tiles, while entries 8-11 are the
+        // ones really in range.
+        let mut oam = [0xF0; 256];
+        for (i, sprite) in oam.chunks_exact_mut(4).take(12).enumerate() {
+            let x = 8 * i as u8;
+            if i < 8 {
+                sprite.copy_from_slice(&[0xF0, 10, 0, x]);
+            } else {
+                sprite.copy_from_slice(&[10, 0xF0, 0, x]);
+            }
+        }
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.oam.copy_from_slice(&oam);
+        ppu.unlimited_sprites = true;
+        ppu.mask = 0x18;
+        ppu.scanline = 12;
+        ppu.cycle = 0;
+        ppu.oam_addr = 1;
+        ppu.tick(257);
+        assert_eq!(ppu.sprite_count, 12);
+        let indices: Vec<u8> = (0..12).collect();
+        assert_eq!(&ppu.line_sprite_indices[..12], &indices[..]);
+        assert_eq!(&ppu.line_sprites[..4], &[10, 0, 0, 0xF0]);
+        assert_eq!(&ppu.line_sprites[32..48], &oam[32..48]);
+    }
+
+    #[test]
+    fn test_sprite_overflow_diagonal_scan() {
+        let mut oam = [0xF0; 256];
+        for sprite in oam.chunks_exact_mut(4).take(8) {
+            sprite.copy_from_slice(&[10, 1, 0, 0]);
+        }
+        // Sprite 8 misses, so sprite 9 is checked with its tile byte as Y.
+        oam[36..40].copy_from_slice(&[0xF0, 10, 0, 0]);
+        let ppu = evaluate_line(&oam, 12);
+        assert_eq!(ppu.status & 0x20, 0x20, "false positive");
+
+        // A real ninth sprite is missed when the byte read as Y is out of range.
+        oam[36..40].copy_from_slice(&[10, 0xF0, 0, 0]);
+        let ppu = evaluate_line(&oam, 12);
+        assert_eq!(ppu.status & 0x20, 0, "false negative");
+    }
+
+    #[test]
+    fn test_sprite_evaluation_starts_at_oam_addr() {
+        let mut oam = [0xF0; 256];
+        oam[8..12].copy_from_slice(&[10, 2, 3, 4]);
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.oam.copy_from_slice(&oam);
+        ppu.mask = 0x18;
+        ppu.scanline = 12;
+        ppu.cycle = 0;
+        ppu.oam_addr = 8;
+        ppu.tick(257);
+        assert_eq!(ppu.sprite_count, 1);
+        assert_eq!(&ppu.secondary_oam[..4], &[10, 2, 3, 4]);
+        assert!(ppu.b_sprite_zero_hit_possible);
+        assert_eq!(ppu.oam_addr, 0);
+    }
+
+    #[test]
+    fn test_oam_data_reads_ff_while_clearing() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.oam[0] = 0x42;
+        ppu.scanline = 12;
+        ppu.cycle = 0;
+        ppu.tick(10);
+        assert_eq!(ppu.read_register(0x2004), 0x42); // Rendering off
+
+        ppu.mask = 0x18;
+        assert_eq!(ppu.read_register(0x2004), 0xFF);
+    }
+
+    #[test]
+    fn test_oam_access_during_rendering() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.oam[..8].copy_from_slice(&[10, 0x11, 0x22, 0x33, 0x80, 0x44, 0x55, 0x66]);
+        ppu.mask = 0x18;
+        ppu.scanline = 12;
+        ppu.cycle = 0;
+        ppu.tick(64);
+
+        // Evaluation copies sprite 0 and skips sprite 1; reads follow it.
+        let mut bus = Vec::new();
+        for _ in 0..8 {
+            ppu.tick(1);
+            bus.push(ppu.read_register(0x2004));
+        }
+        assert_eq!(bus, [10, 10, 0x11, 0x11, 0x22, 0x22, 0x33, 0x33]);
+        ppu.tick(2);
+        assert_eq!(ppu.read_register(0x2004), 0x80);
+
+        // Sprite fetches read secondary OAM, repeating X.
+        ppu.cycle = 256;
+        ppu.tick(8);
+        assert_eq!(ppu.read_register(0x2004), 0x33);
+        ppu.tick(2);
+        assert_eq!(ppu.read_register(0x2004), 0xFF);
+
+        // Writes are dropped and bump OAMADDR to the next sprite.
+        ppu.cycle = 330;
+        ppu.oam_addr = 0x05;
+        ppu.write_register(0x2004, 0x99);
+        assert_eq!(ppu.oam_addr, 0x09);
+        assert!(!ppu.oam.contains(&0x99));
+    }
+
+    #[test]
+    fn test_data_access_during_rendering_bumps_scroll() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.v = (3 << 12) | (5 << 5) | 31; // Fine Y 3, coarse Y 5, coarse X 31
+        ppu.mask = 0x18;
+        ppu.scanline = 20;
+        ppu.cycle = 340;
+        ppu.read_register(0x2007);
+        assert_eq!(ppu.v, 0x0400 | (4 << 12) | (5 << 5));
+        ppu.write_register(0x2007, 0x12);
+        assert_eq!(ppu.v, 0x0400 | (5 << 12) | (5 << 5) | 1);
+
+        // Outside rendering the usual +1/+32 applies.
+        ppu.scanline = 241;
+        ppu.write_register(0x2007, 0x12);
+        assert_eq!(ppu.v, 0x0400 | (5 << 12) | (5 << 5) | 2);
+    }
+
+    #[test]
+    fn test_open_bus_latch_and_decay() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.write_register(0x2003, 0x1F);
+        // Write-only registers and the low bits of PPUSTATUS read the latch.
+        assert_eq!(ppu.read_register(0x2000), 0x1F);
+        ppu.status = 0x80;
+        assert_eq!(ppu.read_register(0x2002), 0x9F);
+        assert_eq!(ppu.peek_register(0x2005), 0x9F);
+
+        // Bits fade once left alone for about 600 ms...
+        ppu.dot_count += OPEN_BUS_DECAY_DOTS + 1;
+        assert_eq!(ppu.read_register(0x2000), 0x00);
+
+        // ...but each read only refreshes the bits it drives.
+        ppu.write_register(0x2003, 0xFF);
+        ppu.dot_count += OPEN_BUS_DECAY_DOTS / 2;
+        ppu.status = 0xE0;
+        assert_eq!(ppu.read_register(0x2002), 0xFF);
+        ppu.dot_count += OPEN_BUS_DECAY_DOTS / 2 + 1;
+        assert_eq!(ppu.read_register(0x2001), 0xE0);
+    }
+
+    #[test]
+    fn test_palette_read_high_bits_from_latch() {
+        let mut ppu = Ppu::new(Mirroring::Horizontal, vec![0; 8192]);
+        ppu.palette[1] = 0x2A;
+        ppu.write_register(0x2006, 0x3F);
+        ppu.write_register(0x2006, 0x01);
+        ppu.write_register(0x2001, 0xC0);
+        assert_eq!(ppu.read_register(0x2007), 0xEA);
+    }
 }
@@ -0,0 +1,132 @@
+use crate::clock::MasterClock;
+use std::fmt;
+use std::str::FromStr;
+
+/// Console timing. NTSC and PAL are Nintendo's consoles; Dendy is the common
+/// famiclone that combines PAL's 50 Hz, 312-line frame with NTSC's 3:1
+/// CPU/PPU ratio and APU tables, and starts vblank 50 lines later.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    /// Timing from NES 2.0 header byte 12. Multi-region games run as NTSC.
+    pub fn from_nes2_timing(byte: u8) -> Self {
+        match byte & 0x03 {
+            1 => Region::Pal,
+            3 => Region::Dendy,
+            _ => Region::Ntsc,
+        }
+    }
+
+    pub fn master_clock(self) -> MasterClock {
+        match self {
+            Region::Ntsc => MasterClock::ntsc(),
+            Region::Pal => MasterClock::pal(),
+            // Same crystal as PAL, but the CPU divides by 15 for a 3:1 ratio.
+            Region::Dendy => MasterClock::new(26_601_712.0, 15, 5),
+        }
+    }
+
+    /// Scanlines per frame, counting vblank and the pre-render line.
+    pub fn scanlines(self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    pub fn pre_render_scanline(self) -> u16 {
+        self.scanlines() - 1
+    }
+
+    /// Scanline whose dot 1 sets the VBlank flag.
+    pub fn vblank_scanline(self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// Only the NTSC PPU skips a dot on odd frames while rendering.
+    pub fn skips_odd_frame_dot(self) -> bool {
+        self == Region::Ntsc
+    }
+
+    /// Whether the APU uses the PAL frame counter and noise/DMC period tables.
+    pub fn has_pal_apu(self) -> bool {
+        self == Region::Pal
+    }
+
+    /// CPU cycles per frame, rounded up.
+    pub fn cpu_cycles_per_frame(self) -> u32 {
+        match self {
+            Region::Ntsc => 29781,
+            Region::Pal => 33248,
+            Region::Dendy => 35464,
+        }
+    }
+
+    /// Frames per second, from the PPU dot rate and frame length.
+    pub fn frame_rate(self) -> f64 {
+        let clock = self.master_clock();
+        let mut dots = 341.0 * self.scanlines() as f64;
+        if self.skips_odd_frame_dot() {
+            dots -= 0.5;
+        }
+        clock.master_hz / clock.ppu_divider as f64 / dots
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(format!(
+                "Unknown region '{}' (expected ntsc, pal or dendy)",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_rates() {
+        assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 0.001);
+        assert!((Region::Pal.frame_rate() - 50.0070).abs() < 0.001);
+        assert!((Region::Dendy.frame_rate() - 50.0070).abs() < 0.001);
+        assert!((Region::Pal.master_clock().cpu_hz() - 1_662_607.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_cycles_per_frame_match_clock() {
+        for region in [Region::Ntsc, Region::Pal, Region::Dendy] {
+            let cycles = region.master_clock().cpu_hz() / region.frame_rate();
+            let rounded = region.cpu_cycles_per_frame() as f64;
+            assert!((-1e-6..=0.5).contains(&(rounded - cycles)), "{}", region);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::cartridge::{self, Mirroring, Rom, RomError, SUPPORTED_MAPPERS};
+use crate::checksum::{crc32, sha1, to_hex};
+use crate::game_db::{self, GameDbEntry};
+use crate::region::Region;
+use std::fmt;
+
+/// Everything `--info` reports about a cartridge image: the header as
+/// written, hashes of its contents and what the game database corrects.
+pub struct RomInfo {
+    /// "iNES", "NES 2.0" or "iNES (dirty header)".
+    pub format: &'static str,
+    pub has_trainer: bool,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    /// Board settings exactly as the header states them.
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    pub has_battery: bool,
+    pub region: Region,
+    pub prg_crc32: u32,
+    pub chr_crc32: u32,
+    /// CRC-32 of PRG followed by CHR, the game database key.
+    pub rom_crc32: u32,
+    pub prg_sha1: [u8; 20],
+    pub chr_sha1: [u8; 20],
+    pub rom_sha1: [u8; 20],
+    /// Board name for the mapper the game will actually run with.
+    pub board: String,
+    pub db_entry: Option<GameDbEntry>,
+    pub warnings: Vec<String>,
+}
+
+/// Inspect a cartridge image without loading it into a `Nes`.
+pub fn rom_info(raw: &[u8]) -> Result<RomInfo, RomError> {
+    let rom = Rom::parse(raw)?;
+    let nes2 = (raw[7] >> 2) & 0b11 == 2;
+    let dirty = cartridge::has_dirty_header(raw);
+    let has_trainer = raw[6] & 0b0100 != 0;
+
+    let rom_crc32 = game_db::rom_crc32(&rom.prg_rom, &rom.chr_rom);
+    let db_entry = game_db::lookup_crc32(rom_crc32);
+
+    let mut warnings = Vec::new();
+    if dirty {
+        warnings.push(
+            "Header bytes 7-15 hold junk (e.g. \"DiskDude!\"); ignored the upper mapper \
+             nibble, PRG RAM size and TV system"
+                .to_string(),
+        );
+    }
+    if let Some(entry) = &db_entry {
+        for (field, header, db) in entry.differences(&rom) {
+            warnings.push(format!(
+                "Header {} is {}, database says {}",
+                field, header, db
+            ));
+        }
+    }
+    let used = 16 + if has_trainer { 512 } else { 0 } + rom.prg_rom.len() + rom.chr_rom.len();
+    if raw.len() > used {
+        warnings.push(format!(
+            "{} bytes of trailing data after CHR ROM",
+            raw.len() - used
+        ));
+    }
+    let mapper = db_entry.as_ref().map_or(rom.mapper, |entry| entry.mapper);
+    if !SUPPORTED_MAPPERS.contains(&mapper) {
+        warnings.push(format!("Mapper {} is not supported", mapper));
+    }
+
+    let mut rom_data = rom.prg_rom.clone();
+    rom_data.extend_from_slice(&rom.chr_rom);
+
+    Ok(RomInfo {
+        format: match (nes2, dirty) {
+            (true, _) => "NES 2.0",
+            (false, true) => "iNES (dirty header)",
+            (false, false) => "iNES",
+        },
+        has_trainer,
+        prg_rom_size: rom.prg_rom.len(),
+        chr_rom_size: rom.chr_rom.len(),
+        prg_ram_size: rom.prg_ram_size,
+        mapper: rom.mapper,
+        mirroring: rom.screen_mirroring,
+        has_battery: rom.has_battery,
+        region: rom.region,
+        prg_crc32: crc32(&rom.prg_rom),
+        chr_crc32: crc32(&rom.chr_rom),
+        rom_crc32,
+        prg_sha1: sha1(&rom.prg_rom),
+        chr_sha1: sha1(&rom.chr_rom),
+        rom_sha1: sha1(&rom_data),
+        board: board_name(mapper, rom.prg_rom.len()),
+        db_entry,
+        warnings,
+    })
+}
+
+fn board_name(mapper: u8, prg_rom_size: usize) -> String {
+    match mapper {
+        0 if prg_rom_size <= 0x4000 => "NROM-128".to_string(),
+        0 => "NROM-256".to_string(),
+        1 => "SxROM (MMC1)".to_string(),
+        2 => "UxROM".to_string(),
+        3 => "CNROM".to_string(),
+        4 => "TxROM (MMC3)".to_string(),
+        _ => format!("Mapper {}", mapper),
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Format:     {}", self.format)?;
+        if let Some(entry) = &self.db_entry {
+            writeln!(f, "Game:       {}", entry.name)?;
+        }
+        writeln!(f, "Board:      {}", self.board)?;
+        writeln!(f, "Mapper:     {}", self.mapper)?;
+        writeln!(f, "Mirroring:  {:?}", self.mirroring)?;
+        writeln!(
+            f,
+            "Battery:    {}",
+            if self.has_battery { "yes" } else { "no" }
+        )?;
+        writeln!(f, "Region:     {}", self.region)?;
+        writeln!(
+            f,
+            "Trainer:    {}",
+            if self.has_trainer { "yes" } else { "no" }
+        )?;
+        writeln!(f, "PRG ROM:    {} KiB", self.prg_rom_size / 1024)?;
+        if self.chr_rom_size == 0 {
+            writeln!(f, "CHR ROM:    none (8 KiB CHR RAM)")?;
+        } else {
+            writeln!(f, "CHR ROM:    {} KiB", self.chr_rom_size / 1024)?;
+        }
+        writeln!(f, "PRG RAM:    {} KiB", self.prg_ram_size / 1024)?;
+        writeln!(
+            f,
+            "PRG CRC32:  {:08X}  SHA-1: {}",
+            self.prg_crc32,
+            to_hex(&self.prg_sha1)
+        )?;
+        writeln!(
+            f,
+            "CHR CRC32:  {:08X}  SHA-1: {}",
+            self.chr_crc32,
+            to_hex(&self.chr_sha1)
+        )?;
+        writeln!(
+            f,
+            "ROM CRC32:  {:08X}  SHA-1: {}",
+            self.rom_crc32,
+            to_hex(&self.rom_sha1)
+        )?;
+        if self.db_entry.is_none() {
+            writeln!(f, "Database:   no match")?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "Warning:    {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_nrom;
+
+    fn build_rom(flags_6: u8) -> Vec<u8> {
+        let mut raw = test_nrom(1, &[]);
+        raw[6] = flags_6;
+        raw
+    }
+
+    #[test]
+    fn test_reports_header_and_hashes() {
+        let raw = build_rom(0x13); // Mapper 1, battery, vertical
+        let info = rom_info(&raw).unwrap();
+        assert_eq!(info.format, "iNES");
+        assert_eq!(info.board, "SxROM (MMC1)");
+        assert_eq!(info.mirroring, Mirroring::Vertical);
+        assert!(info.has_battery);
+        assert_eq!(info.prg_crc32, crc32(&raw[16..16 + 0x4000]));
+        assert_eq!(info.chr_sha1, sha1(&[0; 0x2000]));
+        assert!(info.db_entry.is_none());
+        assert!(info.warnings.is_empty());
+        assert!(info.to_string().contains("Board:      SxROM (MMC1)"));
+    }
+
+    #[test]
+    fn test_dirty_header_ignores_junk() {
+        // "DiskDude!" over bytes 7-15: byte 7 'D' would read as mapper 0x40.
+        let mut raw = build_rom(0x20);
+        raw[7..16].copy_from_slice(b"DiskDude!");
+        let info = rom_info(&raw).unwrap();
+        assert_eq!(info.format, "iNES (dirty header)");
+        assert_eq!(info.mapper, 2);
+        assert_eq!(info.region, Region::Ntsc);
+        assert_eq!(info.warnings.len(), 1);
+        assert_eq!(Rom::new(&raw).unwrap().mapper, 2);
+    }
+
+    #[test]
+    fn test_warns_about_unsupported_mapper_and_trailing_data() {
+        let mut raw = build_rom(0x50);
+        raw.extend_from_slice(&[0xFF; 32]);
+        let info = rom_info(&raw).unwrap();
+        assert_eq!(info.board, "Mapper 5");
+        assert_eq!(info.warnings.len(), 2);
+    }
+}
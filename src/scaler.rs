@@ -0,0 +1,280 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Pixel-art upscalers for the RGBA frame buffer, all run on the CPU.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Scaler {
+    #[default]
+    None,
+    /// AdvMAME2x/EPX: copies a neighbor into a corner where two agree.
+    Scale2x,
+    /// AdvMAME3x.
+    Scale3x,
+    /// xBR level 1 at 2x only (no higher levels or factors): picks the dominant edge direction at each corner
+    /// from a 21-pixel neighborhood and blends along it.
+    Xbr2x,
+}
+
+type Rgba = [u8; 4];
+
+impl Scaler {
+    pub const ALL: [Scaler; 4] = [
+        Scaler::None,
+        Scaler::Scale2x,
+        Scaler::Scale3x,
+        Scaler::Xbr2x,
+    ];
+
+    /// Output pixels per input pixel along each axis.
+    pub fn factor(self) -> usize {
+        match self {
+            Scaler::None => 1,
+            Scaler::Scale2x | Scaler::Xbr2x => 2,
+            Scaler::Scale3x => 3,
+        }
+    }
+
+    /// Scale a `width` x `height` RGBA image.
+    pub fn apply(self, input: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let factor = self.factor();
+        let mut output = vec![0; input.len() * factor * factor];
+        self.apply_into(input, width, height, &mut output);
+        output
+    }
+
+    /// `apply` into a caller-provided buffer, such as `Pixels::frame_mut`.
+    /// `output` must hold `width * height * factor^2` RGBA pixels.
+    pub fn apply_into(self, input: &[u8], width: usize, height: usize, output: &mut [u8]) {
+        let n = self.factor();
+        let image = Image {
+            pixels: input,
+            width,
+            height,
+        };
+        let out_width = width * n;
+        let mut block = [[0u8; 4]; 9];
+        for y in 0..height {
+            for x in 0..width {
+                match self {
+                    Scaler::None => block[0] = image.get(x, y, 0, 0),
+                    Scaler::Scale2x => scale2x(&image, x, y, &mut block),
+                    Scaler::Scale3x => scale3x(&image, x, y, &mut block),
+                    Scaler::Xbr2x => xbr2x(&image, x, y, &mut block),
+                }
+                for sy in 0..n {
+                    let row = (y * n + sy) * out_width + x * n;
+                    for sx in 0..n {
+                        let idx = (row + sx) * 4;
+                        output[idx..idx + 4].copy_from_slice(&block[sy * n + sx]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Scaler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Scaler::None => "none",
+            Scaler::Scale2x => "scale2x",
+            Scaler::Scale3x => "scale3x",
+            Scaler::Xbr2x => "xbr",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Scaler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let name = match s.to_ascii_lowercase().as_str() {
+            "xbr2x" => "xbr".to_string(),
+            name => name.to_string(),
+        };
+        Scaler::ALL
+            .into_iter()
+            .find(|scaler| scaler.to_string() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown scaler '{}' (expected none, scale2x, scale3x or xbr)",
+                    s
+                )
+            })
+    }
+}
+
+struct Image<'a> {
+    pixels: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Image<'_> {
+    /// Pixel at (`x` + `dx`, `y` + `dy`), clamped to the image edges.
+    fn get(&self, x: usize, y: usize, dx: isize, dy: isize) -> Rgba {
+        let x = (x as isize + dx).clamp(0, self.width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, self.height as isize - 1) as usize;
+        let idx = (y * self.width + x) * 4;
+        self.pixels[idx..idx + 4].try_into().unwrap()
+    }
+}
+
+fn scale2x(image: &Image, x: usize, y: usize, out: &mut [Rgba; 9]) {
+    let px = |dx, dy| image.get(x, y, dx, dy);
+    let (b, d, e, f, h) = (px(0, -1), px(-1, 0), px(0, 0), px(1, 0), px(0, 1));
+    *out = [e; 9];
+    if b != h && d != f {
+        out[0] = if d == b { d } else { e };
+        out[1] = if b == f { f } else { e };
+        out[2] = if d == h { d } else { e };
+        out[3] = if h == f { f } else { e };
+    }
+}
+
+fn scale3x(image: &Image, x: usize, y: usize, out: &mut [Rgba; 9]) {
+    let px = |dx, dy| image.get(x, y, dx, dy);
+    let (a, b, c) = (px(-1, -1), px(0, -1), px(1, -1));
+    let (d, e, f) = (px(-1, 0), px(0, 0), px(1, 0));
+    let (g, h, i) = (px(-1, 1), px(0, 1), px(1, 1));
+    *out = [e; 9];
+    if b != h && d != f {
+        let pick = |cond: bool, color: Rgba| if cond { color } else { e };
+        out[0] = pick(d == b, d);
+        out[1] = pick((d == b && e != c) || (b == f && e != a), b);
+        out[2] = pick(b == f, f);
+        out[3] = pick((d == b && e != g) || (d == h && e != a), d);
+        out[5] = pick((b == f && e != i) || (h == f && e != c), f);
+        out[6] = pick(d == h, d);
+        out[7] = pick((d == h && e != i) || (h == f && e != g), h);
+        out[8] = pick(h == f, f);
+    }
+}
+
+fn yuv(color: Rgba) -> (i32, i32, i32) {
+    let (r, g, b) = (color[0] as i32, color[1] as i32, color[2] as i32);
+    (
+        (299 * r + 587 * g + 114 * b) / 1000,
+        (-169 * r - 331 * g + 500 * b) / 1000,
+        (500 * r - 419 * g - 81 * b) / 1000,
+    )
+}
+
+/// Weighted average of `colors`.
+fn blend(colors: &[(Rgba, u32)]) -> Rgba {
+    let total: u32 = colors.iter().map(|&(_, weight)| weight).sum();
+    let mut out = [0xFF; 4];
+    for (channel, value) in out.iter_mut().enumerate().take(3) {
+        let sum: u32 = colors
+            .iter()
+            .map(|&(color, weight)| color[channel] as u32 * weight)
+            .sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+    out
+}
+
+/// xBR's color distance: YUV differences weighted 48:7:6.
+fn distance(a: Rgba, b: Rgba) -> i32 {
+    let (ya, ua, va) = yuv(a);
+    let (yb, ub, vb) = yuv(b);
+    48 * (ya - yb).abs() + 7 * (ua - ub).abs() + 6 * (va - vb).abs()
+}
+
+fn xbr2x(image: &Image, x: usize, y: usize, out: &mut [Rgba; 9]) {
+    let e = image.get(x, y, 0, 0);
+    for (slot, (sx, sy)) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].into_iter().enumerate() {
+        // Mirror the neighborhood so the corner being filled is bottom-right.
+        let px = |dx: isize, dy: isize| image.get(x, y, dx * sx, dy * sy);
+        let (b, c, d, f, g) = (px(0, -1), px(1, -1), px(-1, 0), px(1, 0), px(-1, 1));
+        let (h, i) = (px(0, 1), px(1, 1));
+        let (f4, i4, h5, i5) = (px(2, 0), px(2, 1), px(0, 2), px(1, 2));
+
+        // Edge strength along the F-H diagonal versus across it (E-I).
+        let along = distance(e, c)
+            + distance(e, g)
+            + distance(i, f4)
+            + distance(i, h5)
+            + 4 * distance(h, f);
+        let across = distance(h, d)
+            + distance(h, i5)
+            + distance(f, i4)
+            + distance(f, b)
+            + 4 * distance(e, i);
+        out[slot] = if along < across {
+            let nearest = if distance(e, f) <= distance(e, h) {
+                f
+            } else {
+                h
+            };
+            blend(&[(e, 1), (nearest, 1)])
+        } else {
+            e
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba = [0, 0, 0, 0xFF];
+    const WHITE: Rgba = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    /// 4x4 image, white below the main diagonal.
+    fn staircase() -> Vec<u8> {
+        (0..16)
+            .flat_map(|i| if i % 4 < i / 4 { WHITE } else { BLACK })
+            .collect()
+    }
+
+    fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> Rgba {
+        let idx = (y * width + x) * 4;
+        image[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_flat_image_is_unchanged() {
+        let input: Vec<u8> = [0x12, 0x34, 0x56, 0xFF].repeat(16);
+        for scaler in Scaler::ALL {
+            let n = scaler.factor();
+            let output = scaler.apply(&input, 4, 4);
+            assert_eq!(output.len(), 16 * n * n * 4);
+            assert_eq!(
+                output,
+                [0x12, 0x34, 0x56, 0xFF].repeat(16 * n * n),
+                "{}",
+                scaler
+            );
+        }
+    }
+
+    #[test]
+    fn test_scale2x_fills_diagonal() {
+        let output = Scaler::Scale2x.apply(&staircase(), 4, 4);
+        // Pixel (1, 2) is white with black above and to its right; its
+        // top-right quarter becomes black to smooth the step.
+        assert_eq!(pixel(&output, 8, 2, 4), WHITE);
+        assert_eq!(pixel(&output, 8, 3, 4), BLACK);
+        assert_eq!(pixel(&output, 8, 3, 5), WHITE);
+    }
+
+    #[test]
+    fn test_xbr_blends_edges() {
+        let output = Scaler::Xbr2x.apply(&staircase(), 4, 4);
+        // Corner of pixel (1, 2) facing the black pixels above and right.
+        let corner = pixel(&output, 8, 3, 4);
+        assert!(corner != WHITE && corner != BLACK, "{:?}", corner);
+        assert_eq!(pixel(&output, 8, 0, 7), WHITE);
+    }
+
+    #[test]
+    fn test_scaler_names() {
+        for scaler in Scaler::ALL {
+            assert_eq!(scaler.to_string().parse::<Scaler>(), Ok(scaler));
+        }
+        assert_eq!("XBR2x".parse::<Scaler>(), Ok(Scaler::Xbr2x));
+        assert!("super-eagle".parse::<Scaler>().is_err());
+    }
+}
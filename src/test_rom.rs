@@ -0,0 +1,190 @@
+use crate::cartridge::RomError;
+use crate::Nes;
+
+/// Signature written to $6001-$6003 by test ROMs that follow the blargg protocol.
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUEST: u8 = 0x81;
+
+/// Frames to wait before honouring a reset request. The protocol asks for at
+/// least 100 ms so the ROM can finish writing to battery RAM.
+const RESET_DELAY_FRAMES: u32 = 8;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TestRomStatus {
+    /// The ROM reported result code 0.
+    Passed,
+    /// The ROM reported a non-zero result code.
+    Failed(u8),
+    /// The ROM never finished within the frame budget.
+    Timeout,
+    /// The $DE $B0 $61 signature never appeared at $6001.
+    NoSignature,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestRomResult {
+    pub status: TestRomStatus,
+    /// ASCII text the ROM left at $6004 (NUL-terminated).
+    pub message: String,
+    pub frames: u32,
+    pub resets: u32,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.status == TestRomStatus::Passed
+    }
+}
+
+/// Run a test ROM that reports through $6000 until it finishes or `max_frames`
+/// have elapsed. Reset requests ($81) are honoured after a short delay.
+pub fn run_test_rom(rom_data: &[u8], max_frames: u32) -> Result<TestRomResult, RomError> {
+    let mut nes = Nes::new_with_rom(rom_data)?;
+    nes.reset();
+
+    let mut seen_signature = false;
+    let mut reset_countdown: Option<u32> = None;
+    let mut resets = 0;
+
+    for frame in 0..max_frames {
+        nes.run_frame();
+
+        if !has_signature(&nes) {
+            continue;
+        }
+        seen_signature = true;
+
+        match nes.bus.peek(0x6000) {
+            STATUS_RUNNING => {}
+            STATUS_RESET_REQUEST => match reset_countdown {
+                Some(0) => {
+                    // Clear the request so the same one isn't honoured twice
+                    // if the ROM is slow to update the status after reset.
+                    nes.bus.poke(0x6000, STATUS_RUNNING);
+                    nes.reset();
+                    resets += 1;
+                    reset_countdown = None;
+                }
+                Some(n) => reset_countdown = Some(n - 1),
+                None => reset_countdown = Some(RESET_DELAY_FRAMES),
+            },
+            code => {
+                return Ok(TestRomResult {
+                    status: if code == 0 {
+                        TestRomStatus::Passed
+                    } else {
+                        TestRomStatus::Failed(code)
+                    },
+                    message: read_message(&nes),
+                    frames: frame + 1,
+                    resets,
+                });
+            }
+        }
+    }
+
+    Ok(TestRomResult {
+        status: if seen_signature {
+            TestRomStatus::Timeout
+        } else {
+            TestRomStatus::NoSignature
+        },
+        message: read_message(&nes),
+        frames: max_frames,
+        resets,
+    })
+}
+
+fn has_signature(nes: &Nes) -> bool {
+    (0..3).all(|i| nes.bus.peek(0x6001 + i) == SIGNATURE[i as usize])
+}
+
+fn read_message(nes: &Nes) -> String {
+    let mut message = String::new();
+    for addr in 0x6004..0x8000u16 {
+        let byte = nes.bus.peek(addr);
+        if byte == 0 {
+            break;
+        }
+        message.push(byte as char);
+    }
+    message.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_nrom;
+
+    /// Build an NROM image whose reset handler at $C000 runs `code`.
+    fn build_rom(code: &[u8]) -> Vec<u8> {
+        test_nrom(2, code)
+    }
+
+    /// Code that writes the signature, the message "OK" and `status` to $6000.
+    fn report(status: u8) -> Vec<u8> {
+        vec![
+            0xA9, 0xDE, 0x8D, 0x01, 0x60, // LDA #$DE; STA $6001
+            0xA9, 0xB0, 0x8D, 0x02, 0x60, // LDA #$B0; STA $6002
+            0xA9, 0x61, 0x8D, 0x03, 0x60, // LDA #$61; STA $6003
+            0xA9, b'O', 0x8D, 0x04, 0x60, // LDA #'O'; STA $6004
+            0xA9, b'K', 0x8D, 0x05, 0x60, // LDA #'K'; STA $6005
+            0xA9, 0x00, 0x8D, 0x06, 0x60, // LDA #0;   STA $6006
+            0xA9, status, 0x8D, 0x00, 0x60, // LDA #status; STA $6000
+        ]
+    }
+
+    /// Append `JMP *` assuming `code` is placed at `origin`.
+    fn with_idle_loop_at(mut code: Vec<u8>, origin: u16) -> Vec<u8> {
+        let addr = origin + code.len() as u16;
+        code.extend_from_slice(&[0x4C, addr as u8, (addr >> 8) as u8]);
+        code
+    }
+
+    fn with_idle_loop(code: Vec<u8>) -> Vec<u8> {
+        with_idle_loop_at(code, 0xC000)
+    }
+
+    #[test]
+    fn test_reports_pass_and_message() {
+        let rom = build_rom(&with_idle_loop(report(0x00)));
+        let result = run_test_rom(&rom, 10).unwrap();
+        assert_eq!(result.status, TestRomStatus::Passed);
+        assert_eq!(result.message, "OK");
+    }
+
+    #[test]
+    fn test_reports_failure_code() {
+        let rom = build_rom(&with_idle_loop(report(0x03)));
+        let result = run_test_rom(&rom, 10).unwrap();
+        assert_eq!(result.status, TestRomStatus::Failed(3));
+    }
+
+    #[test]
+    fn test_missing_signature() {
+        let rom = build_rom(&with_idle_loop(vec![]));
+        let result = run_test_rom(&rom, 5).unwrap();
+        assert_eq!(result.status, TestRomStatus::NoSignature);
+    }
+
+    #[test]
+    fn test_honours_reset_request() {
+        // First boot: bump $6100 and request a reset. Second boot: report pass.
+        let mut code = vec![
+            0xAD, 0x00, 0x61, // LDA $6100
+            0xD0, 0x29, // BNE second_boot
+            0xEE, 0x00, 0x61, // INC $6100
+        ];
+        code.extend(with_idle_loop_at(report(STATUS_RESET_REQUEST), 0xC008));
+        let second_boot = 0xC000 + code.len() as u16;
+        assert_eq!(second_boot, 0xC005 + 0x29);
+        code.extend(with_idle_loop_at(report(0x00), second_boot));
+        let rom = build_rom(&code);
+
+        let result = run_test_rom(&rom, 60).unwrap();
+        assert_eq!(result.status, TestRomStatus::Passed);
+        assert_eq!(result.resets, 1);
+    }
+}
@@ -0,0 +1,69 @@
+use rust_emu::test_rom::{run_test_rom, TestRomStatus};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_FRAME_BUDGET: u32 = 3600;
+
+fn collect_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_roms(&path, roms);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("nes"))
+        {
+            roms.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_roms_report_pass() {
+    let dir = std::env::var("TEST_ROM_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms"));
+    let frames = std::env::var("TEST_ROM_FRAMES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_FRAME_BUDGET);
+
+    let mut roms = Vec::new();
+    collect_roms(&dir, &mut roms);
+    roms.sort();
+    if roms.is_empty() {
+        println!("No test ROMs found in {}, skipping", dir.display());
+        return;
+    }
+
+    let mut failures = Vec::new();
+    for path in &roms {
+        let rom_data = std::fs::read(path).expect("failed to read test ROM");
+        let name = path.strip_prefix(&dir).unwrap_or(path).display();
+        let result = match run_test_rom(&rom_data, frames) {
+            Ok(result) => result,
+            Err(err) => {
+                println!("FAIL {}: {}", name, err);
+                failures.push(format!("{}: {}", name, err));
+                continue;
+            }
+        };
+        match result.status {
+            TestRomStatus::Passed => println!("PASS {}", name),
+            status => {
+                println!("FAIL {} ({:?}): {}", name, status, result.message);
+                failures.push(format!("{} ({:?}): {}", name, status, result.message));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} test ROMs failed:\n{}",
+        failures.len(),
+        roms.len(),
+        failures.join("\n")
+    );
+}